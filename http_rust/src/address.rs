@@ -0,0 +1,208 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::Json,
+};
+use solana_sdk::{
+    account::Account,
+    pubkey,
+    pubkey::{Pubkey, MAX_SEEDS, MAX_SEED_LEN},
+    system_program, sysvar,
+};
+use tracing::info;
+
+use crate::{
+    cluster::ClusterRpc,
+    decode::{METADATA_PROGRAM_ID, METADATA_PROGRAM_NAME},
+    error::AppError,
+    idl::IdlRegistry,
+    types::*,
+    AppState,
+};
+
+/// Programs by the name `parsed.program` gives them: the transaction-status parser's name
+/// where it has one (`system`, `spl-token`, ...), a kebab-case name in the same style otherwise
+const KNOWN_PROGRAMS: &[(Pubkey, &str)] = &[
+    (system_program::ID, "system"),
+    (pubkey!("Vote111111111111111111111111111111111111111"), "vote"),
+    (pubkey!("Stake11111111111111111111111111111111111111"), "stake"),
+    (pubkey!("Config1111111111111111111111111111111111111"), "config"),
+    (pubkey!("ComputeBudget111111111111111111111111111111"), "compute-budget"),
+    (pubkey!("AddressLookupTab1e1111111111111111111111111"), "address-lookup-table"),
+    (pubkey!("BPFLoader1111111111111111111111111111111111"), "bpf-loader-deprecated"),
+    (pubkey!("BPFLoader2111111111111111111111111111111111"), "bpf-loader"),
+    (pubkey!("BPFLoaderUpgradeab1e11111111111111111111111"), "bpf-upgradeable-loader"),
+    (pubkey!("LoaderV411111111111111111111111111111111111"), "loader-v4"),
+    (pubkey!("Ed25519SigVerify111111111111111111111111111"), "ed25519"),
+    (pubkey!("KeccakSecp256k11111111111111111111111111111"), "secp256k1"),
+    (spl_token::ID, "spl-token"),
+    (spl_token_2022::ID, "spl-token-2022"),
+    (spl_associated_token_account::ID, "spl-associated-token-account"),
+    (pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr"), "spl-memo"),
+    (pubkey!("Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo"), "spl-memo"),
+    (METADATA_PROGRAM_ID, METADATA_PROGRAM_NAME),
+    (pubkey!("CoREENxT6tW1HoK8ypY1SxRMZTcVPm7R94rH4PZNhX7d"), "mpl-core"),
+];
+
+/// Named like the `jsonParsed` account types
+const SYSVARS: &[(Pubkey, &str)] = &[
+    (sysvar::clock::ID, "clock"),
+    (sysvar::epoch_rewards::ID, "epochRewards"),
+    (sysvar::epoch_schedule::ID, "epochSchedule"),
+    (sysvar::fees::ID, "fees"),
+    (sysvar::instructions::ID, "instructions"),
+    (sysvar::last_restart_slot::ID, "lastRestartSlot"),
+    (sysvar::recent_blockhashes::ID, "recentBlockhashes"),
+    (sysvar::rent::ID, "rent"),
+    (sysvar::rewards::ID, "rewards"),
+    (sysvar::slot_hashes::ID, "slotHashes"),
+    (sysvar::slot_history::ID, "slotHistory"),
+    (sysvar::stake_history::ID, "stakeHistory"),
+];
+
+pub async fn derive_pda(Json(pda_req): Json<PdaRequest>) -> Result<Json<PdaResponse>, AppError> {
+    let program_id = validate_pubkey(&pda_req.program_id)?;
+    let seeds = pda_req.seeds.iter().map(seed_bytes).collect::<Result<Vec<_>, _>>()?;
+
+    // The bump seed takes the last slot
+    if seeds.len() >= MAX_SEEDS {
+        return Err(AppError::BadRequest(format!("At most {} seeds are allowed", MAX_SEEDS - 1)));
+    }
+    if let Some(index) = seeds.iter().position(|seed| seed.len() > MAX_SEED_LEN) {
+        return Err(AppError::BadRequest(format!(
+            "Seed {} is {} bytes; seeds are at most {}",
+            index,
+            seeds[index].len(),
+            MAX_SEED_LEN
+        )));
+    }
+
+    let seeds: Vec<&[u8]> = seeds.iter().map(Vec::as_slice).collect();
+    let (address, bump) = Pubkey::try_find_program_address(&seeds, &program_id)
+        .ok_or_else(|| AppError::BadRequest("No bump yields an address off the curve".to_string()))?;
+
+    info!("Derived PDA {} (bump {}) for {}", address, bump, program_id);
+
+    Ok(Json(PdaResponse { address: address.to_string(), bump, program_id: pda_req.program_id }))
+}
+
+// Integers are little-endian, as `to_le_bytes()` in an on-chain program would make them
+fn seed_bytes(seed: &PdaSeed) -> Result<Vec<u8>, AppError> {
+    let bytes = match seed {
+        PdaSeed::String(value) => value.as_bytes().to_vec(),
+        PdaSeed::Pubkey(value) => validate_pubkey(value)?.to_bytes().to_vec(),
+        PdaSeed::U8(value) => vec![*value],
+        PdaSeed::U16(value) => value.to_le_bytes().to_vec(),
+        PdaSeed::U32(value) => value.to_le_bytes().to_vec(),
+        PdaSeed::U64(value) => value.to_le_bytes().to_vec(),
+        PdaSeed::Hex(value) => hex::decode(value.strip_prefix("0x").unwrap_or(value))
+            .map_err(|_| AppError::BadRequest(format!("Invalid hex seed: {}", value)))?,
+    };
+    Ok(bytes)
+}
+
+pub async fn classify_address(
+    State(state): State<AppState>,
+    ClusterRpc(cluster): ClusterRpc,
+    Path(address): Path<String>,
+    Query(query): Query<CommitmentQuery>,
+) -> Result<Json<AddressClassification>, AppError> {
+    let pubkey = validate_pubkey(&address)?;
+    let account = cluster.rpc_client.get_account_with_commitment(&pubkey, query.commitment()?).await?.value;
+    Ok(Json(classify(address, &pubkey, account.as_ref(), &state.idls)))
+}
+
+fn classify(address: String, pubkey: &Pubkey, account: Option<&Account>, idls: &IdlRegistry) -> AddressClassification {
+    let on_curve = pubkey.is_on_curve();
+    let sysvar = SYSVARS.iter().find(|(id, _)| id == pubkey).map(|(_, name)| name.to_string());
+    let program = KNOWN_PROGRAMS
+        .iter()
+        .find(|(id, _)| id == pubkey)
+        .map(|(_, name)| name.to_string())
+        .or_else(|| idls.get(pubkey).map(|registered| registered.idl.name().to_string()));
+    let executable = account.is_some_and(|account| account.executable);
+    // Every PDA is off the curve, but so are some vanity addresses like the sysvars
+    let likely_pda = !on_curve && sysvar.is_none() && program.is_none() && !executable;
+
+    let kind = if sysvar.is_some() {
+        AddressKind::Sysvar
+    } else if program.is_some() || executable {
+        AddressKind::Program
+    } else if likely_pda {
+        AddressKind::Pda
+    } else if account.is_none_or(|account| account.owner == system_program::ID) {
+        AddressKind::Wallet
+    } else {
+        AddressKind::Account
+    };
+
+    AddressClassification {
+        address,
+        kind,
+        on_curve,
+        program,
+        sysvar,
+        exists: account.is_some(),
+        owner: account.map(|account| account.owner.to_string()),
+        executable,
+        likely_pda,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use solana_sdk::signature::{Keypair, Signer};
+
+    #[tokio::test]
+    async fn derives_pdas_from_typed_seeds() {
+        let program_id = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let pda_req: PdaRequest = serde_json::from_value(json!({
+            "program_id": program_id.to_string(),
+            "seeds": [
+                { "type": "string", "value": "prereqs" },
+                { "type": "pubkey", "value": user.to_string() },
+                { "type": "u16", "value": 513 },
+                { "type": "u64", "value": 7 },
+                { "type": "hex", "value": "0xdeadbeef" }
+            ]
+        }))
+        .unwrap();
+
+        let Json(pda) = derive_pda(Json(pda_req)).await.unwrap();
+        let seeds: [&[u8]; 5] = [b"prereqs", user.as_ref(), &[1, 2], &7u64.to_le_bytes(), &[0xde, 0xad, 0xbe, 0xef]];
+        let (address, bump) = Pubkey::find_program_address(&seeds, &program_id);
+        assert_eq!(pda.address, address.to_string());
+        assert_eq!(pda.bump, bump);
+
+        let too_long = PdaRequest { program_id: program_id.to_string(), seeds: vec![PdaSeed::Hex("00".repeat(33))] };
+        let err = derive_pda(Json(too_long)).await.unwrap_err();
+        assert_eq!(err.to_string(), "Bad request: Seed 0 is 33 bytes; seeds are at most 32");
+    }
+
+    #[test]
+    fn classifies_addresses() {
+        let idls = IdlRegistry::default();
+        let kind = |pubkey: Pubkey, account: Option<&Account>| {
+            let classified = classify(pubkey.to_string(), &pubkey, account, &idls);
+            (classified.kind, classified.likely_pda)
+        };
+
+        let wallet = Keypair::new().pubkey();
+        let funded = Account { lamports: 1, ..Account::default() };
+        assert_eq!(kind(wallet, None), (AddressKind::Wallet, false));
+        assert_eq!(kind(wallet, Some(&funded)), (AddressKind::Wallet, false));
+        let mint = Account { owner: spl_token::ID, ..Account::default() };
+        assert_eq!(kind(wallet, Some(&mint)), (AddressKind::Account, false));
+
+        let (pda, _) = Pubkey::find_program_address(&[b"vault"], &Pubkey::new_unique());
+        assert_eq!(kind(pda, Some(&mint)), (AddressKind::Pda, true));
+        assert_eq!(kind(sysvar::clock::ID, None), (AddressKind::Sysvar, false));
+        assert_eq!(kind(spl_token::ID, None), (AddressKind::Program, false));
+
+        let program = Account { executable: true, ..Account::default() };
+        let classified = classify(pda.to_string(), &pda, Some(&program), &idls);
+        assert_eq!((classified.kind, classified.program), (AddressKind::Program, None));
+    }
+}
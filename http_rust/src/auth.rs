@@ -0,0 +1,391 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{
+        header::{AUTHORIZATION, SEC_WEBSOCKET_PROTOCOL},
+        request::Parts,
+        HeaderMap,
+    },
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, SystemTime},
+};
+use tracing::{info, warn};
+
+use crate::{error::AppError, ratelimit::Quota, session::Sessions, types::validate_pubkey};
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+/// WebSocket subprotocol `/ws` selects; browsers offer it next to `bearer.<key>`
+pub const WS_PROTOCOL: &str = "solana-http-api";
+const WS_TOKEN_PREFIX: &str = "bearer.";
+
+/// What an API key may do. `admin` implies every other scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Queries, simulation and building unsigned transactions
+    Read,
+    /// Anything that broadcasts a transaction, and registering or deleting webhooks
+    Transfer,
+    Admin,
+}
+
+/// One entry of the key file.
+#[derive(Debug, Deserialize)]
+struct KeyEntry {
+    name: String,
+    key: String,
+    scopes: Vec<Scope>,
+    /// Wallets this key may move funds from; unrestricted when absent
+    #[serde(default)]
+    allowed_wallets: Option<Vec<String>>,
+    /// Lets a rotated-out key keep working until clients have switched over
+    #[serde(default)]
+    expires_at: Option<DateTime<Utc>>,
+    /// Overrides the default per-key quota
+    #[serde(default)]
+    rate_limit: Option<Quota>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeyFile {
+    keys: Vec<KeyEntry>,
+}
+
+/// An authenticated caller's key, as loaded from the key file.
+#[derive(Debug)]
+pub struct ApiKey {
+    /// Display name from the key file; not necessarily unique
+    pub name: String,
+    /// Unique and stable across reloads: the name plus a hash of the secret,
+    /// or `wallet:<address>` for a session. Quotas and idempotency keys hang off it.
+    pub id: String,
+    pub scopes: HashSet<Scope>,
+    pub allowed_wallets: Option<HashSet<Pubkey>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub rate_limit: Option<Quota>,
+}
+
+impl ApiKey {
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope) || self.scopes.contains(&Scope::Admin)
+    }
+}
+
+/// API keys loaded from a JSON key file (`API_KEYS_FILE`).
+///
+/// The file is re-read whenever it changes (see [`ApiKeys::watch`]), so keys can be
+/// added, rotated or revoked without a restart. Without a key file authentication
+/// is disabled and every request is let through.
+pub struct ApiKeys {
+    path: Option<PathBuf>,
+    keys: RwLock<HashMap<String, Arc<ApiKey>>>,
+    modified: Mutex<Option<SystemTime>>,
+}
+
+impl ApiKeys {
+    pub fn disabled() -> Self {
+        ApiKeys {
+            path: None,
+            keys: RwLock::new(HashMap::new()),
+            modified: Mutex::new(None),
+        }
+    }
+
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let api_keys = ApiKeys {
+            path: Some(path.into()),
+            keys: RwLock::new(HashMap::new()),
+            modified: Mutex::new(None),
+        };
+        api_keys.reload()?;
+        Ok(api_keys)
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Re-reads the key file. On error the previously loaded keys stay active.
+    pub fn reload(&self) -> Result<usize, String> {
+        let Some(path) = &self.path else { return Ok(0) };
+        let modified = std::fs::metadata(path).and_then(|meta| meta.modified()).ok();
+        let keys = read_key_file(path)?;
+        let count = keys.len();
+        *self.keys.write().unwrap() = keys;
+        *self.modified.lock().unwrap() = modified;
+        Ok(count)
+    }
+
+    /// Polls the key file forever and reloads it whenever its modification time changes.
+    pub async fn watch(self: Arc<Self>, interval: Duration) {
+        let Some(path) = self.path.clone() else { return };
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let modified = std::fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+            if modified == *self.modified.lock().unwrap() {
+                continue;
+            }
+            match self.reload() {
+                Ok(count) => info!("Reloaded {} API keys from {}", count, path.display()),
+                Err(err) => warn!("Keeping previous API keys: {}", err),
+            }
+        }
+    }
+
+    /// Resolves the key presented in `X-API-Key` or `Authorization: Bearer`.
+    /// Returns `None` when authentication is disabled.
+    pub fn authenticate(&self, headers: &HeaderMap) -> Result<Option<Arc<ApiKey>>, AppError> {
+        if !self.enabled() {
+            return Ok(None);
+        }
+        let presented = presented_key(headers)
+            .ok_or_else(|| AppError::Unauthorized("Missing API key".to_string()))?;
+        let api_key = self
+            .keys
+            .read()
+            .unwrap()
+            .get(presented)
+            .cloned()
+            .ok_or_else(|| AppError::Unauthorized("Invalid API key".to_string()))?;
+        if api_key.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+            return Err(AppError::Unauthorized(format!("API key {} has expired", api_key.name)));
+        }
+        Ok(Some(api_key))
+    }
+}
+
+/// The credential in `X-API-Key` or `Authorization: Bearer`: an API key or a session token.
+///
+/// Browser WebSockets can't set headers, so a `bearer.<key>` entry in
+/// `Sec-WebSocket-Protocol` counts as well.
+pub fn presented_key(headers: &HeaderMap) -> Option<&str> {
+    if let Some(key) = headers.get(API_KEY_HEADER) {
+        return key.to_str().ok();
+    }
+    if let Some(authorization) = headers.get(AUTHORIZATION) {
+        return authorization.to_str().ok()?.strip_prefix("Bearer ").map(str::trim);
+    }
+    headers
+        .get(SEC_WEBSOCKET_PROTOCOL)?
+        .to_str()
+        .ok()?
+        .split(',')
+        .find_map(|protocol| protocol.trim().strip_prefix(WS_TOKEN_PREFIX))
+}
+
+fn read_key_file(path: &Path) -> Result<HashMap<String, Arc<ApiKey>>, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|err| format!("Failed to read API key file {}: {}", path.display(), err))?;
+    let file: KeyFile = serde_json::from_str(&contents)
+        .map_err(|err| format!("Invalid API key file {}: {}", path.display(), err))?;
+
+    let mut keys = HashMap::new();
+    for entry in file.keys {
+        if entry.key.is_empty() {
+            return Err(format!("API key {} is empty", entry.name));
+        }
+        let allowed_wallets = entry
+            .allowed_wallets
+            .map(|wallets| {
+                wallets
+                    .iter()
+                    .map(|wallet| validate_pubkey(wallet))
+                    .collect::<Result<HashSet<_>, _>>()
+                    .map_err(|err| format!("API key {}: {}", entry.name, err))
+            })
+            .transpose()?;
+        let api_key = ApiKey {
+            id: key_id(&entry.name, &entry.key),
+            name: entry.name,
+            scopes: entry.scopes.into_iter().collect(),
+            allowed_wallets,
+            expires_at: entry.expires_at,
+            rate_limit: entry.rate_limit,
+        };
+        if keys.insert(entry.key, Arc::new(api_key)).is_some() {
+            return Err("API key file contains the same key twice".to_string());
+        }
+    }
+    Ok(keys)
+}
+
+fn key_id(name: &str, key: &str) -> String {
+    let digest = Sha256::digest(format!("api-key-id:{}", key));
+    format!("{}#{}", name, hex::encode(&digest[..8]))
+}
+
+/// Middleware state: the key and session stores and the scope a group of routes needs.
+#[derive(Clone)]
+pub struct RequireScope {
+    pub api_keys: Arc<ApiKeys>,
+    pub sessions: Arc<Sessions>,
+    pub scope: Scope,
+}
+
+/// Rejects requests without a valid key or session holding the route's scope, and
+/// hands the key to handlers as a [`Caller`].
+pub async fn require_scope(
+    State(guard): State<RequireScope>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    // Sessions resolve even without a key file, so handlers still see the wallet
+    let api_key = match guard.sessions.authenticate(request.headers())? {
+        Some(session) => Some(session),
+        None => guard.api_keys.authenticate(request.headers())?,
+    };
+    if let Some(api_key) = &api_key {
+        if !api_key.has_scope(guard.scope) {
+            return Err(AppError::Unauthorized(format!(
+                "API key {} lacks the {:?} scope",
+                api_key.name, guard.scope
+            )));
+        }
+    }
+    request.extensions_mut().insert(Caller(api_key));
+    Ok(next.run(request).await)
+}
+
+/// The API key behind a request; `None` when authentication is disabled.
+#[derive(Debug, Clone)]
+pub struct Caller(pub Option<Arc<ApiKey>>);
+
+impl Caller {
+    /// Checks the key may move funds out of `wallet`.
+    pub fn authorize_wallet(&self, wallet: &Pubkey) -> Result<(), AppError> {
+        let Some(api_key) = &self.0 else { return Ok(()) };
+        match &api_key.allowed_wallets {
+            Some(allowed) if !allowed.contains(wallet) => Err(AppError::Unauthorized(format!(
+                "API key {} may not spend from {}",
+                api_key.name, wallet
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Caller {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<Caller>().cloned().unwrap_or(Caller(None)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{session::SessionConfig, store::Store};
+    use axum::{body::Body, http::StatusCode, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn key_file(name: &str, contents: &serde_json::Value) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("api-keys-{}-{}.json", name, std::process::id()));
+        std::fs::write(&path, contents.to_string()).unwrap();
+        path
+    }
+
+    async fn status(router: &Router, headers: &[(&str, &str)]) -> StatusCode {
+        let mut request = axum::http::Request::get("/").body(Body::empty()).unwrap();
+        for (name, value) in headers {
+            request.headers_mut().insert(name.to_string().parse::<axum::http::HeaderName>().unwrap(), value.parse().unwrap());
+        }
+        router.clone().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn enforces_keys_and_scopes() {
+        let path = key_file("scopes", &serde_json::json!({ "keys": [
+            { "name": "reader", "key": "read-key", "scopes": ["read"] },
+            { "name": "ops", "key": "admin-key", "scopes": ["admin"] },
+            { "name": "old", "key": "old-key", "scopes": ["transfer"], "expires_at": "2020-01-01T00:00:00Z" },
+        ]}));
+        let guard = RequireScope {
+            api_keys: Arc::new(ApiKeys::load(&path).unwrap()),
+            sessions: Arc::new(Sessions::new(Arc::new(Store::in_memory()), SessionConfig::default())),
+            scope: Scope::Transfer,
+        };
+        let router = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .route_layer(axum::middleware::from_fn_with_state(guard, require_scope));
+
+        assert_eq!(status(&router, &[]).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(&router, &[("x-api-key", "nope")]).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(&router, &[("x-api-key", "read-key")]).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(&router, &[("x-api-key", "old-key")]).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(&router, &[("x-api-key", "admin-key")]).await, StatusCode::OK);
+        assert_eq!(status(&router, &[("authorization", "Bearer admin-key")]).await, StatusCode::OK);
+        let protocols = format!("{}, bearer.admin-key", WS_PROTOCOL);
+        assert_eq!(status(&router, &[("sec-websocket-protocol", &protocols)]).await, StatusCode::OK);
+        assert_eq!(status(&router, &[("sec-websocket-protocol", WS_PROTOCOL)]).await, StatusCode::UNAUTHORIZED);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn restricts_source_wallets() {
+        let allowed = Pubkey::new_unique();
+        let api_key = ApiKey {
+            name: "payments".to_string(),
+            id: "payments#0".to_string(),
+            scopes: HashSet::from([Scope::Transfer]),
+            allowed_wallets: Some(HashSet::from([allowed])),
+            expires_at: None,
+            rate_limit: None,
+        };
+        let caller = Caller(Some(Arc::new(api_key)));
+        assert!(caller.authorize_wallet(&allowed).is_ok());
+        assert!(caller.authorize_wallet(&Pubkey::new_unique()).is_err());
+        assert!(Caller(None).authorize_wallet(&Pubkey::new_unique()).is_ok());
+    }
+
+    #[test]
+    fn keys_sharing_a_name_get_distinct_ids() {
+        let path = key_file("ids", &serde_json::json!({ "keys": [
+            { "name": "app", "key": "one", "scopes": ["read"] },
+            { "name": "app", "key": "two", "scopes": ["read"] },
+        ]}));
+        let api_keys = ApiKeys::load(&path).unwrap();
+        let id = |key: &str| {
+            let headers = HeaderMap::from_iter([(API_KEY_HEADER.parse().unwrap(), key.parse().unwrap())]);
+            api_keys.authenticate(&headers).unwrap().unwrap().id.clone()
+        };
+        assert!(id("one").starts_with("app#"));
+        assert_ne!(id("one"), id("two"));
+        assert_eq!(id("one"), id("one"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reload_rotates_keys_and_survives_bad_files() {
+        let path = key_file("rotate", &serde_json::json!({ "keys": [
+            { "name": "app", "key": "first", "scopes": ["read"] },
+        ]}));
+        let api_keys = ApiKeys::load(&path).unwrap();
+        let headers = |key: &str| HeaderMap::from_iter([(API_KEY_HEADER.parse().unwrap(), key.parse().unwrap())]);
+        assert!(api_keys.authenticate(&headers("first")).is_ok());
+
+        std::fs::write(&path, serde_json::json!({ "keys": [
+            { "name": "app", "key": "second", "scopes": ["read"] },
+        ]}).to_string()).unwrap();
+        api_keys.reload().unwrap();
+        assert!(api_keys.authenticate(&headers("first")).is_err());
+        assert!(api_keys.authenticate(&headers("second")).is_ok());
+
+        std::fs::write(&path, "{ not json").unwrap();
+        assert!(api_keys.reload().is_err());
+        assert!(api_keys.authenticate(&headers("second")).is_ok());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,187 @@
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::{error::AppError, pool::RpcPool, pubsub::PubsubHub, AppState};
+
+/// Clusters that are always available, with their public RPC endpoints
+const BUILTIN_CLUSTERS: &[(&str, &str)] = &[
+    ("devnet", "https://api.devnet.solana.com"),
+    ("testnet", "https://api.testnet.solana.com"),
+    ("mainnet-beta", "https://api.mainnet-beta.solana.com"),
+    ("localnet", "http://127.0.0.1:8899"),
+];
+
+/// First path segments of top-level routes, which cluster names must not shadow
+const RESERVED_NAMES: &[&str] = &[
+    "health", "admin", "balance", "balances", "account", "accounts", "tokens", "token", "transfer", "transaction",
+    "simulate", "history", "ws", "webhooks", "idl", "program", "pda", "address",
+    "auth", "verify-message", "nonce",
+];
+
+/// One Solana cluster: the RPC pool that serves it and its shared pubsub connection.
+pub struct Cluster {
+    pub name: String,
+    pub rpc_client: Arc<RpcClient>,
+    pub rpc_pool: Arc<RpcPool>,
+    pub pubsub: Arc<PubsubHub>,
+}
+
+impl Cluster {
+    pub fn new(name: String, rpc_pool: Arc<RpcPool>, ws_url: String) -> Self {
+        Cluster {
+            name,
+            rpc_client: Arc::new(rpc_pool.client()),
+            rpc_pool,
+            pubsub: Arc::new(PubsubHub::new(ws_url)),
+        }
+    }
+}
+
+/// Set on requests under a `/:cluster/...` prefix; absent for the unprefixed routes.
+#[derive(Debug, Clone)]
+pub struct ClusterName(pub String);
+
+/// Extracts the cluster a request targets: the route prefix if any, otherwise the default.
+pub struct ClusterRpc(pub Arc<Cluster>);
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClusterRpc {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let name = parts
+            .extensions
+            .get::<ClusterName>()
+            .map(|name| name.0.as_str())
+            .unwrap_or(&state.default_cluster);
+        state
+            .clusters
+            .get(name)
+            .cloned()
+            .map(ClusterRpc)
+            .ok_or_else(|| AppError::BadRequest(format!("Unknown cluster: {}", name)))
+    }
+}
+
+/// Resolves the RPC URLs for every cluster.
+///
+/// Built-in clusters come first. `clusters_spec` (`name=url[,url...];name=...`) adds
+/// custom clusters or replaces a built-in cluster's URLs. `default_urls`, when set,
+/// replaces the default cluster's URLs.
+pub fn cluster_urls(
+    clusters_spec: Option<&str>,
+    default_cluster: &str,
+    default_urls: Option<&str>,
+) -> Result<BTreeMap<String, Vec<String>>, String> {
+    let mut clusters: BTreeMap<String, Vec<String>> = BUILTIN_CLUSTERS
+        .iter()
+        .map(|(name, url)| (name.to_string(), vec![url.to_string()]))
+        .collect();
+
+    let entries = clusters_spec.unwrap_or_default().split(';').filter(|entry| !entry.trim().is_empty());
+    for entry in entries {
+        let (name, urls) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("Cluster entry must be name=url[,url...]: {}", entry))?;
+        let name = name.trim();
+        validate_cluster_name(name)?;
+        clusters.insert(name.to_string(), split_urls(urls, name)?);
+    }
+
+    if let Some(urls) = default_urls {
+        validate_cluster_name(default_cluster)?;
+        clusters.insert(default_cluster.to_string(), split_urls(urls, default_cluster)?);
+    }
+    if !clusters.contains_key(default_cluster) {
+        return Err(format!("Default cluster {} is not configured", default_cluster));
+    }
+    Ok(clusters)
+}
+
+/// The pubsub URL that goes with an RPC URL: `ws(s)://` on the same host, with an
+/// explicit port bumped by one as `solana-test-validator` expects (8899 -> 8900).
+pub fn websocket_url(rpc_url: &str) -> String {
+    let (scheme, rest) = match rpc_url.split_once("://") {
+        Some(("https", rest)) => ("wss", rest),
+        Some((_, rest)) => ("ws", rest),
+        None => ("ws", rpc_url),
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, ""),
+    };
+    let authority = match authority.rsplit_once(':') {
+        Some((host, port)) if !host.ends_with(']') || authority.starts_with('[') => match port.parse::<u16>() {
+            Ok(port) => format!("{}:{}", host, port.saturating_add(1)),
+            Err(_) => authority.to_string(),
+        },
+        _ => authority.to_string(),
+    };
+    format!("{}://{}{}", scheme, authority, path)
+}
+
+fn split_urls(urls: &str, name: &str) -> Result<Vec<String>, String> {
+    let urls: Vec<String> = urls
+        .split(',')
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+        .collect();
+    if urls.is_empty() {
+        return Err(format!("Cluster {} has no RPC URLs", name));
+    }
+    Ok(urls)
+}
+
+fn validate_cluster_name(name: &str) -> Result<(), String> {
+    let valid_chars = name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if name.is_empty() || !valid_chars || RESERVED_NAMES.contains(&name) {
+        return Err(format!("Invalid cluster name: {}", name));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_clusters_are_always_available() {
+        let clusters = cluster_urls(None, "devnet", None).unwrap();
+        assert_eq!(
+            clusters.keys().collect::<Vec<_>>(),
+            vec!["devnet", "localnet", "mainnet-beta", "testnet"]
+        );
+    }
+
+    #[test]
+    fn custom_clusters_and_default_override() {
+        let clusters = cluster_urls(
+            Some("staging=http://a:8899, http://b:8899;mainnet-beta=https://rpc.example.com"),
+            "staging",
+            Some("http://c:8899"),
+        )
+        .unwrap();
+        assert_eq!(clusters["staging"], vec!["http://c:8899"]);
+        assert_eq!(clusters["mainnet-beta"], vec!["https://rpc.example.com"]);
+    }
+
+    #[test]
+    fn derives_websocket_urls() {
+        assert_eq!(websocket_url("https://api.devnet.solana.com"), "wss://api.devnet.solana.com");
+        assert_eq!(websocket_url("http://127.0.0.1:8899"), "ws://127.0.0.1:8900");
+        assert_eq!(
+            websocket_url("https://rpc.example.com/?api-key=abc"),
+            "wss://rpc.example.com/?api-key=abc"
+        );
+    }
+
+    #[test]
+    fn rejects_bad_cluster_config() {
+        assert!(cluster_urls(Some("staging"), "devnet", None).is_err());
+        assert!(cluster_urls(Some("staging="), "devnet", None).is_err());
+        assert!(cluster_urls(Some("health=http://a"), "devnet", None).is_err());
+        assert!(cluster_urls(Some("bad/name=http://a"), "devnet", None).is_err());
+        assert!(cluster_urls(None, "unknown", None).is_err());
+    }
+}
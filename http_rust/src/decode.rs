@@ -0,0 +1,617 @@
+use base64::{prelude::BASE64_STANDARD, Engine};
+use serde_json::{json, Value};
+use solana_account_decoder::parse_account_data::{parse_account_data, AccountAdditionalData};
+use solana_sdk::{
+    account::Account,
+    compute_budget,
+    instruction::CompiledInstruction,
+    message::{v0::LoadedAddresses, AccountKeys},
+    pubkey,
+    pubkey::Pubkey,
+};
+use spl_token_2022::{
+    extension::StateWithExtensions,
+    state::{Account as TokenAccount, Mint},
+};
+use solana_transaction_status::{
+    parse_instruction, EncodedConfirmedTransactionWithStatusMeta, UiInnerInstructions,
+    UiInstruction, UiLoadedAddresses, UiTransactionTokenBalance,
+};
+use std::{collections::BTreeMap, str::FromStr};
+
+use crate::{error::AppError, idl::IdlRegistry, types::*};
+
+/// Metaplex Token Metadata program
+pub const METADATA_PROGRAM_ID: Pubkey = pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+/// Name of the Token Metadata program in `parsed.program` and address classification
+pub const METADATA_PROGRAM_NAME: &str = "mpl-token-metadata";
+/// Account key byte of a Metaplex `Metadata` account
+const METADATA_V1_KEY: u8 = 4;
+
+// Turns a base64-encoded confirmed transaction into the full `GET /transaction` response
+pub fn transaction_response(
+    signature: String,
+    confirmed: EncodedConfirmedTransactionWithStatusMeta,
+    idls: &IdlRegistry,
+) -> Result<TransactionResponse, AppError> {
+    let meta = confirmed.transaction.meta;
+    let decoded = confirmed
+        .transaction
+        .transaction
+        .decode()
+        .ok_or_else(|| AppError::Internal(format!("Transaction could not be decoded: {}", signature)))?;
+
+    let loaded_addresses = meta
+        .as_ref()
+        .and_then(|meta| Option::<UiLoadedAddresses>::from(meta.loaded_addresses.clone()))
+        .filter(|loaded| !loaded.writable.is_empty() || !loaded.readonly.is_empty());
+    let loaded = loaded_addresses.as_ref().map(to_loaded_addresses).transpose()?;
+    let account_keys = AccountKeys::new(decoded.message.static_account_keys(), loaded.as_ref());
+
+    let mut inner_by_index: BTreeMap<u8, Vec<DecodedInstruction>> = BTreeMap::new();
+    let inner = meta
+        .as_ref()
+        .and_then(|meta| Option::<Vec<UiInnerInstructions>>::from(meta.inner_instructions.clone()))
+        .unwrap_or_default();
+    for set in inner {
+        let decoded_inner = set
+            .instructions
+            .into_iter()
+            .filter_map(|instruction| match instruction {
+                UiInstruction::Compiled(compiled) => Some(compiled),
+                UiInstruction::Parsed(_) => None,
+            })
+            .map(|compiled| {
+                let instruction = CompiledInstruction {
+                    program_id_index: compiled.program_id_index,
+                    accounts: compiled.accounts,
+                    data: bs58::decode(&compiled.data).into_vec().unwrap_or_default(),
+                };
+                decode_instruction(&instruction, &account_keys, compiled.stack_height, idls)
+            })
+            .collect();
+        inner_by_index.insert(set.index, decoded_inner);
+    }
+
+    let instructions = decoded
+        .message
+        .instructions()
+        .iter()
+        .enumerate()
+        .map(|(index, instruction)| {
+            let mut decoded = decode_instruction(instruction, &account_keys, Some(1), idls);
+            decoded.inner_instructions = inner_by_index.remove(&(index as u8)).unwrap_or_default();
+            decoded
+        })
+        .collect();
+
+    let accounts: Vec<String> = account_keys.iter().map(|key| key.to_string()).collect();
+    let log_messages = meta
+        .as_ref()
+        .and_then(|meta| Option::<Vec<String>>::from(meta.log_messages.clone()))
+        .unwrap_or_default();
+    let events = logged_events(&log_messages, idls);
+
+    let (balance_changes, token_balance_changes) = match &meta {
+        Some(meta) => (
+            sol_balance_changes(&accounts, &meta.pre_balances, &meta.post_balances),
+            token_balance_changes(
+                &accounts,
+                Option::<Vec<UiTransactionTokenBalance>>::from(meta.pre_token_balances.clone()).unwrap_or_default(),
+                Option::<Vec<UiTransactionTokenBalance>>::from(meta.post_token_balances.clone()).unwrap_or_default(),
+            ),
+        ),
+        None => (Vec::new(), Vec::new()),
+    };
+
+    Ok(TransactionResponse {
+        signature,
+        slot: confirmed.slot,
+        block_time: confirmed.block_time,
+        version: confirmed.transaction.version,
+        success: meta.as_ref().map(|meta| meta.err.is_none()).unwrap_or(false),
+        error: meta.as_ref().and_then(|meta| meta.err.as_ref()).map(|err| err.to_string()),
+        fee: meta.as_ref().map(|meta| meta.fee),
+        compute_units_consumed: meta
+            .as_ref()
+            .and_then(|meta| Option::<u64>::from(meta.compute_units_consumed.clone())),
+        accounts,
+        loaded_addresses: loaded_addresses.map(|loaded| LoadedAddressesResponse {
+            writable: loaded.writable,
+            readonly: loaded.readonly,
+        }),
+        instructions,
+        events,
+        log_messages,
+        balance_changes,
+        token_balance_changes,
+    })
+}
+
+fn to_loaded_addresses(loaded: &UiLoadedAddresses) -> Result<LoadedAddresses, AppError> {
+    let parse = |keys: &[String]| {
+        keys.iter()
+            .map(|key| {
+                Pubkey::from_str(key)
+                    .map_err(|_| AppError::Internal(format!("Invalid loaded address: {}", key)))
+            })
+            .collect::<Result<Vec<_>, _>>()
+    };
+    Ok(LoadedAddresses {
+        writable: parse(&loaded.writable)?,
+        readonly: parse(&loaded.readonly)?,
+    })
+}
+
+/// Decodes one compiled instruction. Programs the transaction-status parser knows
+/// (System, SPL Token, Associated Token, Memo, ...), Compute Budget and programs with
+/// a registered IDL get a `parsed` body; everything else is returned raw.
+pub fn decode_instruction(
+    instruction: &CompiledInstruction,
+    account_keys: &AccountKeys,
+    stack_height: Option<u32>,
+    idls: &IdlRegistry,
+) -> DecodedInstruction {
+    let key = |index: u8| {
+        account_keys
+            .get(index as usize)
+            .map(|key| key.to_string())
+            .unwrap_or_else(|| format!("<missing account {}>", index))
+    };
+    let program_id = account_keys.get(instruction.program_id_index as usize).copied();
+
+    let (program, parsed) = match program_id {
+        Some(program_id) if program_id == compute_budget::id() => {
+            match parse_compute_budget(&instruction.data) {
+                Some(parsed) => (Some("compute-budget".to_string()), Some(parsed)),
+                None => (None, None),
+            }
+        }
+        Some(program_id) => match idls.get(&program_id) {
+            Some(registered) => {
+                let accounts: Vec<String> = instruction.accounts.iter().map(|index| key(*index)).collect();
+                match registered.idl.decode_instruction(&instruction.data, &accounts) {
+                    Some(parsed) => (Some(registered.idl.name().to_string()), Some(parsed)),
+                    None => (None, None),
+                }
+            }
+            None => match parse_instruction::parse(&program_id, instruction, account_keys, stack_height) {
+                Ok(parsed) => (Some(parsed.program), Some(parsed.parsed)),
+                Err(_) => (None, None),
+            },
+        },
+        None => (None, None),
+    };
+
+    DecodedInstruction {
+        program_id: key(instruction.program_id_index),
+        program,
+        accounts: instruction.accounts.iter().map(|index| key(*index)).collect(),
+        data: bs58::encode(&instruction.data).into_string(),
+        parsed,
+        stack_height,
+        inner_instructions: Vec::new(),
+    }
+}
+
+// Anchor's `emit!` logs events as `Program data: <base64>` from inside the emitting
+// program, so the invocation stack in the logs says whose IDL decodes them
+fn logged_events(log_messages: &[String], idls: &IdlRegistry) -> Vec<DecodedEvent> {
+    let mut stack: Vec<&str> = Vec::new();
+    let mut events = Vec::new();
+    for line in log_messages {
+        let Some(rest) = line.strip_prefix("Program ") else { continue };
+        if let Some(data) = rest.strip_prefix("data: ") {
+            let Some(program_id) = stack.last() else { continue };
+            let Some(registered) = Pubkey::from_str(program_id).ok().and_then(|id| idls.get(&id)) else { continue };
+            let Ok(bytes) = BASE64_STANDARD.decode(data) else { continue };
+            if let Some((name, data)) = registered.idl.decode_event(&bytes) {
+                events.push(DecodedEvent {
+                    program_id: program_id.to_string(),
+                    program: registered.idl.name().to_string(),
+                    name,
+                    data,
+                });
+            }
+        } else if let Some((program_id, status)) = rest.split_once(' ') {
+            if status.starts_with("invoke [") {
+                stack.push(program_id);
+            } else if status == "success" || status.starts_with("failed") {
+                stack.pop();
+            }
+        }
+    }
+    events
+}
+
+// Compute Budget instructions are a borsh enum: one tag byte followed by a little-endian value
+fn parse_compute_budget(data: &[u8]) -> Option<Value> {
+    let (tag, rest) = data.split_first()?;
+    let u32_arg = || rest.get(..4).map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()));
+    let u64_arg = || rest.get(..8).map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()));
+
+    let parsed = match tag {
+        1 => json!({ "type": "requestHeapFrame", "info": { "bytes": u32_arg()? } }),
+        2 => json!({ "type": "setComputeUnitLimit", "info": { "units": u32_arg()? } }),
+        3 => json!({ "type": "setComputeUnitPrice", "info": { "microLamports": u64_arg()? } }),
+        4 => json!({ "type": "setLoadedAccountsDataSizeLimit", "info": { "bytes": u32_arg()? } }),
+        _ => return None,
+    };
+    Some(parsed)
+}
+
+fn sol_balance_changes(accounts: &[String], pre: &[u64], post: &[u64]) -> Vec<BalanceChange> {
+    accounts
+        .iter()
+        .zip(pre.iter().zip(post))
+        .map(|(address, (pre, post))| BalanceChange {
+            address: address.clone(),
+            pre_lamports: *pre,
+            post_lamports: *post,
+            change: lamport_change_to_sol(i64::try_from(*post as i128 - *pre as i128).unwrap_or(0)),
+        })
+        .collect()
+}
+
+fn token_balance_changes(
+    accounts: &[String],
+    pre: Vec<UiTransactionTokenBalance>,
+    post: Vec<UiTransactionTokenBalance>,
+) -> Vec<TokenBalanceChange> {
+    let mut by_index: BTreeMap<u8, (Option<UiTransactionTokenBalance>, Option<UiTransactionTokenBalance>)> =
+        BTreeMap::new();
+    for balance in pre {
+        let index = balance.account_index;
+        by_index.entry(index).or_default().0 = Some(balance);
+    }
+    for balance in post {
+        let index = balance.account_index;
+        by_index.entry(index).or_default().1 = Some(balance);
+    }
+
+    by_index
+        .into_iter()
+        .filter_map(|(index, (pre, post))| {
+            let any = pre.as_ref().or(post.as_ref())?;
+            Some(TokenBalanceChange {
+                address: accounts.get(index as usize).cloned().unwrap_or_default(),
+                mint: any.mint.clone(),
+                owner: Option::<String>::from(any.owner.clone()),
+                decimals: any.ui_token_amount.decimals,
+                pre_amount: pre
+                    .as_ref()
+                    .map(|balance| balance.ui_token_amount.ui_amount_string.clone())
+                    .unwrap_or_else(|| "0".to_string()),
+                post_amount: post
+                    .as_ref()
+                    .map(|balance| balance.ui_token_amount.ui_amount_string.clone())
+                    .unwrap_or_else(|| "0".to_string()),
+            })
+        })
+        .collect()
+}
+
+/// Decodes account data for programs with a registered IDL, the programs the RPC's
+/// `jsonParsed` encoding knows (SPL Token and Token-2022 with extensions, stake, nonce,
+/// address lookup tables, vote, sysvars, ...) and Metaplex metadata. Token accounts need their mint's
+/// decimals; without them, or for any other program, there is nothing to parse.
+pub fn parse_account(
+    pubkey: &Pubkey,
+    account: &Account,
+    mint_decimals: Option<u8>,
+    idls: &IdlRegistry,
+) -> Option<ParsedAccountData> {
+    if let Some(parsed) = idls.decode_account(&account.owner, &account.data) {
+        return Some(parsed);
+    }
+    if account.owner == METADATA_PROGRAM_ID {
+        let info = parse_metadata(&account.data)?;
+        return Some(ParsedAccountData {
+            program: METADATA_PROGRAM_NAME.to_string(),
+            parsed: json!({ "type": "metadata", "info": info }),
+        });
+    }
+    let additional = AccountAdditionalData { spl_token_decimals: mint_decimals };
+    let parsed = parse_account_data(pubkey, &account.owner, &account.data, Some(additional)).ok()?;
+    Some(ParsedAccountData { program: parsed.program, parsed: parsed.parsed })
+}
+
+/// Mint of an SPL Token or Token-2022 token account
+pub fn token_account_mint(account: &Account) -> Option<Pubkey> {
+    if !is_token_program(&account.owner) {
+        return None;
+    }
+    StateWithExtensions::<TokenAccount>::unpack(&account.data).ok().map(|state| state.base.mint)
+}
+
+pub fn mint_decimals(account: &Account) -> Option<u8> {
+    if !is_token_program(&account.owner) {
+        return None;
+    }
+    StateWithExtensions::<Mint>::unpack(&account.data).ok().map(|state| state.base.decimals)
+}
+
+fn is_token_program(program_id: &Pubkey) -> bool {
+    *program_id == spl_token::id() || *program_id == spl_token_2022::id()
+}
+
+// Metaplex `Metadata` is borsh-encoded. Name, symbol and uri are NUL-padded to fixed
+// sizes, and accounts created before a field existed end early, so missing trailing
+// options read as `null`.
+fn parse_metadata(data: &[u8]) -> Option<Value> {
+    let mut reader = BorshReader::new(data);
+    if reader.u8()? != METADATA_V1_KEY {
+        return None;
+    }
+    let update_authority = reader.pubkey()?;
+    let mint = reader.pubkey()?;
+    let mut padded_string = || reader.string().map(|value| value.trim_end_matches('\0').to_string());
+    let name = padded_string()?;
+    let symbol = padded_string()?;
+    let uri = padded_string()?;
+    let seller_fee_basis_points = reader.u16()?;
+    let creators = reader.option(|reader| {
+        let count = reader.u32()?;
+        (0..count)
+            .map(|_| {
+                Some(json!({
+                    "address": reader.pubkey()?.to_string(),
+                    "verified": reader.bool()?,
+                    "share": reader.u8()?,
+                }))
+            })
+            .collect::<Option<Vec<_>>>()
+    })?;
+    let primary_sale_happened = reader.bool()?;
+    let is_mutable = reader.bool()?;
+    let edition_nonce = reader.option(BorshReader::u8)?;
+    let token_standard = reader.option(|reader| {
+        let standard = match reader.u8()? {
+            0 => "nonFungible",
+            1 => "fungibleAsset",
+            2 => "fungible",
+            3 => "nonFungibleEdition",
+            4 => "programmableNonFungible",
+            5 => "programmableNonFungibleEdition",
+            _ => return None,
+        };
+        Some(standard)
+    })?;
+    let collection = reader.option(|reader| {
+        Some(json!({ "verified": reader.bool()?, "key": reader.pubkey()?.to_string() }))
+    })?;
+    let uses = reader.option(|reader| {
+        let use_method = match reader.u8()? {
+            0 => "burn",
+            1 => "multiple",
+            2 => "single",
+            _ => return None,
+        };
+        Some(json!({ "useMethod": use_method, "remaining": reader.u64()?, "total": reader.u64()? }))
+    })?;
+    let collection_details = reader.option(|reader| match reader.u8()? {
+        0 => Some(json!({ "size": reader.u64()? })),
+        1 => Some(json!({ "size": null })),
+        _ => None,
+    })?;
+    let programmable_config = reader.option(|reader| match reader.u8()? {
+        0 => Some(json!({ "ruleSet": reader.option(BorshReader::pubkey)?.map(|key| key.to_string()) })),
+        _ => None,
+    })?;
+
+    Some(json!({
+        "updateAuthority": update_authority.to_string(),
+        "mint": mint.to_string(),
+        "name": name,
+        "symbol": symbol,
+        "uri": uri,
+        "sellerFeeBasisPoints": seller_fee_basis_points,
+        "creators": creators,
+        "primarySaleHappened": primary_sale_happened,
+        "isMutable": is_mutable,
+        "editionNonce": edition_nonce,
+        "tokenStandard": token_standard,
+        "collection": collection,
+        "uses": uses,
+        "collectionDetails": collection_details,
+        "programmableConfig": programmable_config,
+    }))
+}
+
+/// Sequence elements one reader hands out in total. Elements of zero-sized types, such as
+/// empty structs, take no bytes, so the data length alone doesn't bound nested sequences.
+const MAX_SEQUENCE_ELEMENTS: usize = 100_000;
+
+/// Reads borsh values off the front of a byte slice; `None` once the data runs out
+pub struct BorshReader<'a> {
+    data: &'a [u8],
+    elements_left: usize,
+}
+
+impl<'a> BorshReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        BorshReader { data, elements_left: MAX_SEQUENCE_ELEMENTS }
+    }
+
+    /// Takes `count` sequence elements out of the reader's budget; `None` once it is spent
+    pub fn claim_elements(&mut self, count: usize) -> Option<()> {
+        self.elements_left = self.elements_left.checked_sub(count)?;
+        Some(())
+    }
+
+    pub fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (bytes, rest) = self.data.split_first_chunk::<N>()?;
+        self.data = rest;
+        Some(*bytes)
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        self.take::<1>().map(|[byte]| byte)
+    }
+
+    pub fn bool(&mut self) -> Option<bool> {
+        self.u8().map(|byte| byte != 0)
+    }
+
+    pub fn u16(&mut self) -> Option<u16> {
+        self.take().map(u16::from_le_bytes)
+    }
+
+    pub fn u32(&mut self) -> Option<u32> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    pub fn u64(&mut self) -> Option<u64> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    pub fn pubkey(&mut self) -> Option<Pubkey> {
+        self.take().map(Pubkey::new_from_array)
+    }
+
+    /// A length-prefixed `Vec<u8>`
+    pub fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        if self.data.len() < len {
+            return None;
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(bytes)
+    }
+
+    pub fn string(&mut self) -> Option<String> {
+        self.bytes().map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    /// A borsh `Option`; data that has already ended reads as `None`
+    pub fn option<T>(&mut self, read: impl FnOnce(&mut Self) -> Option<T>) -> Option<Option<T>> {
+        match self.data.split_first() {
+            None | Some((0, _)) => {
+                self.data = self.data.get(1..).unwrap_or_default();
+                Some(None)
+            }
+            Some((1, rest)) => {
+                self.data = rest;
+                read(self).map(Some)
+            }
+            Some(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{
+        compute_budget::ComputeBudgetInstruction, message::Message, system_instruction,
+    };
+
+    #[test]
+    fn decodes_compute_budget_and_system_instructions() {
+        let payer = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let message = Message::new(
+            &[
+                ComputeBudgetInstruction::set_compute_unit_limit(200_000),
+                ComputeBudgetInstruction::set_compute_unit_price(5_000),
+                system_instruction::transfer(&payer, &recipient, 42),
+            ],
+            Some(&payer),
+        );
+        let account_keys = AccountKeys::new(&message.account_keys, None);
+        let decoded: Vec<_> = message
+            .instructions
+            .iter()
+            .map(|instruction| decode_instruction(instruction, &account_keys, Some(1), &IdlRegistry::default()))
+            .collect();
+
+        assert_eq!(decoded[0].program.as_deref(), Some("compute-budget"));
+        assert_eq!(decoded[0].parsed.as_ref().unwrap()["info"]["units"], 200_000);
+        assert_eq!(decoded[1].parsed.as_ref().unwrap()["type"], "setComputeUnitPrice");
+        assert_eq!(decoded[2].program.as_deref(), Some("system"));
+        assert_eq!(decoded[2].parsed.as_ref().unwrap()["info"]["lamports"], 42);
+        assert_eq!(decoded[2].accounts, vec![payer.to_string(), recipient.to_string()]);
+    }
+
+    #[test]
+    fn leaves_unknown_programs_raw() {
+        let payer = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let message = Message::new(
+            &[solana_sdk::instruction::Instruction::new_with_bytes(program_id, &[1, 2, 3], vec![])],
+            Some(&payer),
+        );
+        let account_keys = AccountKeys::new(&message.account_keys, None);
+        let decoded = decode_instruction(&message.instructions[0], &account_keys, Some(1), &IdlRegistry::default());
+
+        assert_eq!(decoded.program_id, program_id.to_string());
+        assert!(decoded.parsed.is_none());
+        assert_eq!(decoded.data, bs58::encode([1, 2, 3]).into_string());
+    }
+
+    // Borsh string padded with NULs the way Metaplex stores names
+    fn padded(value: &str, len: usize) -> Vec<u8> {
+        let mut bytes = (len as u32).to_le_bytes().to_vec();
+        bytes.extend(value.as_bytes());
+        bytes.resize(4 + len, 0);
+        bytes
+    }
+
+    #[test]
+    fn parses_metaplex_metadata_including_truncated_accounts() {
+        let update_authority = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let creator = Pubkey::new_unique();
+        let mut data = vec![METADATA_V1_KEY];
+        data.extend(update_authority.to_bytes());
+        data.extend(mint.to_bytes());
+        data.extend(padded("Token", 32));
+        data.extend(padded("TKN", 10));
+        data.extend(padded("https://example.com/token.json", 200));
+        data.extend(500u16.to_le_bytes());
+        data.extend([1, 1, 0, 0, 0]);
+        data.extend(creator.to_bytes());
+        data.extend([1, 100]);
+        // primary sale, mutable, edition nonce 255, token standard fungible; the rest is missing
+        data.extend([0, 1, 1, 255, 1, 2]);
+
+        let account = Account { owner: METADATA_PROGRAM_ID, data, ..Account::default() };
+        let parsed = parse_account(&Pubkey::new_unique(), &account, None, &IdlRegistry::default()).unwrap();
+        assert_eq!(parsed.program, "mpl-token-metadata");
+        let info = &parsed.parsed["info"];
+        assert_eq!(info["mint"], mint.to_string());
+        assert_eq!(info["name"], "Token");
+        assert_eq!(info["uri"], "https://example.com/token.json");
+        assert_eq!(info["sellerFeeBasisPoints"], 500);
+        assert_eq!(info["creators"][0]["address"], creator.to_string());
+        assert_eq!(info["creators"][0]["share"], 100);
+        assert_eq!(info["editionNonce"], 255);
+        assert_eq!(info["tokenStandard"], "fungible");
+        assert!(info["collection"].is_null());
+    }
+
+    #[test]
+    fn parses_token_accounts_with_their_mint_decimals() {
+        use solana_sdk::program_pack::Pack;
+
+        let mint = Pubkey::new_unique();
+        let mut data = vec![0; spl_token::state::Account::LEN];
+        spl_token::state::Account {
+            mint,
+            owner: Pubkey::new_unique(),
+            amount: 1_500_000,
+            state: spl_token::state::AccountState::Initialized,
+            ..Default::default()
+        }
+        .pack_into_slice(&mut data);
+        let account = Account { owner: spl_token::id(), data, ..Account::default() };
+
+        assert_eq!(token_account_mint(&account), Some(mint));
+        assert!(parse_account(&Pubkey::new_unique(), &account, None, &IdlRegistry::default()).is_none());
+        let parsed = parse_account(&Pubkey::new_unique(), &account, Some(6), &IdlRegistry::default()).unwrap();
+        assert_eq!(parsed.program, "spl-token");
+        assert_eq!(parsed.parsed["type"], "account");
+        assert_eq!(parsed.parsed["info"]["tokenAmount"]["uiAmountString"], "1.5");
+    }
+}
@@ -1,5 +1,5 @@
 use axum::{
-    http::StatusCode,
+    http::{header::RETRY_AFTER, StatusCode},
     response::{IntoResponse, Json},
 };
 use serde_json::json;
@@ -8,7 +8,7 @@ use thiserror::Error;
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Solana RPC error: {0}")]
-    SolanaRpc(#[from] solana_client::client_error::ClientError),
+    SolanaRpc(Box<solana_client::client_error::ClientError>),
     
     #[error("Invalid public key: {0}")]
     InvalidPublicKey(String),
@@ -16,40 +16,78 @@ pub enum AppError {
     #[error("Wallet not found: {0}")]
     WalletNotFound(String),
     
+    #[error("Not found: {0}")]
+    NotFound(String),
+    
     #[error("Invalid signature: {0}")]
     InvalidSignature(String),
     
     #[error("Invalid amount: {0}")]
     InvalidAmount(String),
     
+    #[error("Token account frozen: {0}")]
+    FrozenAccount(String),
+    
     #[error("Bad request: {0}")]
     BadRequest(String),
     
     #[error("Unauthorized: {0}")]
     Unauthorized(String),
     
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    
+    #[error("Rate limit exceeded: {message}")]
+    RateLimited { message: String, retry_after_secs: u64 },
+    
     #[error("Internal server error: {0}")]
     Internal(String),
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> axum::response::Response {
-        let (status, error_message) = match &self {
+// Boxed so handler results stay small; `?` on RPC calls still converts directly.
+impl From<solana_client::client_error::ClientError> for AppError {
+    fn from(err: solana_client::client_error::ClientError) -> Self {
+        AppError::SolanaRpc(Box::new(err))
+    }
+}
+
+impl AppError {
+    /// HTTP status and short error label for the response body
+    fn status(&self) -> (StatusCode, &'static str) {
+        match self {
             AppError::SolanaRpc(_) => (StatusCode::BAD_GATEWAY, "Solana RPC error"),
             AppError::InvalidPublicKey(_) => (StatusCode::BAD_REQUEST, "Invalid public key"),
             AppError::WalletNotFound(_) => (StatusCode::NOT_FOUND, "Wallet not found"),
+            AppError::NotFound(_) => (StatusCode::NOT_FOUND, "Not found"),
             AppError::InvalidSignature(_) => (StatusCode::BAD_REQUEST, "Invalid signature"),
             AppError::InvalidAmount(_) => (StatusCode::BAD_REQUEST, "Invalid amount"),
+            AppError::FrozenAccount(_) => (StatusCode::CONFLICT, "Token account frozen"),
             AppError::BadRequest(_) => (StatusCode::BAD_REQUEST, "Bad request"),
             AppError::Unauthorized(_) => (StatusCode::UNAUTHORIZED, "Unauthorized"),
+            AppError::Conflict(_) => (StatusCode::CONFLICT, "Conflict"),
+            AppError::RateLimited { .. } => (StatusCode::TOO_MANY_REQUESTS, "Rate limit exceeded"),
             AppError::Internal(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error"),
-        };
+        }
+    }
+    
+    pub fn label(&self) -> &'static str {
+        self.status().1
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> axum::response::Response {
+        let (status, error_message) = self.status();
 
         let body = Json(json!({
             "error": error_message,
             "message": self.to_string(),
         }));
 
-        (status, body).into_response()
+        let mut response = (status, body).into_response();
+        if let AppError::RateLimited { retry_after_secs, .. } = &self {
+            response.headers_mut().insert(RETRY_AFTER, (*retry_after_secs).into());
+        }
+        response
     }
 } 
\ No newline at end of file
@@ -0,0 +1,240 @@
+use serde::{Deserialize, Deserializer};
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    compute_budget::ComputeBudgetInstruction,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    system_instruction::SystemInstruction,
+    system_program,
+    transaction::Transaction,
+};
+use std::str::FromStr;
+use tracing::{info, warn};
+
+use crate::error::AppError;
+
+/// Upper bound the runtime accepts for a transaction's compute unit limit
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+/// Percentile of recent fees used when a strategy needs one but doesn't name it
+const DEFAULT_FEE_PERCENTILE: u8 = 75;
+const MICRO_LAMPORTS_PER_LAMPORT: u128 = 1_000_000;
+
+/// How the compute unit price of a server-built transaction is chosen.
+///
+/// Parsed from `none`, `percentile:N`, `fixed:MICROLAMPORTS` or `max_lamports:N`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FeeStrategy {
+    /// No priority fee; only the compute unit limit is set
+    #[default]
+    None,
+    /// Nth percentile of recent prioritization fees paid for the writable accounts
+    Percentile(u8),
+    /// Fixed price in micro-lamports per compute unit
+    Fixed(u64),
+    /// Default percentile, capped so the priority fee never exceeds this many lamports
+    MaxLamports(u64),
+}
+
+impl FromStr for FeeStrategy {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || AppError::BadRequest(format!("Invalid fee_strategy: {}", value));
+        let (kind, arg) = match value.split_once(':') {
+            Some((kind, arg)) => (kind, Some(arg)),
+            None => (value, None),
+        };
+        match (kind, arg) {
+            ("none", None) => Ok(FeeStrategy::None),
+            ("percentile", Some(arg)) => match arg.parse() {
+                Ok(percentile) if percentile <= 100 => Ok(FeeStrategy::Percentile(percentile)),
+                _ => Err(invalid()),
+            },
+            ("fixed", Some(arg)) => arg.parse().map(FeeStrategy::Fixed).map_err(|_| invalid()),
+            ("max_lamports", Some(arg)) => arg.parse().map(FeeStrategy::MaxLamports).map_err(|_| invalid()),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for FeeStrategy {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(|err: AppError| serde::de::Error::custom(err.to_string()))
+    }
+}
+
+/// Compute budget chosen for a transaction
+#[derive(Debug, Clone, Copy)]
+pub struct ComputeBudget {
+    pub unit_limit: u32,
+    /// Micro-lamports per compute unit
+    pub unit_price: u64,
+}
+
+/// Prepends compute budget instructions to `instructions`: a unit limit sized from a
+/// simulation (the maximum if it fails) and, unless the strategy is `none`, a unit
+/// price for the writable accounts.
+pub async fn with_compute_budget(
+    rpc_client: &RpcClient,
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    strategy: FeeStrategy,
+) -> Result<(Vec<Instruction>, ComputeBudget), AppError> {
+    // A durable nonce advance only counts as the first instruction, so budgeting goes after it
+    let (nonce_advance, instructions) = match instructions.split_first() {
+        Some((first, rest)) if is_nonce_advance(first) => (Some(first), rest),
+        _ => (None, instructions),
+    };
+
+    // Simulate with both budget instructions in place so their own cost is counted
+    let mut probe: Vec<Instruction> = nonce_advance.into_iter().cloned().collect();
+    probe.push(ComputeBudgetInstruction::set_compute_unit_limit(MAX_COMPUTE_UNIT_LIMIT));
+    probe.push(ComputeBudgetInstruction::set_compute_unit_price(0));
+    probe.extend_from_slice(instructions);
+    let simulation = rpc_client
+        .simulate_transaction_with_config(
+            &Transaction::new_with_payer(&probe, Some(payer)),
+            RpcSimulateTransactionConfig {
+                sig_verify: false,
+                replace_recent_blockhash: true,
+                commitment: Some(CommitmentConfig::confirmed()),
+                ..RpcSimulateTransactionConfig::default()
+            },
+        )
+        .await?
+        .value;
+    // A transaction that fails now, say for a payer funded only later, may still land once
+    // it's signed and sent, so it gets the full limit instead of failing the build
+    let unit_limit = match simulation.err {
+        None => compute_unit_limit(simulation.units_consumed),
+        Some(err) => {
+            warn!("Compute budget simulation failed, using the maximum limit: {}", err);
+            MAX_COMPUTE_UNIT_LIMIT
+        }
+    };
+
+    let unit_price = match strategy {
+        FeeStrategy::None => 0,
+        FeeStrategy::Fixed(price) => price,
+        FeeStrategy::Percentile(percentile) => {
+            recent_fee_percentile(rpc_client, instructions, percentile).await?
+        }
+        FeeStrategy::MaxLamports(max_lamports) => {
+            let price = recent_fee_percentile(rpc_client, instructions, DEFAULT_FEE_PERCENTILE).await?;
+            cap_unit_price(price, max_lamports, unit_limit)
+        }
+    };
+
+    info!("Compute budget: {} units at {} micro-lamports ({:?})", unit_limit, unit_price, strategy);
+
+    let mut budgeted: Vec<Instruction> = nonce_advance.into_iter().cloned().collect();
+    budgeted.push(ComputeBudgetInstruction::set_compute_unit_limit(unit_limit));
+    if unit_price > 0 {
+        budgeted.push(ComputeBudgetInstruction::set_compute_unit_price(unit_price));
+    }
+    budgeted.extend_from_slice(instructions);
+
+    Ok((budgeted, ComputeBudget { unit_limit, unit_price }))
+}
+
+fn is_nonce_advance(instruction: &Instruction) -> bool {
+    system_program::check_id(&instruction.program_id)
+        && matches!(bincode::deserialize(&instruction.data), Ok(SystemInstruction::AdvanceNonceAccount))
+}
+
+// Simulated usage plus a 10% margin, within the runtime's bounds
+fn compute_unit_limit(units_consumed: Option<u64>) -> u32 {
+    match units_consumed {
+        Some(units) => (units + units / 10).clamp(1, MAX_COMPUTE_UNIT_LIMIT as u64) as u32,
+        None => MAX_COMPUTE_UNIT_LIMIT,
+    }
+}
+
+async fn recent_fee_percentile(
+    rpc_client: &RpcClient,
+    instructions: &[Instruction],
+    percentile: u8,
+) -> Result<u64, AppError> {
+    let mut writable: Vec<Pubkey> = instructions
+        .iter()
+        .flat_map(|instruction| instruction.accounts.iter())
+        .filter(|meta| meta.is_writable)
+        .map(|meta| meta.pubkey)
+        .collect();
+    writable.sort();
+    writable.dedup();
+
+    let mut fees: Vec<u64> = rpc_client
+        .get_recent_prioritization_fees(&writable)
+        .await?
+        .into_iter()
+        .map(|fee| fee.prioritization_fee)
+        .collect();
+    Ok(fee_percentile(&mut fees, percentile))
+}
+
+fn fee_percentile(fees: &mut [u64], percentile: u8) -> u64 {
+    if fees.is_empty() {
+        return 0;
+    }
+    fees.sort_unstable();
+    let rank = (fees.len() - 1) * percentile.min(100) as usize / 100;
+    fees[rank]
+}
+
+// Largest price at or below `price` whose total priority fee fits in `max_lamports`
+fn cap_unit_price(price: u64, max_lamports: u64, unit_limit: u32) -> u64 {
+    let ceiling = max_lamports as u128 * MICRO_LAMPORTS_PER_LAMPORT / unit_limit.max(1) as u128;
+    price.min(ceiling.min(u64::MAX as u128) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_fee_strategies() {
+        assert_eq!("none".parse::<FeeStrategy>().unwrap(), FeeStrategy::None);
+        assert_eq!("percentile:90".parse::<FeeStrategy>().unwrap(), FeeStrategy::Percentile(90));
+        assert_eq!("fixed:5000".parse::<FeeStrategy>().unwrap(), FeeStrategy::Fixed(5000));
+        assert_eq!("max_lamports:10000".parse::<FeeStrategy>().unwrap(), FeeStrategy::MaxLamports(10000));
+        for bad in ["", "fixed", "percentile:101", "fixed:-1", "none:1", "fastest"] {
+            assert!(bad.parse::<FeeStrategy>().is_err(), "accepted {:?}", bad);
+        }
+    }
+
+    #[test]
+    fn picks_fee_percentile() {
+        assert_eq!(fee_percentile(&mut [], 50), 0);
+        assert_eq!(fee_percentile(&mut [30, 10, 20, 40, 50], 0), 10);
+        assert_eq!(fee_percentile(&mut [30, 10, 20, 40, 50], 50), 30);
+        assert_eq!(fee_percentile(&mut [30, 10, 20, 40, 50], 100), 50);
+    }
+
+    #[test]
+    fn caps_price_to_lamport_budget() {
+        // 200k units at 50 micro-lamports = 10 lamports, within a 100 lamport cap
+        assert_eq!(cap_unit_price(50, 100, 200_000), 50);
+        // 1000 lamports over 200k units allows at most 5000 micro-lamports per unit
+        assert_eq!(cap_unit_price(1_000_000, 1_000, 200_000), 5_000);
+        assert_eq!(cap_unit_price(1_000_000, 0, 200_000), 0);
+    }
+
+    #[test]
+    fn recognizes_nonce_advances() {
+        let (nonce, authority) = (Pubkey::new_unique(), Pubkey::new_unique());
+        assert!(is_nonce_advance(&solana_sdk::system_instruction::advance_nonce_account(&nonce, &authority)));
+        assert!(!is_nonce_advance(&solana_sdk::system_instruction::transfer(&authority, &nonce, 1)));
+        assert!(!is_nonce_advance(&ComputeBudgetInstruction::set_compute_unit_price(0)));
+    }
+
+    #[test]
+    fn sizes_compute_limit_from_simulation() {
+        assert_eq!(compute_unit_limit(Some(1_000)), 1_100);
+        assert_eq!(compute_unit_limit(Some(0)), 1);
+        assert_eq!(compute_unit_limit(Some(2_000_000)), MAX_COMPUTE_UNIT_LIMIT);
+        assert_eq!(compute_unit_limit(None), MAX_COMPUTE_UNIT_LIMIT);
+    }
+}
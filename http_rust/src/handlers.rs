@@ -1,23 +1,84 @@
-use axum::{extract::{Path, State}, response::Json};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use futures::{future::try_join_all, stream, StreamExt};
+use solana_account_decoder::{
+    parse_token::{TokenAccountType, UiAccountState},
+    UiAccountData,
+};
+use solana_client::{
+    rpc_client::GetConfirmedSignaturesForAddress2Config,
+    rpc_config::{RpcSimulateTransactionConfig, RpcTransactionConfig},
+    rpc_request::TokenAccountsFilter,
+    rpc_response::RpcKeyedAccount,
+};
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+use spl_token_2022::{
+    extension::StateWithExtensions,
+    state::{Account as TokenAccount, AccountState, Mint},
+};
 use solana_sdk::{
+    account::Account,
     commitment_config::CommitmentConfig,
-    signature::{Keypair, Signer},
+    hash::Hash,
+    instruction::Instruction,
+    offchain_message::{MessageFormat, OffchainMessage},
+    pubkey::Pubkey,
+    sanitize::Sanitize,
+    signature::{Keypair, Signature, Signer},
     system_instruction,
     transaction::Transaction,
 };
-use std::str::FromStr;
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, UiLoadedAddresses, UiTransactionEncoding,
+};
+use std::collections::{HashMap, HashSet};
 use tracing::info;
 
-use crate::{error::AppError, types::*, AppState};
+use crate::{
+    auth::Caller,
+    cluster::{Cluster, ClusterRpc},
+    decode,
+    error::AppError,
+    fees,
+    idl::IdlRegistry,
+    nonce::DurableNonce,
+    tracker,
+    types::*,
+    AppState,
+};
+
+// Commitment of the blockhash in every transaction the server builds, so
+// last_valid_block_height means the same thing on every path
+pub const BLOCKHASH_COMMITMENT: CommitmentConfig = CommitmentConfig::confirmed();
+
+const DEFAULT_HISTORY_LIMIT: usize = 20;
+// getSignaturesForAddress caps pages at 1000; expanded pages cost one extra call per entry
+const MAX_HISTORY_LIMIT: usize = 1000;
+const MAX_EXPANDED_HISTORY_LIMIT: usize = 100;
+const MAX_CONCURRENT_HISTORY_FETCHES: usize = 10;
+const MAX_BATCH_ADDRESSES: usize = 500;
+// getMultipleAccounts accepts at most 100 addresses per call
+const MULTIPLE_ACCOUNTS_CHUNK: usize = 100;
+// Raw data returned per account by POST /accounts, so 500 large accounts can't make a huge response
+const MAX_BATCH_DATA_LEN: usize = 16 * 1024;
 
 pub async fn get_balance(
-    State(state): State<AppState>,
+    ClusterRpc(cluster): ClusterRpc,
     Path(address): Path<String>,
+    Query(query): Query<CommitmentQuery>,
 ) -> Result<Json<BalanceResponse>, AppError> {
     info!("Getting balance for address: {}", address);
     
     let pubkey = validate_pubkey(&address)?;
-    let balance = state.rpc_client.get_balance_with_commitment(&pubkey, CommitmentConfig::confirmed())?;
+    let commitment = query.commitment()?;
+    let balance = cluster.rpc_client.get_balance_with_commitment(&pubkey, commitment).await?;
     let sol_balance = lamports_to_sol(balance.value);
     
     info!("Balance for {}: {} SOL ({} lamports)", address, sol_balance, balance.value);
@@ -31,64 +92,260 @@ pub async fn get_balance(
 
 pub async fn get_account_info(
     State(state): State<AppState>,
+    ClusterRpc(cluster): ClusterRpc,
     Path(address): Path<String>,
+    Query(query): Query<AccountQuery>,
 ) -> Result<Json<AccountInfoResponse>, AppError> {
     info!("Getting account info for address: {}", address);
     
     let pubkey = validate_pubkey(&address)?;
-    let account = state.rpc_client.get_account_with_commitment(&pubkey, CommitmentConfig::confirmed())?;
+    let commitment = query.commitment()?;
+    let account = cluster.rpc_client.get_account_with_commitment(&pubkey, commitment).await?;
     
     let account_info = account.value.ok_or_else(|| {
         AppError::WalletNotFound(format!("Account not found: {}", address))
     })?;
+    let decimals = mint_decimals(&cluster, [&account_info], commitment).await?;
     
     info!("Account info retrieved for: {}", address);
     
-    Ok(Json(AccountInfoResponse {
-        address,
-        lamports: account_info.lamports,
-        owner: account_info.owner.to_string(),
-        executable: account_info.executable,
-        rent_epoch: account_info.rent_epoch,
+    let data_limit = query.data.then_some(usize::MAX);
+    Ok(Json(account_response(address, pubkey, account_info, &decimals, &state.idls, data_limit)))
+}
+
+pub async fn get_balances(
+    ClusterRpc(cluster): ClusterRpc,
+    Query(query): Query<CommitmentQuery>,
+    Json(batch_req): Json<BatchRequest>,
+) -> Result<Json<BatchResponse<BalanceResponse>>, AppError> {
+    info!("Getting balances for {} addresses", batch_req.addresses.len());
+    
+    let commitment = query.commitment()?;
+    let accounts = get_multiple_accounts(&cluster, batch_req.addresses, commitment).await?;
+    
+    // Like getBalance, an account that doesn't exist holds 0 lamports
+    let results = accounts
+        .into_iter()
+        .map(|(address, account)| match account {
+            Ok((_, account)) => {
+                let lamports = account.map_or(0, |account| account.lamports);
+                BatchResult::Ok(BalanceResponse { address, balance: lamports_to_sol(lamports), lamports })
+            }
+            Err(err) => BatchResult::Err(BatchError::new(address, err)),
+        })
+        .collect();
+    
+    Ok(Json(BatchResponse { results }))
+}
+
+pub async fn get_accounts(
+    State(state): State<AppState>,
+    ClusterRpc(cluster): ClusterRpc,
+    Query(query): Query<AccountQuery>,
+    Json(batch_req): Json<BatchRequest>,
+) -> Result<Json<BatchResponse<AccountInfoResponse>>, AppError> {
+    info!("Getting account info for {} addresses", batch_req.addresses.len());
+    
+    let commitment = query.commitment()?;
+    let accounts = get_multiple_accounts(&cluster, batch_req.addresses, commitment).await?;
+    let found = accounts.iter().filter_map(|(_, account)| account.as_ref().ok()?.1.as_ref());
+    let decimals = mint_decimals(&cluster, found, commitment).await?;
+    let data_limit = query.data.then_some(MAX_BATCH_DATA_LEN);
+    
+    let results = accounts
+        .into_iter()
+        .map(|(address, account)| {
+            let account = account.and_then(|(pubkey, account)| {
+                account
+                    .map(|account| (pubkey, account))
+                    .ok_or_else(|| AppError::WalletNotFound(format!("Account not found: {}", address)))
+            });
+            match account {
+                Ok((pubkey, account)) => {
+                    BatchResult::Ok(account_response(address, pubkey, account, &decimals, &state.idls, data_limit))
+                }
+                Err(err) => BatchResult::Err(BatchError::new(address, err)),
+            }
+        })
+        .collect();
+    
+    Ok(Json(BatchResponse { results }))
+}
+
+// Fetches every valid address with concurrent getMultipleAccounts calls. Invalid
+// addresses get their own error instead of failing the batch; results keep request order.
+async fn get_multiple_accounts(
+    cluster: &Cluster,
+    addresses: Vec<String>,
+    commitment: CommitmentConfig,
+) -> Result<Vec<(String, Result<(Pubkey, Option<Account>), AppError>)>, AppError> {
+    if addresses.is_empty() || addresses.len() > MAX_BATCH_ADDRESSES {
+        return Err(AppError::BadRequest(format!(
+            "addresses must contain between 1 and {} entries",
+            MAX_BATCH_ADDRESSES
+        )));
+    }
+    
+    let pubkeys: Vec<Result<Pubkey, AppError>> = addresses.iter().map(|address| validate_pubkey(address)).collect();
+    let valid: Vec<Pubkey> = pubkeys.iter().filter_map(|pubkey| pubkey.as_ref().ok().copied()).collect();
+    let mut accounts = fetch_accounts(cluster, &valid, commitment).await?.into_iter();
+    
+    Ok(addresses
+        .into_iter()
+        .zip(pubkeys)
+        .map(|(address, pubkey)| {
+            let account = pubkey.map(|pubkey| (pubkey, accounts.next().flatten()));
+            (address, account)
+        })
+        .collect())
+}
+
+// getMultipleAccounts over any number of addresses, MULTIPLE_ACCOUNTS_CHUNK per concurrent call
+async fn fetch_accounts(
+    cluster: &Cluster,
+    pubkeys: &[Pubkey],
+    commitment: CommitmentConfig,
+) -> Result<Vec<Option<Account>>, AppError> {
+    let chunks = try_join_all(pubkeys.chunks(MULTIPLE_ACCOUNTS_CHUNK).map(|chunk| {
+        cluster.rpc_client.get_multiple_accounts_with_commitment(chunk, commitment)
     }))
+    .await?;
+    Ok(chunks.into_iter().flat_map(|chunk| chunk.value).collect())
+}
+
+// Decimals of the mint behind every token account in `accounts`, which parsing them needs
+async fn mint_decimals<'a>(
+    cluster: &Cluster,
+    accounts: impl IntoIterator<Item = &'a Account>,
+    commitment: CommitmentConfig,
+) -> Result<HashMap<Pubkey, u8>, AppError> {
+    let mints: Vec<Pubkey> = accounts
+        .into_iter()
+        .filter_map(decode::token_account_mint)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    if mints.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let mint_accounts = fetch_accounts(cluster, &mints, commitment).await?;
+    Ok(mints
+        .into_iter()
+        .zip(mint_accounts)
+        .filter_map(|(mint, account)| Some((mint, decode::mint_decimals(&account?)?)))
+        .collect())
+}
+
+// Raw data is left out unless `data_limit` is set, and cut to at most that many bytes
+fn account_response(
+    address: String,
+    pubkey: Pubkey,
+    account: Account,
+    decimals: &HashMap<Pubkey, u8>,
+    idls: &IdlRegistry,
+    data_limit: Option<usize>,
+) -> AccountInfoResponse {
+    let mint_decimals = decode::token_account_mint(&account).and_then(|mint| decimals.get(&mint).copied());
+    let data = data_limit.map(|limit| &account.data[..account.data.len().min(limit)]);
+    AccountInfoResponse {
+        parsed: decode::parse_account(&pubkey, &account, mint_decimals, idls),
+        address,
+        lamports: account.lamports,
+        owner: account.owner.to_string(),
+        executable: account.executable,
+        rent_epoch: account.rent_epoch,
+        data_len: account.data.len(),
+        data_truncated: data.map(|data| data.len() < account.data.len()),
+        data: data.map(|data| BASE64_STANDARD.encode(data)),
+    }
+}
+
+pub async fn get_token_accounts(
+    ClusterRpc(cluster): ClusterRpc,
+    Path(owner): Path<String>,
+    Query(query): Query<CommitmentQuery>,
+) -> Result<Json<TokenPortfolioResponse>, AppError> {
+    info!("Getting token accounts for owner: {}", owner);
+    
+    let owner_pubkey = validate_pubkey(&owner)?;
+    let commitment = query.commitment()?;
+    
+    // jsonParsed responses already carry mint decimals, so one call per token program is enough
+    let (token_accounts, token_2022_accounts) = tokio::try_join!(
+        cluster.rpc_client.get_token_accounts_by_owner_with_commitment(
+            &owner_pubkey,
+            TokenAccountsFilter::ProgramId(spl_token::id()),
+            commitment,
+        ),
+        cluster.rpc_client.get_token_accounts_by_owner_with_commitment(
+            &owner_pubkey,
+            TokenAccountsFilter::ProgramId(spl_token_2022::id()),
+            commitment,
+        ),
+    )?;
+    
+    let mut accounts = Vec::new();
+    for keyed in token_accounts.value {
+        accounts.push(parse_token_account(keyed, &owner_pubkey, &spl_token::id())?);
+    }
+    for keyed in token_2022_accounts.value {
+        accounts.push(parse_token_account(keyed, &owner_pubkey, &spl_token_2022::id())?);
+    }
+    
+    info!("Found {} token accounts for {}", accounts.len(), owner);
+    
+    Ok(Json(TokenPortfolioResponse { owner, accounts }))
+}
+
+fn parse_token_account(
+    keyed: RpcKeyedAccount,
+    owner: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<TokenAccountResponse, AppError> {
+    let parsed = match keyed.account.data {
+        UiAccountData::Json(parsed) => parsed.parsed,
+        _ => return Err(AppError::Internal(format!("Token account {} was not jsonParsed", keyed.pubkey))),
+    };
+    let token_account = match serde_json::from_value(parsed) {
+        Ok(TokenAccountType::Account(account)) => account,
+        _ => return Err(AppError::Internal(format!("Unexpected token account data for {}", keyed.pubkey))),
+    };
+    
+    let mint = validate_pubkey(&token_account.mint)?;
+    let associated = get_associated_token_address_with_program_id(owner, &mint, program_id);
+    
+    Ok(TokenAccountResponse {
+        is_associated: associated.to_string() == keyed.pubkey,
+        address: keyed.pubkey,
+        mint: token_account.mint,
+        program: program_id.to_string(),
+        amount: token_account.token_amount.amount,
+        decimals: token_account.token_amount.decimals,
+        ui_amount: token_account.token_amount.ui_amount_string,
+        frozen: token_account.state == UiAccountState::Frozen,
+    })
 }
 
 pub async fn transfer_sol(
     State(state): State<AppState>,
+    ClusterRpc(cluster): ClusterRpc,
+    caller: Caller,
     Json(transfer_req): Json<TransferRequest>,
-) -> Result<Json<TransferResponse>, AppError> {
-    info!("Processing transfer: {} SOL from {} to {}", 
-          transfer_req.amount, transfer_req.from, transfer_req.to);
-    
+) -> Result<(StatusCode, Json<TransferResponse>), AppError> {
     // Validate addresses
     let from_pubkey = validate_pubkey(&transfer_req.from)?;
+    caller.authorize_wallet(&from_pubkey)?;
     let to_pubkey = validate_pubkey(&transfer_req.to)?;
     
     // Validate amount
-    if transfer_req.amount <= 0.0 {
-        return Err(AppError::InvalidAmount("Amount must be greater than 0".to_string()));
-    }
+    let lamports = transfer_req.amount.to_lamports()?;
     
-    let lamports = sol_to_lamports(transfer_req.amount);
+    info!("Processing transfer: {} lamports from {} to {}", 
+          lamports, transfer_req.from, transfer_req.to);
     
     // Handle private key
-    let keypair = if let Some(private_key) = transfer_req.private_key {
-        // Parse private key from string (base58 format)
-        let private_key_bytes = bs58::decode(&private_key)
-            .into_vec()
-            .map_err(|_| AppError::BadRequest("Invalid private key format".to_string()))?;
-        
-        Keypair::from_bytes(&private_key_bytes)
-            .map_err(|_| AppError::BadRequest("Invalid private key".to_string()))?
-    } else {
-        // Create a new keypair for demo (this will have 0 balance)
-        return Err(AppError::BadRequest("Private key is required for transfers".to_string()));
-    };
-    
-    // Check if the from address matches the keypair
-    if keypair.pubkey() != from_pubkey {
-        return Err(AppError::BadRequest("Private key doesn't match from address".to_string()));
-    }
+    let keypair = parse_keypair(transfer_req.private_key.as_deref(), &from_pubkey)?;
+    let nonce = signed_nonce(&cluster, transfer_req.nonce.as_ref(), &from_pubkey).await?;
     
     // Create transfer instruction
     let transfer_instruction = system_instruction::transfer(
@@ -96,73 +353,675 @@ pub async fn transfer_sol(
         &to_pubkey,
         lamports,
     );
+    let (instructions, _) = fees::with_compute_budget(
+        &cluster.rpc_client,
+        &DurableNonce::with_advance(nonce.as_ref(), &[transfer_instruction]),
+        &from_pubkey,
+        transfer_req.fee_strategy,
+    ).await?;
     
     // Get recent blockhash
-    let recent_blockhash = state.rpc_client.get_latest_blockhash()?;
+    let (recent_blockhash, last_valid_block_height) = blockhash(&cluster, nonce.as_ref(), BLOCKHASH_COMMITMENT).await?;
     
     // Create and sign transaction
     let transaction = Transaction::new_signed_with_payer(
-        &[transfer_instruction],
+        &instructions,
         Some(&from_pubkey),
         &[&keypair],
         recent_blockhash,
     );
     
     // Send transaction
-    let signature = state.rpc_client.send_and_confirm_transaction(&transaction)?;
+    if transfer_req.async_mode {
+        let signature = tracker::submit(&state.store, &cluster, &transaction, last_valid_block_height).await?;
+        info!("Transfer sent! Signature: {}", signature);
+        return Ok(accepted(signature));
+    }
+    let signature = cluster.rpc_client.send_and_confirm_transaction(&transaction).await?;
     
     info!("Transfer successful! Signature: {}", signature);
     
-    Ok(Json(TransferResponse {
+    Ok((StatusCode::OK, Json(TransferResponse {
+        signature: signature.to_string(),
+        success: true,
+        message: format!("Successfully transferred {} SOL", lamports_to_sol(lamports)),
+    })))
+}
+
+pub async fn transfer_token(
+    State(state): State<AppState>,
+    ClusterRpc(cluster): ClusterRpc,
+    caller: Caller,
+    Json(transfer_req): Json<TokenTransferRequest>,
+) -> Result<(StatusCode, Json<TransferResponse>), AppError> {
+    // Validate addresses
+    let from_pubkey = validate_pubkey(&transfer_req.from)?;
+    caller.authorize_wallet(&from_pubkey)?;
+    let to_pubkey = validate_pubkey(&transfer_req.to)?;
+    let mint_pubkey = validate_pubkey(&transfer_req.mint)?;
+    
+    let keypair = parse_keypair(transfer_req.private_key.as_deref(), &from_pubkey)?;
+    let nonce = signed_nonce(&cluster, transfer_req.nonce.as_ref(), &from_pubkey).await?;
+    
+    // The mint's owner tells us which token program (and ATA derivation) to use
+    let mint_account = cluster.rpc_client
+        .get_account_with_commitment(&mint_pubkey, CommitmentConfig::confirmed())
+        .await?
+        .value
+        .ok_or_else(|| AppError::WalletNotFound(format!("Mint not found: {}", transfer_req.mint)))?;
+    let token_program_id = mint_account.owner;
+    if token_program_id != spl_token::id() && token_program_id != spl_token_2022::id() {
+        return Err(AppError::BadRequest(format!("{} is not an SPL token mint", transfer_req.mint)));
+    }
+    let decimals = StateWithExtensions::<Mint>::unpack(&mint_account.data)
+        .map_err(|_| AppError::BadRequest(format!("Invalid mint account: {}", transfer_req.mint)))?
+        .base
+        .decimals;
+    
+    let amount = transfer_req.to_base_units(decimals)?;
+    
+    info!("Processing token transfer: {} of mint {} from {} to {}",
+          format_decimal_amount(amount, decimals), mint_pubkey, from_pubkey, to_pubkey);
+    
+    let source = get_associated_token_address_with_program_id(&from_pubkey, &mint_pubkey, &token_program_id);
+    let destination = get_associated_token_address_with_program_id(&to_pubkey, &mint_pubkey, &token_program_id);
+    
+    let token_accounts = cluster.rpc_client
+        .get_multiple_accounts_with_commitment(&[source, destination], CommitmentConfig::confirmed())
+        .await?
+        .value;
+    
+    let source_account = token_accounts[0].as_ref().ok_or_else(|| {
+        AppError::WalletNotFound(format!("Source token account not found: {}", source))
+    })?;
+    let source_state = StateWithExtensions::<TokenAccount>::unpack(&source_account.data)
+        .map_err(|_| AppError::Internal(format!("Invalid token account: {}", source)))?
+        .base;
+    if source_state.state == AccountState::Frozen {
+        return Err(AppError::FrozenAccount(source.to_string()));
+    }
+    if source_state.amount < amount {
+        return Err(AppError::InvalidAmount(format!(
+            "Insufficient token balance: {} available",
+            format_decimal_amount(source_state.amount, decimals)
+        )));
+    }
+    
+    let mut instructions = Vec::new();
+    if token_accounts[1].is_none() {
+        instructions.push(create_associated_token_account_idempotent(
+            &from_pubkey,
+            &to_pubkey,
+            &mint_pubkey,
+            &token_program_id,
+        ));
+    }
+    instructions.push(
+        spl_token_2022::instruction::transfer_checked(
+            &token_program_id,
+            &source,
+            &mint_pubkey,
+            &destination,
+            &from_pubkey,
+            &[],
+            amount,
+            decimals,
+        )
+        .map_err(|e| AppError::Internal(format!("Failed to build transfer instruction: {}", e)))?,
+    );
+    
+    let (instructions, _) = fees::with_compute_budget(
+        &cluster.rpc_client,
+        &DurableNonce::with_advance(nonce.as_ref(), &instructions),
+        &from_pubkey,
+        transfer_req.fee_strategy,
+    ).await?;
+    
+    let (recent_blockhash, last_valid_block_height) = blockhash(&cluster, nonce.as_ref(), BLOCKHASH_COMMITMENT).await?;
+    let transaction = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&from_pubkey),
+        &[&keypair],
+        recent_blockhash,
+    );
+    
+    if transfer_req.async_mode {
+        let signature = tracker::submit(&state.store, &cluster, &transaction, last_valid_block_height).await?;
+        info!("Token transfer sent! Signature: {}", signature);
+        return Ok(accepted(signature));
+    }
+    let signature = cluster.rpc_client.send_and_confirm_transaction(&transaction).await?;
+    
+    info!("Token transfer successful! Signature: {}", signature);
+    
+    Ok((StatusCode::OK, Json(TransferResponse {
         signature: signature.to_string(),
         success: true,
-        message: format!("Successfully transferred {} SOL", transfer_req.amount),
+        message: format!(
+            "Successfully transferred {} tokens of mint {}",
+            format_decimal_amount(amount, decimals),
+            mint_pubkey
+        ),
+    })))
+}
+
+// 202 response for async mode; the tracker takes it from here
+fn accepted(signature: Signature) -> (StatusCode, Json<TransferResponse>) {
+    (StatusCode::ACCEPTED, Json(TransferResponse {
+        signature: signature.to_string(),
+        success: true,
+        message: format!("Transaction sent; poll /transaction/{}/status for confirmation", signature),
     }))
 }
 
+// Durable nonce for a server-signed transaction, whose only key is the `from` wallet's
+async fn signed_nonce(
+    cluster: &Cluster,
+    option: Option<&NonceOption>,
+    from: &Pubkey,
+) -> Result<Option<DurableNonce>, AppError> {
+    let nonce = DurableNonce::resolve(cluster, option, from).await?;
+    if nonce.as_ref().is_some_and(|nonce| nonce.authority != *from) {
+        return Err(AppError::BadRequest(
+            "The nonce authority must be the from wallet, since only its key signs here".to_string(),
+        ));
+    }
+    Ok(nonce)
+}
+
+// The durable nonce, which never expires, or the latest blockhash at `commitment` and its expiry height
+async fn blockhash(
+    cluster: &Cluster,
+    nonce: Option<&DurableNonce>,
+    commitment: CommitmentConfig,
+) -> Result<(Hash, Option<u64>), AppError> {
+    if let Some(nonce) = nonce {
+        return Ok((nonce.blockhash, None));
+    }
+    let (recent_blockhash, last_valid_block_height) =
+        cluster.rpc_client.get_latest_blockhash_with_commitment(commitment).await?;
+    Ok((recent_blockhash, Some(last_valid_block_height)))
+}
+
+// Decodes a base58 keypair and checks it signs for the expected address
+pub fn parse_keypair(private_key: Option<&str>, expected: &Pubkey) -> Result<Keypair, AppError> {
+    let private_key = private_key
+        .ok_or_else(|| AppError::BadRequest("Private key is required for transfers".to_string()))?;
+    
+    // Parse private key from string (base58 format)
+    let private_key_bytes = bs58::decode(private_key)
+        .into_vec()
+        .map_err(|_| AppError::BadRequest("Invalid private key format".to_string()))?;
+    
+    let keypair = Keypair::from_bytes(&private_key_bytes)
+        .map_err(|_| AppError::BadRequest("Invalid private key".to_string()))?;
+    
+    // Check if the from address matches the keypair
+    if keypair.pubkey() != *expected {
+        return Err(AppError::BadRequest("Private key doesn't match from address".to_string()));
+    }
+    
+    Ok(keypair)
+}
+
+pub async fn prepare_transfer(
+    ClusterRpc(cluster): ClusterRpc,
+    Json(prepare_req): Json<PrepareTransferRequest>,
+) -> Result<Json<PreparedTransactionResponse>, AppError> {
+    let (fee_payer, instructions) = sol_transfer_instructions(&prepare_req)?;
+    let nonce = DurableNonce::resolve(&cluster, prepare_req.nonce.as_ref(), &fee_payer).await?;
+    let prepared =
+        prepare_transaction(&cluster, &instructions, &fee_payer, prepare_req.fee_strategy, nonce.as_ref()).await?;
+    
+    match &nonce {
+        Some(nonce) => info!("Prepared transfer for {} using nonce account {}", fee_payer, nonce.account),
+        None => info!(
+            "Prepared transfer for {} valid until block height {}",
+            fee_payer,
+            prepared.last_valid_block_height.unwrap_or_default()
+        ),
+    }
+    
+    Ok(Json(prepared))
+}
+
+/// Wraps `instructions` in an unsigned transaction with a compute budget and a
+/// fresh blockhash, for the client to sign locally and hand back to /transaction/submit.
+/// With a durable `nonce` its advance comes first and the nonce stands in for the blockhash.
+pub async fn prepare_transaction(
+    cluster: &Cluster,
+    instructions: &[Instruction],
+    fee_payer: &Pubkey,
+    fee_strategy: fees::FeeStrategy,
+    nonce: Option<&DurableNonce>,
+) -> Result<PreparedTransactionResponse, AppError> {
+    let (recent_blockhash, last_valid_block_height) = blockhash(cluster, nonce, BLOCKHASH_COMMITMENT).await?;
+    
+    let (instructions, budget) = fees::with_compute_budget(
+        &cluster.rpc_client,
+        &DurableNonce::with_advance(nonce, instructions),
+        fee_payer,
+        fee_strategy,
+    ).await?;
+    
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(fee_payer));
+    transaction.message.recent_blockhash = recent_blockhash;
+    
+    Ok(PreparedTransactionResponse {
+        transaction: encode_transaction(&transaction)?,
+        blockhash: recent_blockhash.to_string(),
+        fee_payer: fee_payer.to_string(),
+        last_valid_block_height,
+        nonce_account: nonce.map(|nonce| nonce.account.to_string()),
+        compute_unit_limit: budget.unit_limit,
+        compute_unit_price: budget.unit_price,
+    })
+}
+
+// Validated SOL transfer instruction, paid for by `from`
+fn sol_transfer_instructions(
+    transfer_req: &PrepareTransferRequest,
+) -> Result<(Pubkey, Vec<Instruction>), AppError> {
+    let from_pubkey = validate_pubkey(&transfer_req.from)?;
+    let to_pubkey = validate_pubkey(&transfer_req.to)?;
+    let lamports = transfer_req.amount.to_lamports()?;
+    
+    info!("Building transfer: {} lamports from {} to {}",
+          lamports, transfer_req.from, transfer_req.to);
+    
+    Ok((from_pubkey, vec![system_instruction::transfer(&from_pubkey, &to_pubkey, lamports)]))
+}
+
+pub async fn simulate_transaction(
+    ClusterRpc(cluster): ClusterRpc,
+    Json(simulate_req): Json<SimulateRequest>,
+) -> Result<Json<SimulationResponse>, AppError> {
+    let (recent_blockhash, _) =
+        cluster.rpc_client.get_latest_blockhash_with_commitment(BLOCKHASH_COMMITMENT).await?;
+    
+    let transaction = match &simulate_req {
+        SimulateRequest::Transaction { transaction } => decode_transaction(transaction)?,
+        SimulateRequest::Transfer(transfer_req) => {
+            let (fee_payer, instructions) = sol_transfer_instructions(transfer_req)?;
+            let nonce = DurableNonce::resolve(&cluster, transfer_req.nonce.as_ref(), &fee_payer).await?;
+            let instructions = DurableNonce::with_advance(nonce.as_ref(), &instructions);
+            let (instructions, _) = fees::with_compute_budget(
+                &cluster.rpc_client,
+                &instructions,
+                &fee_payer,
+                transfer_req.fee_strategy,
+            ).await?;
+            let mut transaction = Transaction::new_with_payer(&instructions, Some(&fee_payer));
+            transaction.message.recent_blockhash = recent_blockhash;
+            transaction
+        }
+    };
+    
+    info!("Simulating transaction with fee payer {}", transaction.message.account_keys.first()
+        .map(|key| key.to_string())
+        .unwrap_or_default());
+    
+    // Fees don't depend on the blockhash, but the node only prices messages with a live one
+    let mut fee_message = transaction.message.clone();
+    fee_message.recent_blockhash = recent_blockhash;
+    
+    let (fee, simulation) = tokio::try_join!(
+        cluster.rpc_client.get_fee_for_message(&fee_message),
+        cluster.rpc_client.simulate_transaction_with_config(
+            &transaction,
+            RpcSimulateTransactionConfig {
+                sig_verify: false,
+                replace_recent_blockhash: true,
+                commitment: Some(CommitmentConfig::confirmed()),
+                ..RpcSimulateTransactionConfig::default()
+            },
+        ),
+    )?;
+    let simulation = simulation.value;
+    
+    Ok(Json(SimulationResponse {
+        success: simulation.err.is_none(),
+        error: simulation.err.map(SimulationError::from),
+        logs: simulation.logs.unwrap_or_default(),
+        units_consumed: simulation.units_consumed,
+        fee,
+        fee_sol: lamports_to_sol(fee),
+    }))
+}
+
+/// Checks a signature over `message` as raw bytes, the way wallet `signMessage` signs,
+/// and wrapped in a version 0 off-chain message header, the way Ledger signs.
+/// A signature that matches neither is a `200` with `valid: false`.
+pub async fn verify_message(
+    Json(verify_req): Json<VerifyMessageRequest>,
+) -> Result<Json<VerifyMessageResponse>, AppError> {
+    let pubkey = validate_pubkey(&verify_req.pubkey)?;
+    let signature = decode_signature(&verify_req.signature)?;
+    let message = match verify_req.encoding {
+        MessageEncoding::Utf8 => verify_req.message.into_bytes(),
+        MessageEncoding::Base64 => BASE64_STANDARD
+            .decode(&verify_req.message)
+            .map_err(|_| AppError::BadRequest("Message is not valid base64".to_string()))?,
+    };
+    
+    let (format, offchain) = if signature.verify(pubkey.as_ref(), &message) {
+        (Some(SignedMessageFormat::Raw), None)
+    } else {
+        // Binary or overlong messages can't be off-chain messages, so they just don't match
+        match OffchainMessage::new(0, &message) {
+            Ok(offchain) if offchain.verify(&pubkey, &signature).unwrap_or(false) => {
+                let format = match offchain.get_format() {
+                    MessageFormat::RestrictedAscii => "restricted_ascii",
+                    MessageFormat::LimitedUtf8 => "limited_utf8",
+                    MessageFormat::ExtendedUtf8 => "extended_utf8",
+                };
+                let header = OffchainHeader { version: offchain.get_version(), format: format.to_string() };
+                (Some(SignedMessageFormat::Offchain), Some(header))
+            }
+            _ => (None, None),
+        }
+    };
+    
+    info!("Verified message signature by {}: {:?}", pubkey, format);
+    
+    Ok(Json(VerifyMessageResponse { pubkey: pubkey.to_string(), valid: format.is_some(), format, offchain }))
+}
+
+pub async fn submit_transaction(
+    State(state): State<AppState>,
+    ClusterRpc(cluster): ClusterRpc,
+    caller: Caller,
+    Json(submit_req): Json<SubmitTransactionRequest>,
+) -> Result<(StatusCode, Json<TransferResponse>), AppError> {
+    let transaction = decode_transaction(&submit_req.transaction)?;
+    transaction
+        .sanitize()
+        .map_err(|e| AppError::BadRequest(format!("Transaction is malformed: {}", e)))?;
+    
+    // An unsigned message would otherwise pass both checks below vacuously
+    let num_signers = transaction.message.header.num_required_signatures as usize;
+    if transaction.signatures.is_empty() || transaction.signatures.len() != num_signers {
+        return Err(AppError::InvalidSignature(format!(
+            "Transaction carries {} signatures but its message requires {}",
+            transaction.signatures.len(),
+            num_signers
+        )));
+    }
+    
+    if !transaction.is_signed() {
+        return Err(AppError::InvalidSignature("Transaction is missing required signatures".to_string()));
+    }
+    
+    // Reject anything whose signatures don't cover this exact message
+    transaction
+        .verify()
+        .map_err(|_| AppError::InvalidSignature("Transaction signatures do not match message".to_string()))?;
+    
+    // Every signer is a wallet the transaction can spend from
+    for signer in transaction.message.account_keys.iter().take(num_signers) {
+        caller.authorize_wallet(signer)?;
+    }
+    
+    if let Some(signature) = transaction.signatures.first() {
+        info!("Submitting client-signed transaction: {}", signature);
+    }
+    
+    // The blockhash's expiry height isn't known here, so the tracker asks the cluster instead
+    if submit_req.async_mode {
+        let signature = tracker::submit(&state.store, &cluster, &transaction, None).await?;
+        return Ok(accepted(signature));
+    }
+    let signature = cluster.rpc_client.send_and_confirm_transaction(&transaction).await?;
+    
+    info!("Transaction submitted! Signature: {}", signature);
+    
+    Ok((StatusCode::OK, Json(TransferResponse {
+        signature: signature.to_string(),
+        success: true,
+        message: "Transaction submitted and confirmed".to_string(),
+    })))
+}
+
 pub async fn get_transaction(
     State(state): State<AppState>,
+    ClusterRpc(cluster): ClusterRpc,
     Path(signature): Path<String>,
+    Query(query): Query<CommitmentQuery>,
 ) -> Result<Json<TransactionResponse>, AppError> {
     info!("Getting transaction info for signature: {}", signature);
     
-    let sig = solana_sdk::signature::Signature::from_str(&signature)
-        .map_err(|_| AppError::InvalidSignature(format!("Invalid signature: {}", signature)))?;
+    let sig = parse_signature(&signature)?;
+    let commitment = query.confirmed_commitment()?;
     
-    let transaction = state.rpc_client.get_transaction_with_config(
+    // Fetch raw bytes and decode locally so every encoding path yields full account keys
+    let transaction = cluster.rpc_client.get_transaction_with_config(
         &sig, 
-        solana_client::rpc_config::RpcTransactionConfig {
-            encoding: Some(solana_transaction_status::UiTransactionEncoding::Json),
-            commitment: Some(CommitmentConfig::confirmed()),
+        RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::Base64),
+            commitment: Some(commitment),
             max_supported_transaction_version: Some(0),
         }
-    )?;
+    ).await?;
     
-    // transaction is already the actual transaction data, not an Option
-    let tx_info = transaction;
+    let response = decode::transaction_response(signature, transaction, &state.idls)?;
     
-    let accounts: Vec<String> = match &tx_info.transaction.transaction {
-        solana_transaction_status::EncodedTransaction::Json(ui_tx) => {
-            match &ui_tx.message {
-                solana_transaction_status::UiMessage::Parsed(parsed) => {
-                    parsed.account_keys.iter().map(|key| key.pubkey.to_string()).collect()
-                }
-                solana_transaction_status::UiMessage::Raw(raw) => {
-                    raw.account_keys.iter().map(|key| key.to_string()).collect()
-                }
-            }
-        }
-        _ => vec![], // Handle other encoding types if needed
+    info!("Transaction info retrieved for: {}", response.signature);
+    
+    Ok(Json(response))
+}
+
+pub async fn get_history(
+    ClusterRpc(cluster): ClusterRpc,
+    Path(address): Path<String>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Json<HistoryResponse>, AppError> {
+    info!("Getting transaction history for address: {}", address);
+    
+    let pubkey = validate_pubkey(&address)?;
+    let commitment = parse_confirmed_commitment(query.commitment.as_deref())?;
+    let limit = query.limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    let max_limit = if query.expand { MAX_EXPANDED_HISTORY_LIMIT } else { MAX_HISTORY_LIMIT };
+    if limit == 0 || limit > max_limit {
+        return Err(AppError::BadRequest(format!("limit must be between 1 and {}", max_limit)));
+    }
+    
+    let signatures = cluster.rpc_client.get_signatures_for_address_with_config(
+        &pubkey,
+        GetConfirmedSignaturesForAddress2Config {
+            before: query.before.as_deref().map(parse_signature).transpose()?,
+            until: query.until.as_deref().map(parse_signature).transpose()?,
+            limit: Some(limit),
+            commitment: Some(commitment),
+        },
+    ).await?;
+    
+    // A transaction that can't be fetched (pruned, or an RPC hiccup) only loses its own details
+    let details: Vec<Option<Result<_, AppError>>> = if query.expand {
+        let fetches: Vec<_> = signatures.iter().map(|status| async {
+            let sig = parse_signature(&status.signature)?;
+            let transaction = cluster.rpc_client.get_transaction_with_config(
+                &sig,
+                RpcTransactionConfig {
+                    encoding: Some(UiTransactionEncoding::Base64),
+                    commitment: Some(commitment),
+                    max_supported_transaction_version: Some(0),
+                },
+            ).await?;
+            Ok(transaction)
+        }).collect();
+        stream::iter(fetches)
+            .buffered(MAX_CONCURRENT_HISTORY_FETCHES)
+            .map(Some)
+            .collect()
+            .await
+    } else {
+        signatures.iter().map(|_| None).collect()
     };
     
-    info!("Transaction info retrieved for: {}", signature);
+    let next_before = if signatures.len() == limit {
+        signatures.last().map(|status| status.signature.clone())
+    } else {
+        None
+    };
+    
+    let transactions = signatures
+        .into_iter()
+        .zip(details)
+        .map(|(status, details)| {
+            let (transaction, expand_error) = match details {
+                Some(Ok(transaction)) => (Some(transaction), None),
+                Some(Err(err)) => (None, Some(err.to_string())),
+                None => (None, None),
+            };
+            let fee = transaction
+                .as_ref()
+                .and_then(|tx| tx.transaction.meta.as_ref())
+                .map(|meta| meta.fee);
+            let change = transaction.as_ref().and_then(|tx| sol_balance_change(tx, &pubkey));
+            HistoryEntry {
+                signature: status.signature,
+                slot: status.slot,
+                block_time: status.block_time,
+                success: status.err.is_none(),
+                error: status.err.map(|err| err.to_string()),
+                memo: status.memo,
+                confirmation_status: status.confirmation_status,
+                fee,
+                balance_change: change.map(lamport_change_to_sol),
+                balance_change_lamports: change,
+                expand_error,
+            }
+        })
+        .collect::<Vec<_>>();
+    
+    info!("Found {} transactions for {}", transactions.len(), address);
     
-    Ok(Json(TransactionResponse {
-        signature,
-        slot: tx_info.slot,
-        block_time: tx_info.block_time,
-        success: tx_info.transaction.meta.as_ref().map(|meta| meta.err.is_none()).unwrap_or(false),
-        fee: tx_info.transaction.meta.as_ref().map(|meta| meta.fee),
-        accounts,
+    Ok(Json(HistoryResponse {
+        address,
+        transactions,
+        next_before,
     }))
-} 
\ No newline at end of file
+}
+
+// Lamport change for `address`, looking through static keys then any v0 loaded addresses
+fn sol_balance_change(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+    address: &Pubkey,
+) -> Option<i64> {
+    let meta = transaction.transaction.meta.as_ref()?;
+    let decoded = transaction.transaction.transaction.decode()?;
+    
+    let address = address.to_string();
+    let loaded = Option::<UiLoadedAddresses>::from(meta.loaded_addresses.clone()).unwrap_or_default();
+    let index = decoded
+        .message
+        .static_account_keys()
+        .iter()
+        .map(|key| key.to_string())
+        .chain(loaded.writable)
+        .chain(loaded.readonly)
+        .position(|key| key == address)?;
+    
+    let pre = *meta.pre_balances.get(index)? as i128;
+    let post = *meta.post_balances.get(index)? as i128;
+    i64::try_from(post - pre).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn includes_raw_data_only_on_request() {
+        let account = Account { lamports: 1, data: vec![7; 100], owner: Pubkey::new_unique(), executable: false, rent_epoch: 0 };
+        let response = |data_limit| {
+            let address = Pubkey::new_unique();
+            account_response(address.to_string(), address, account.clone(), &HashMap::new(), &IdlRegistry::default(), data_limit)
+        };
+
+        let omitted = response(None);
+        assert_eq!((omitted.data_len, omitted.data, omitted.data_truncated), (100, None, None));
+
+        let full = response(Some(usize::MAX));
+        assert_eq!(full.data, Some(BASE64_STANDARD.encode([7; 100])));
+        assert_eq!(full.data_truncated, Some(false));
+
+        let capped = response(Some(10));
+        assert_eq!(capped.data, Some(BASE64_STANDARD.encode([7; 10])));
+        assert_eq!(capped.data_truncated, Some(true));
+        assert_eq!(capped.data_len, 100);
+    }
+
+    #[tokio::test]
+    async fn verifies_raw_and_offchain_message_signatures() {
+        let keypair = Keypair::new();
+        let verify = |body: serde_json::Value| async move {
+            let Json(response) = verify_message(Json(serde_json::from_value(body).unwrap())).await.unwrap();
+            serde_json::to_value(response).unwrap()
+        };
+        let message = "Sign in to prereqs";
+
+        let raw = keypair.sign_message(message.as_bytes());
+        let result = verify(serde_json::json!({
+            "pubkey": keypair.pubkey().to_string(),
+            "message": BASE64_STANDARD.encode(message),
+            "encoding": "base64",
+            "signature": raw.to_string(),
+        }))
+        .await;
+        assert_eq!((result["valid"].clone(), result["format"].clone()), (true.into(), "raw".into()));
+        assert!(result.get("offchain").is_none());
+
+        let offchain = OffchainMessage::new(0, message.as_bytes()).unwrap().sign(&keypair).unwrap();
+        let result = verify(serde_json::json!({
+            "pubkey": keypair.pubkey().to_string(),
+            "message": message,
+            "signature": BASE64_STANDARD.encode(offchain),
+        }))
+        .await;
+        assert_eq!(result["format"], "offchain");
+        assert_eq!(result["offchain"], serde_json::json!({ "version": 0, "format": "restricted_ascii" }));
+
+        let result = verify(serde_json::json!({
+            "pubkey": Keypair::new().pubkey().to_string(),
+            "message": message,
+            "signature": raw.to_string(),
+        }))
+        .await;
+        assert_eq!((result["valid"].clone(), result["format"].clone()), (false.into(), serde_json::Value::Null));
+
+        let bad_base64 = verify_message(Json(
+            serde_json::from_value(serde_json::json!({
+                "pubkey": keypair.pubkey().to_string(),
+                "message": "not base64!",
+                "encoding": "base64",
+                "signature": raw.to_string(),
+            }))
+            .unwrap(),
+        ))
+        .await
+        .unwrap_err();
+        assert_eq!(bad_base64.to_string(), "Bad request: Message is not valid base64");
+    }
+
+    #[tokio::test]
+    async fn rejects_transactions_without_signatures() {
+        let state = crate::tests::test_state(&["devnet"], crate::ratelimit::RateLimits::unlimited());
+        let cluster = state.clusters["devnet"].clone();
+        let unsigned = Transaction::new_unsigned(solana_sdk::message::Message::new(&[], None));
+        assert_eq!(unsigned.message.header.num_required_signatures, 0);
+
+        let error = submit_transaction(
+            State(state),
+            ClusterRpc(cluster),
+            Caller(None),
+            Json(SubmitTransactionRequest {
+                transaction: BASE64_STANDARD.encode(bincode::serialize(&unsigned).unwrap()),
+                async_mode: false,
+            }),
+        )
+        .await
+        .unwrap_err();
+        assert!(axum::response::IntoResponse::into_response(error).status().is_client_error());
+    }
+}
@@ -0,0 +1,274 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{OriginalUri, Request, State},
+    http::{HeaderName, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use redb::TableDefinition;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{sync::Arc, time::Duration};
+use tracing::warn;
+
+use crate::{
+    auth::Caller,
+    error::AppError,
+    ratelimit::{spawn_billed, ClientIp},
+    store::{Store, Table},
+};
+
+/// Idempotency keys by `{caller}:{Idempotency-Key}`, the caller being `key:{api key id}`,
+/// `ip:{client ip}` without authentication, or `anonymous` when neither is known
+const KEYS: Table = TableDefinition::new("idempotency_keys");
+
+const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+/// Set on responses replayed from an earlier request with the same key
+const REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+const MAX_KEY_LEN: usize = 255;
+/// Same limit as axum's `Json` extractor, which would reject anything larger anyway
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+/// How long a key is remembered
+const KEY_TTL: Duration = Duration::from_secs(24 * 3600);
+/// Handlers always finish and store their response, so a key still pending after this
+/// long was left by a server that stopped mid-request and may be taken over
+const PENDING_LEASE: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    /// SHA-256 of method, path, query and body
+    request_hash: String,
+    /// `None` while the first request is still being handled
+    response: Option<StoredResponse>,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredResponse {
+    status: u16,
+    body: String,
+}
+
+/// Middleware for write endpoints: the first request with a given
+/// `Idempotency-Key` runs normally and its response is stored; retries with
+/// the same key and body get that response back without running the handler.
+///
+/// Only successes and deterministic client errors are stored. Server errors such
+/// as RPC timeouts release the key so a retry runs the handler again. The handler
+/// runs on its own task, so its response is stored even if the client disconnects
+/// first. Requests without the header and `GET` requests pass through untouched.
+pub async fn idempotency(State(store): State<Arc<Store>>, request: Request, next: Next) -> Result<Response, AppError> {
+    if request.method() == Method::GET {
+        return Ok(next.run(request).await);
+    }
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY) else {
+        return Ok(next.run(request).await);
+    };
+    let key = key
+        .to_str()
+        .ok()
+        .filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LEN)
+        .ok_or_else(|| {
+            AppError::BadRequest(format!("Idempotency-Key must be 1-{} visible ASCII characters", MAX_KEY_LEN))
+        })?
+        .to_string();
+
+    // Keys are per caller so two clients can't see each other's responses
+    let owner = match (request.extensions().get::<Caller>(), request.extensions().get::<ClientIp>()) {
+        (Some(Caller(Some(api_key))), _) => format!("key:{}", api_key.id),
+        (_, Some(ClientIp(ip))) => format!("ip:{}", ip),
+        _ => "anonymous".to_string(),
+    };
+    let store_key = format!("{}:{}", owner, key);
+
+    let (parts, body) = request.into_parts();
+    let body = to_bytes(body, MAX_BODY_BYTES)
+        .await
+        .map_err(|err| AppError::BadRequest(format!("Failed to read request body: {}", err)))?;
+    // Nested cluster routes see their path without the `/{cluster}` prefix, which must count too
+    let uri = parts.extensions.get::<OriginalUri>().map_or(&parts.uri, |original| &original.0);
+    let request_hash = request_hash(&parts.method, &uri.to_string(), &body);
+
+    let existing = store.write(|txn| {
+        let existing: Option<Entry> = txn.get(KEYS, &store_key)?;
+        match existing {
+            Some(entry) if !expired(&entry) && !abandoned(&entry) => Ok(Some(entry)),
+            _ => {
+                let entry = Entry { request_hash: request_hash.clone(), response: None, created_at: Utc::now() };
+                txn.put(KEYS, &store_key, &entry)?;
+                Ok(None)
+            }
+        }
+    })?;
+
+    if let Some(entry) = existing {
+        if entry.request_hash != request_hash {
+            return Err(AppError::Conflict(format!(
+                "Idempotency-Key {} was already used with a different request",
+                key
+            )));
+        }
+        let Some(stored) = entry.response else {
+            return Err(AppError::Conflict(format!(
+                "A request with Idempotency-Key {} is still in progress",
+                key
+            )));
+        };
+        return Ok(replay(stored));
+    }
+
+    let request = Request::from_parts(parts, Body::from(body));
+    let handled = spawn_billed(async move {
+        let response = next.run(request).await;
+        let (parts, body) = response.into_parts();
+        let body = to_bytes(body, usize::MAX)
+            .await
+            .map_err(|err| AppError::Internal(format!("Failed to read response body: {}", err)))?;
+        let saved = if replayable(parts.status) {
+            let stored =
+                StoredResponse { status: parts.status.as_u16(), body: String::from_utf8_lossy(&body).into_owned() };
+            let entry = Entry { request_hash, response: Some(stored), created_at: Utc::now() };
+            store.put(KEYS, &store_key, &entry)
+        } else {
+            store.remove(KEYS, &store_key).map(|_| ())
+        };
+        if let Err(err) = saved {
+            // The handler already ran, so the client still gets its response
+            warn!("Failed to record response for Idempotency-Key {}: {}", key, err);
+        }
+        Ok::<_, AppError>((parts, body))
+    });
+    let (parts, body) = handled
+        .await
+        .map_err(|err| AppError::Internal(format!("Request handler failed: {}", err)))??;
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+fn request_hash(method: &Method, uri: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_str());
+    hasher.update(b" ");
+    hasher.update(uri);
+    hasher.update(b"\n");
+    hasher.update(body);
+    hex::encode(hasher.finalize())
+}
+
+// Timeouts and rate limits are client errors that a later retry may not repeat
+fn replayable(status: StatusCode) -> bool {
+    status.is_success()
+        || (status.is_client_error() && status != StatusCode::REQUEST_TIMEOUT && status != StatusCode::TOO_MANY_REQUESTS)
+}
+
+fn expired(entry: &Entry) -> bool {
+    let ttl = chrono::Duration::from_std(KEY_TTL).expect("key TTL fits in chrono::Duration");
+    Utc::now() - entry.created_at > ttl
+}
+
+fn abandoned(entry: &Entry) -> bool {
+    let lease = chrono::Duration::from_std(PENDING_LEASE).expect("lease fits in chrono::Duration");
+    entry.response.is_none() && Utc::now() - entry.created_at > lease
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let status = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let mut response = (status, stored.body).into_response();
+    let headers = response.headers_mut();
+    headers.insert(axum::http::header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    headers.insert(REPLAYED, HeaderValue::from_static("true"));
+    response
+}
+
+/// Forgets expired keys forever so the table doesn't grow without bound.
+pub async fn prune(store: Arc<Store>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let result = store.list::<Entry>(KEYS).and_then(|entries| {
+            for (key, entry) in entries {
+                if expired(&entry) {
+                    store.remove(KEYS, &key)?;
+                }
+            }
+            Ok(())
+        });
+        if let Err(err) = result {
+            warn!("Idempotency key pruning failed: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::post, Router};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tower::ServiceExt;
+
+    fn request(ip: &str, body: &'static str) -> Request {
+        let mut request = Request::post("/slow").header("idempotency-key", "retry-1").body(Body::from(body)).unwrap();
+        request.extensions_mut().insert(ClientIp(ip.parse().unwrap()));
+        request
+    }
+
+    #[tokio::test]
+    async fn stores_responses_of_abandoned_requests_per_client() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        let router = Router::new()
+            .route(
+                "/slow",
+                post(move || async move {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    counter.fetch_add(1, Ordering::Relaxed).to_string()
+                }),
+            )
+            .route_layer(middleware::from_fn_with_state(Arc::new(Store::in_memory()), idempotency));
+
+        // The client gives up before the handler finishes
+        let abandoned = tokio::time::timeout(Duration::from_millis(10), router.clone().oneshot(request("10.0.0.1", "a")));
+        assert!(abandoned.await.is_err());
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let retry = router.clone().oneshot(request("10.0.0.1", "a")).await.unwrap();
+        assert_eq!(retry.headers()["idempotent-replayed"], "true");
+        assert_eq!(to_bytes(retry.into_body(), usize::MAX).await.unwrap(), "0");
+
+        // Another client's key of the same name is its own
+        let other = router.oneshot(request("10.0.0.2", "b")).await.unwrap();
+        assert_eq!(other.status(), StatusCode::OK);
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn releases_keys_after_server_errors() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        let router = Router::new()
+            .route(
+                "/slow",
+                post(move || async move {
+                    match counter.fetch_add(1, Ordering::Relaxed) {
+                        0 => Err(AppError::Internal("RPC pool exhausted".to_string())),
+                        call => Ok(call.to_string()),
+                    }
+                }),
+            )
+            .route_layer(middleware::from_fn_with_state(Arc::new(Store::in_memory()), idempotency));
+
+        let failed = router.clone().oneshot(request("10.0.0.1", "a")).await.unwrap();
+        assert_eq!(failed.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let retry = router.clone().oneshot(request("10.0.0.1", "a")).await.unwrap();
+        assert_eq!(retry.status(), StatusCode::OK);
+        assert!(retry.headers().get("idempotent-replayed").is_none());
+        assert_eq!(to_bytes(retry.into_body(), usize::MAX).await.unwrap(), "1");
+
+        // The success is kept
+        let replayed = router.oneshot(request("10.0.0.1", "a")).await.unwrap();
+        assert_eq!(replayed.headers()["idempotent-replayed"], "true");
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+    }
+}
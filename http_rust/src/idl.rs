@@ -0,0 +1,1307 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use redb::TableDefinition;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::{Pubkey, MAX_SEEDS, MAX_SEED_LEN},
+};
+use std::{
+    collections::HashMap,
+    path::Path as FsPath,
+    str::FromStr,
+    sync::{Arc, RwLock},
+};
+use tracing::{info, warn};
+
+use crate::{
+    cluster::ClusterRpc,
+    decode::BorshReader,
+    error::AppError,
+    handlers,
+    nonce::DurableNonce,
+    store::{Store, Table},
+    types::*,
+    AppState,
+};
+
+/// IDLs registered through `POST /idl`, as uploaded, by program id
+const IDLS: Table = TableDefinition::new("idls");
+
+/// Prefix of the self-invocation `emit_cpi!` uses to record an event
+const EVENT_IX_TAG: [u8; 8] = 0x1d9a_cb51_2ea5_45e4u64.to_le_bytes();
+/// Guards against self-referencing type aliases
+const MAX_TYPE_DEPTH: usize = 32;
+
+/// An Anchor IDL. Both the 0.30+ format (explicit discriminators, `pubkey`) and
+/// the older one (discriminators derived from names, `publicKey`, inline types) parse.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Idl {
+    #[serde(default)]
+    pub address: Option<String>,
+    #[serde(default)]
+    pub metadata: IdlMetadata,
+    /// Pre-0.30 IDLs keep the name at the top level
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub instructions: Vec<IdlInstruction>,
+    #[serde(default)]
+    pub accounts: Vec<IdlTypeRef>,
+    #[serde(default)]
+    pub events: Vec<IdlTypeRef>,
+    #[serde(default)]
+    pub types: Vec<IdlTypeDef>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct IdlMetadata {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub address: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdlInstruction {
+    pub name: String,
+    #[serde(default)]
+    pub discriminator: Option<Vec<u8>>,
+    #[serde(default)]
+    pub accounts: Vec<IdlInstructionAccount>,
+    #[serde(default)]
+    pub args: Vec<IdlField>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdlInstructionAccount {
+    pub name: String,
+    #[serde(default, alias = "isMut")]
+    pub writable: bool,
+    #[serde(default, alias = "isSigner")]
+    pub signer: bool,
+    #[serde(default, alias = "isOptional")]
+    pub optional: bool,
+    /// Fixed address, like a program or sysvar
+    #[serde(default)]
+    pub address: Option<String>,
+    #[serde(default)]
+    pub pda: Option<IdlPda>,
+    /// Members of a nested `Accounts` struct, in order
+    #[serde(default)]
+    pub accounts: Vec<IdlInstructionAccount>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdlPda {
+    pub seeds: Vec<IdlSeed>,
+    /// Derive under this program instead of the IDL's own
+    #[serde(default, alias = "programId")]
+    pub program: Option<IdlSeed>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum IdlSeed {
+    /// Raw bytes in 0.30+ IDLs; older ones give a `type` and a value of that type
+    Const {
+        value: Value,
+        #[serde(default, rename = "type")]
+        ty: Option<IdlType>,
+    },
+    /// An instruction argument, or a field of one (`arg.field`)
+    Arg { path: String },
+    /// Another account of the instruction
+    Account { path: String },
+}
+
+/// An account or event: its discriminator, and its layout either inline
+/// (older IDLs) or in `types` under the same name
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdlTypeRef {
+    pub name: String,
+    #[serde(default)]
+    pub discriminator: Option<Vec<u8>>,
+    #[serde(default, rename = "type")]
+    pub ty: Option<IdlTypeDefTy>,
+    /// Older IDLs list event fields directly
+    #[serde(default)]
+    pub fields: Option<Vec<IdlField>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdlTypeDef {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: IdlTypeDefTy,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum IdlTypeDefTy {
+    Struct {
+        #[serde(default)]
+        fields: Option<IdlFields>,
+    },
+    Enum {
+        variants: Vec<IdlEnumVariant>,
+    },
+    Type {
+        alias: IdlType,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum IdlFields {
+    Named(Vec<IdlField>),
+    Tuple(Vec<IdlType>),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdlEnumVariant {
+    pub name: String,
+    #[serde(default)]
+    pub fields: Option<IdlFields>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdlField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: IdlType,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "Value")]
+pub enum IdlType {
+    /// `bool`, integers, floats, `string`, `bytes` or `pubkey`
+    Primitive(String),
+    Option(Box<IdlType>),
+    /// `COption`, with a four-byte tag
+    COption(Box<IdlType>),
+    Vec(Box<IdlType>),
+    Array(Box<IdlType>, usize),
+    Defined(String),
+}
+
+const PRIMITIVES: &[&str] = &[
+    "bool", "u8", "i8", "u16", "i16", "u32", "i32", "f32", "u64", "i64", "f64", "u128", "i128", "string", "bytes",
+    "pubkey",
+];
+
+impl TryFrom<Value> for IdlType {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, String> {
+        let unsupported = |value: &Value| format!("Unsupported IDL type: {}", value);
+        match &value {
+            Value::String(name) if name == "publicKey" => Ok(IdlType::Primitive("pubkey".to_string())),
+            Value::String(name) if PRIMITIVES.contains(&name.as_str()) => Ok(IdlType::Primitive(name.clone())),
+            Value::Object(map) if map.len() == 1 => {
+                let (kind, inner) = map.iter().next().unwrap();
+                let boxed = |inner: &Value| IdlType::try_from(inner.clone()).map(Box::new);
+                match (kind.as_str(), inner) {
+                    ("option", inner) => Ok(IdlType::Option(boxed(inner)?)),
+                    ("coption", inner) => Ok(IdlType::COption(boxed(inner)?)),
+                    ("vec", inner) => Ok(IdlType::Vec(boxed(inner)?)),
+                    ("array", Value::Array(parts)) if parts.len() == 2 => {
+                        let len = parts[1].as_u64().ok_or_else(|| unsupported(&value))?;
+                        Ok(IdlType::Array(boxed(&parts[0])?, len as usize))
+                    }
+                    // `{"defined": "Name"}` before 0.30, `{"defined": {"name": "Name"}}` after
+                    ("defined", Value::String(name)) => Ok(IdlType::Defined(name.clone())),
+                    ("defined", Value::Object(defined)) => match defined.get("name") {
+                        Some(Value::String(name)) => Ok(IdlType::Defined(name.clone())),
+                        _ => Err(unsupported(&value)),
+                    },
+                    _ => Err(unsupported(&value)),
+                }
+            }
+            _ => Err(unsupported(&value)),
+        }
+    }
+}
+
+impl IdlInstruction {
+    fn discriminator(&self) -> Vec<u8> {
+        self.discriminator.clone().unwrap_or_else(|| sighash("global", &snake_case(&self.name)))
+    }
+
+    /// Accounts in the order the instruction takes them, nested groups flattened
+    fn flat_accounts(&self) -> Vec<&IdlInstructionAccount> {
+        fn flatten<'a>(accounts: &'a [IdlInstructionAccount], flat: &mut Vec<&'a IdlInstructionAccount>) {
+            for account in accounts {
+                if account.accounts.is_empty() {
+                    flat.push(account);
+                } else {
+                    flatten(&account.accounts, flat);
+                }
+            }
+        }
+        let mut flat = Vec::new();
+        flatten(&self.accounts, &mut flat);
+        flat
+    }
+
+    pub fn account_names(&self) -> Vec<&str> {
+        self.flat_accounts().into_iter().map(|account| account.name.as_str()).collect()
+    }
+}
+
+impl IdlTypeRef {
+    fn discriminator(&self, namespace: &str) -> Vec<u8> {
+        self.discriminator.clone().unwrap_or_else(|| sighash(namespace, &self.name))
+    }
+}
+
+impl Idl {
+    pub fn name(&self) -> &str {
+        self.metadata.name.as_deref().or(self.name.as_deref()).unwrap_or("anchor")
+    }
+
+    fn program_id(&self) -> Option<&str> {
+        self.address.as_deref().or(self.metadata.address.as_deref())
+    }
+
+    /// Checks that every `defined` type the IDL uses is declared somewhere in it
+    fn validate(&self) -> Result<(), String> {
+        let mut used = Vec::new();
+        for instruction in &self.instructions {
+            used.extend(instruction.args.iter().map(|arg| &arg.ty));
+        }
+        for type_ref in self.accounts.iter().chain(&self.events) {
+            used.extend(type_ref.fields.iter().flatten().map(|field| &field.ty));
+            if let Some(ty) = &type_ref.ty {
+                collect_types(ty, &mut used);
+            }
+            if type_ref.ty.is_none() && type_ref.fields.is_none() && self.find_type(&type_ref.name).is_none() {
+                return Err(format!("Type {} is not defined", type_ref.name));
+            }
+        }
+        for def in &self.types {
+            collect_types(&def.ty, &mut used);
+        }
+
+        while let Some(ty) = used.pop() {
+            match ty {
+                IdlType::Primitive(_) => {}
+                IdlType::Option(inner) | IdlType::COption(inner) | IdlType::Vec(inner) | IdlType::Array(inner, _) => {
+                    used.push(inner)
+                }
+                IdlType::Defined(name) => {
+                    if self.find_type(name).is_none() {
+                        return Err(format!("Type {} is not defined", name));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Named types live in `types`, or inline on an account in older IDLs
+    fn find_type(&self, name: &str) -> Option<&IdlTypeDefTy> {
+        self.types
+            .iter()
+            .find(|def| def.name == name)
+            .map(|def| &def.ty)
+            .or_else(|| self.accounts.iter().find(|account| account.name == name)?.ty.as_ref())
+    }
+
+    /// The account type whose discriminator `data` starts with, and its fields
+    pub fn decode_account(&self, data: &[u8]) -> Option<(String, Value)> {
+        self.accounts.iter().find_map(|account| {
+            let body = data.strip_prefix(account.discriminator("account").as_slice())?;
+            // Accounts are often allocated larger than their contents; trailing bytes are ignored
+            let value = self.decode_type_ref(account, &mut BorshReader::new(body))?;
+            Some((account.name.clone(), value))
+        })
+    }
+
+    /// An event from `Program data:` logs or an `emit_cpi!` instruction, without its tag
+    pub fn decode_event(&self, data: &[u8]) -> Option<(String, Value)> {
+        self.events.iter().find_map(|event| {
+            let body = data.strip_prefix(event.discriminator("event").as_slice())?;
+            let value = self.decode_type_ref(event, &mut BorshReader::new(body))?;
+            Some((event.name.clone(), value))
+        })
+    }
+
+    /// `{ "type": name, "info": { "args": ..., "accounts": { name: address } } }` for one
+    /// of the program's instructions, or `{ "type": "event", ... }` for an `emit_cpi!` event
+    pub fn decode_instruction(&self, data: &[u8], accounts: &[String]) -> Option<Value> {
+        if let Some(event) = data.strip_prefix(EVENT_IX_TAG.as_slice()) {
+            let (name, event) = self.decode_event(event)?;
+            return Some(json!({ "type": "event", "info": { "name": name, "data": event } }));
+        }
+        self.instructions.iter().find_map(|instruction| {
+            let body = data.strip_prefix(instruction.discriminator().as_slice())?;
+            let mut reader = BorshReader::new(body);
+            let mut args = Map::new();
+            for arg in &instruction.args {
+                args.insert(arg.name.clone(), self.decode_value(&arg.ty, &mut reader, 0)?);
+            }
+            let named: Map<String, Value> = instruction
+                .account_names()
+                .into_iter()
+                .zip(accounts)
+                .map(|(name, address)| (name.to_string(), json!(address)))
+                .collect();
+            Some(json!({ "type": instruction.name, "info": { "args": args, "accounts": named } }))
+        })
+    }
+
+    fn decode_type_ref(&self, type_ref: &IdlTypeRef, reader: &mut BorshReader) -> Option<Value> {
+        if let Some(ty) = &type_ref.ty {
+            return self.decode_type_def(ty, reader, 0);
+        }
+        if let Some(fields) = &type_ref.fields {
+            return self.decode_named_fields(fields, reader, 0);
+        }
+        self.decode_type_def(self.find_type(&type_ref.name)?, reader, 0)
+    }
+
+    fn decode_type_def(&self, ty: &IdlTypeDefTy, reader: &mut BorshReader, depth: usize) -> Option<Value> {
+        match ty {
+            IdlTypeDefTy::Struct { fields } => self.decode_fields(fields.as_ref(), reader, depth),
+            // Unit variants read as their name, others as `{ name: fields }`
+            IdlTypeDefTy::Enum { variants } => {
+                let variant = variants.get(reader.u8()? as usize)?;
+                match &variant.fields {
+                    None => Some(json!(variant.name)),
+                    Some(fields) => Some(json!({ &variant.name: self.decode_fields(Some(fields), reader, depth)? })),
+                }
+            }
+            IdlTypeDefTy::Type { alias } => self.decode_value(alias, reader, depth + 1),
+        }
+    }
+
+    fn decode_fields(&self, fields: Option<&IdlFields>, reader: &mut BorshReader, depth: usize) -> Option<Value> {
+        match fields {
+            None => Some(json!({})),
+            Some(IdlFields::Named(fields)) => self.decode_named_fields(fields, reader, depth),
+            Some(IdlFields::Tuple(types)) => types
+                .iter()
+                .map(|ty| self.decode_value(ty, reader, depth + 1))
+                .collect::<Option<Vec<_>>>()
+                .map(Value::Array),
+        }
+    }
+
+    fn decode_named_fields(&self, fields: &[IdlField], reader: &mut BorshReader, depth: usize) -> Option<Value> {
+        let mut map = Map::new();
+        for field in fields {
+            map.insert(field.name.clone(), self.decode_value(&field.ty, reader, depth + 1)?);
+        }
+        Some(Value::Object(map))
+    }
+
+    // Integers up to 64 bits are JSON numbers; 128-bit ones are decimal strings.
+    // `bytes` are base64, like account data elsewhere in the API.
+    fn decode_value(&self, ty: &IdlType, reader: &mut BorshReader, depth: usize) -> Option<Value> {
+        if depth > MAX_TYPE_DEPTH {
+            return None;
+        }
+        let value = match ty {
+            IdlType::Primitive(name) => match name.as_str() {
+                "bool" => json!(reader.bool()?),
+                "u8" => json!(reader.u8()?),
+                "i8" => json!(i8::from_le_bytes(reader.take()?)),
+                "u16" => json!(reader.u16()?),
+                "i16" => json!(i16::from_le_bytes(reader.take()?)),
+                "u32" => json!(reader.u32()?),
+                "i32" => json!(i32::from_le_bytes(reader.take()?)),
+                "f32" => json!(f32::from_le_bytes(reader.take()?)),
+                "u64" => json!(reader.u64()?),
+                "i64" => json!(i64::from_le_bytes(reader.take()?)),
+                "f64" => json!(f64::from_le_bytes(reader.take()?)),
+                "u128" => json!(u128::from_le_bytes(reader.take()?).to_string()),
+                "i128" => json!(i128::from_le_bytes(reader.take()?).to_string()),
+                "string" => json!(reader.string()?),
+                "bytes" => json!(BASE64_STANDARD.encode(reader.bytes()?)),
+                "pubkey" => json!(reader.pubkey()?.to_string()),
+                _ => return None,
+            },
+            IdlType::Option(inner) => {
+                reader.option(|reader| self.decode_value(inner, reader, depth + 1))?.unwrap_or(Value::Null)
+            }
+            IdlType::COption(inner) => match u32::from_le_bytes(reader.take()?) {
+                0 => Value::Null,
+                1 => self.decode_value(inner, reader, depth + 1)?,
+                _ => return None,
+            },
+            IdlType::Vec(inner) => {
+                let len = reader.u32()? as usize;
+                self.decode_sequence(inner, len, reader, depth)?
+            }
+            IdlType::Array(inner, len) => self.decode_sequence(inner, *len, reader, depth)?,
+            IdlType::Defined(name) => self.decode_type_def(self.find_type(name)?, reader, depth + 1)?,
+        };
+        Some(value)
+    }
+
+    fn decode_sequence(&self, ty: &IdlType, len: usize, reader: &mut BorshReader, depth: usize) -> Option<Value> {
+        // Counted before decoding, so a corrupt length fails without building anything
+        reader.claim_elements(len)?;
+        (0..len)
+            .map(|_| self.decode_value(ty, reader, depth + 1))
+            .collect::<Option<Vec<_>>>()
+            .map(Value::Array)
+    }
+}
+
+impl Idl {
+    /// Builds instruction `name` from JSON `args` and account addresses by name.
+    /// Accounts left out are filled in from fixed `address`es and `pda` seeds; optional
+    /// ones default to the program id, which Anchor reads as `None`. Returns the
+    /// instruction and the IDL name of each of its accounts.
+    pub fn build_instruction(
+        &self,
+        program_id: &Pubkey,
+        name: &str,
+        args: &Map<String, Value>,
+        accounts: &HashMap<String, String>,
+    ) -> Result<(Instruction, Vec<String>), AppError> {
+        let instruction = self
+            .instructions
+            .iter()
+            .find(|instruction| instruction.name == name)
+            .ok_or_else(|| AppError::NotFound(format!("{} has no instruction {}", self.name(), name)))?;
+
+        if let Some(unknown) = args.keys().find(|key| !instruction.args.iter().any(|arg| &arg.name == *key)) {
+            return Err(AppError::BadRequest(format!("Unknown arg {} for {}", unknown, name)));
+        }
+        let mut data = instruction.discriminator();
+        for arg in &instruction.args {
+            let value = args.get(&arg.name).unwrap_or(&Value::Null);
+            self.encode_value(&arg.ty, value, &mut data, 0)
+                .map_err(|err| AppError::BadRequest(format!("Invalid arg {}: {}", arg.name, err)))?;
+        }
+
+        let flat = instruction.flat_accounts();
+        let mut resolved: HashMap<&str, Pubkey> = HashMap::new();
+        for (account_name, address) in accounts {
+            let account = flat
+                .iter()
+                .find(|account| &account.name == account_name)
+                .ok_or_else(|| AppError::BadRequest(format!("Unknown account {} for {}", account_name, name)))?;
+            resolved.insert(&account.name, validate_pubkey(address)?);
+        }
+        for account in &flat {
+            if let (Some(address), false) = (&account.address, resolved.contains_key(account.name.as_str())) {
+                let address = address.parse().map_err(|_| {
+                    AppError::BadRequest(format!("IDL address of {} is invalid: {}", account.name, address))
+                })?;
+                resolved.insert(&account.name, address);
+            }
+        }
+        // Seeds can name other PDAs, so derive until a pass adds nothing
+        loop {
+            let mut derived = false;
+            for account in &flat {
+                let Some(pda) = &account.pda else { continue };
+                if resolved.contains_key(account.name.as_str()) {
+                    continue;
+                }
+                if let Some(address) = self.derive_pda(program_id, instruction, &account.name, pda, args, &resolved)? {
+                    resolved.insert(&account.name, address);
+                    derived = true;
+                }
+            }
+            if !derived {
+                break;
+            }
+        }
+
+        let missing: Vec<&str> = flat
+            .iter()
+            .filter(|account| !account.optional && !resolved.contains_key(account.name.as_str()))
+            .map(|account| account.name.as_str())
+            .collect();
+        if !missing.is_empty() {
+            return Err(AppError::BadRequest(format!("Missing accounts for {}: {}", name, missing.join(", "))));
+        }
+        let metas = flat
+            .iter()
+            .map(|account| match resolved.get(account.name.as_str()) {
+                Some(pubkey) => AccountMeta { pubkey: *pubkey, is_signer: account.signer, is_writable: account.writable },
+                None => AccountMeta::new_readonly(*program_id, false),
+            })
+            .collect();
+        let names = flat.iter().map(|account| account.name.clone()).collect();
+        Ok((Instruction { program_id: *program_id, accounts: metas, data }, names))
+    }
+
+    // `None` while a seed names an account that isn't resolved yet
+    fn derive_pda(
+        &self,
+        program_id: &Pubkey,
+        instruction: &IdlInstruction,
+        account: &str,
+        pda: &IdlPda,
+        args: &Map<String, Value>,
+        resolved: &HashMap<&str, Pubkey>,
+    ) -> Result<Option<Pubkey>, AppError> {
+        let mut seeds = Vec::with_capacity(pda.seeds.len());
+        for seed in &pda.seeds {
+            match self.seed_bytes(instruction, account, seed, args, resolved)? {
+                Some(bytes) => seeds.push(bytes),
+                None => return Ok(None),
+            }
+        }
+        let program = match &pda.program {
+            None => *program_id,
+            Some(seed) => match self.seed_bytes(instruction, account, seed, args, resolved)? {
+                Some(bytes) => Pubkey::try_from(bytes.as_slice()).map_err(|_| {
+                    AppError::BadRequest(format!("PDA program of {} is not a public key", account))
+                })?,
+                None => return Ok(None),
+            },
+        };
+        // The bump seed takes the last slot
+        if seeds.len() >= MAX_SEEDS || seeds.iter().any(|seed| seed.len() > MAX_SEED_LEN) {
+            return Err(AppError::BadRequest(format!(
+                "Seeds of {} exceed {} seeds of {} bytes",
+                account,
+                MAX_SEEDS - 1,
+                MAX_SEED_LEN
+            )));
+        }
+        let seeds: Vec<&[u8]> = seeds.iter().map(Vec::as_slice).collect();
+        Pubkey::try_find_program_address(&seeds, &program)
+            .map(|(address, _)| Some(address))
+            .ok_or_else(|| AppError::BadRequest(format!("No PDA exists for the seeds of {}", account)))
+    }
+
+    fn seed_bytes(
+        &self,
+        instruction: &IdlInstruction,
+        account: &str,
+        seed: &IdlSeed,
+        args: &Map<String, Value>,
+        resolved: &HashMap<&str, Pubkey>,
+    ) -> Result<Option<Vec<u8>>, AppError> {
+        let invalid = |err: String| AppError::BadRequest(format!("Seed of {}: {}", account, err));
+        match seed {
+            IdlSeed::Const { value, ty } => {
+                let bytes = IdlType::Vec(Box::new(IdlType::Primitive("u8".to_string())));
+                self.seed_value(ty.as_ref().unwrap_or(&bytes), value).map(Some).map_err(invalid)
+            }
+            IdlSeed::Arg { path } => {
+                let mut fields = path.split('.');
+                let arg_name = fields.next().unwrap_or_default();
+                let unknown = || invalid(format!("unknown arg {}", path));
+                let arg = instruction.args.iter().find(|arg| arg.name == arg_name).ok_or_else(unknown)?;
+                let mut ty = &arg.ty;
+                let mut value = args.get(arg_name).unwrap_or(&Value::Null);
+                for field in fields {
+                    ty = self.field_type(ty, field).ok_or_else(unknown)?;
+                    value = value.get(field).unwrap_or(&Value::Null);
+                }
+                self.seed_value(ty, value).map(Some).map_err(|err| invalid(format!("arg {}: {}", path, err)))
+            }
+            IdlSeed::Account { path } => {
+                if let Some(address) = resolved.get(path.as_str()) {
+                    return Ok(Some(address.to_bytes().to_vec()));
+                }
+                if instruction.flat_accounts().iter().any(|other| other.name == *path) {
+                    return Ok(None);
+                }
+                // `account.field` seeds come from account data, which isn't fetched
+                Err(AppError::BadRequest(format!("{} is seeded by {}; pass its address in accounts", account, path)))
+            }
+        }
+    }
+
+    // Anchor seeds strings and byte vectors by their contents, without the length prefix
+    fn seed_value(&self, ty: &IdlType, value: &Value) -> Result<Vec<u8>, String> {
+        let mut bytes = Vec::new();
+        self.encode_value(ty, value, &mut bytes, 0)?;
+        if matches!(ty, IdlType::Vec(_)) || matches!(ty, IdlType::Primitive(name) if name == "string" || name == "bytes") {
+            bytes.drain(..4);
+        }
+        Ok(bytes)
+    }
+
+    fn field_type<'a>(&'a self, ty: &'a IdlType, field: &str) -> Option<&'a IdlType> {
+        let IdlType::Defined(name) = ty else { return None };
+        match self.find_type(name)? {
+            IdlTypeDefTy::Struct { fields: Some(IdlFields::Named(fields)) } => {
+                fields.iter().find(|candidate| candidate.name == field).map(|field| &field.ty)
+            }
+            _ => None,
+        }
+    }
+
+    /// Borsh-encodes `value` as `ty`: the inverse of `decode_value`, except that
+    /// integers of any size may also be given as decimal strings
+    fn encode_value(&self, ty: &IdlType, value: &Value, out: &mut Vec<u8>, depth: usize) -> Result<(), String> {
+        if depth > MAX_TYPE_DEPTH {
+            return Err("types nest too deeply".to_string());
+        }
+        let expected = |what: &str| format!("expected {}, got {}", what, value);
+        match ty {
+            IdlType::Primitive(name) => {
+                let name = name.as_str();
+                match name {
+                    "bool" => out.push(value.as_bool().ok_or_else(|| expected(name))? as u8),
+                    "u8" => out.extend(integer::<u8>(value).ok_or_else(|| expected(name))?.to_le_bytes()),
+                    "i8" => out.extend(integer::<i8>(value).ok_or_else(|| expected(name))?.to_le_bytes()),
+                    "u16" => out.extend(integer::<u16>(value).ok_or_else(|| expected(name))?.to_le_bytes()),
+                    "i16" => out.extend(integer::<i16>(value).ok_or_else(|| expected(name))?.to_le_bytes()),
+                    "u32" => out.extend(integer::<u32>(value).ok_or_else(|| expected(name))?.to_le_bytes()),
+                    "i32" => out.extend(integer::<i32>(value).ok_or_else(|| expected(name))?.to_le_bytes()),
+                    "f32" => out.extend((value.as_f64().ok_or_else(|| expected(name))? as f32).to_le_bytes()),
+                    "u64" => out.extend(integer::<u64>(value).ok_or_else(|| expected(name))?.to_le_bytes()),
+                    "i64" => out.extend(integer::<i64>(value).ok_or_else(|| expected(name))?.to_le_bytes()),
+                    "f64" => out.extend(value.as_f64().ok_or_else(|| expected(name))?.to_le_bytes()),
+                    "u128" => out.extend(integer::<u128>(value).ok_or_else(|| expected(name))?.to_le_bytes()),
+                    "i128" => out.extend(integer::<i128>(value).ok_or_else(|| expected(name))?.to_le_bytes()),
+                    "string" => encode_bytes(value.as_str().ok_or_else(|| expected("a string"))?.as_bytes(), out),
+                    "bytes" => {
+                        let bytes = value.as_str().and_then(|value| BASE64_STANDARD.decode(value).ok());
+                        encode_bytes(&bytes.ok_or_else(|| expected("base64 bytes"))?, out);
+                    }
+                    "pubkey" => {
+                        let pubkey: Option<Pubkey> = value.as_str().and_then(|value| value.parse().ok());
+                        out.extend(pubkey.ok_or_else(|| expected("a public key"))?.to_bytes());
+                    }
+                    _ => return Err(format!("unsupported type {}", name)),
+                }
+            }
+            IdlType::Option(inner) => {
+                if value.is_null() {
+                    out.push(0);
+                } else {
+                    out.push(1);
+                    self.encode_value(inner, value, out, depth + 1)?;
+                }
+            }
+            IdlType::COption(inner) => {
+                if value.is_null() {
+                    out.extend(0u32.to_le_bytes());
+                } else {
+                    out.extend(1u32.to_le_bytes());
+                    self.encode_value(inner, value, out, depth + 1)?;
+                }
+            }
+            IdlType::Vec(inner) => {
+                let items = value.as_array().ok_or_else(|| expected("an array"))?;
+                out.extend((items.len() as u32).to_le_bytes());
+                self.encode_sequence(inner, items, out, depth)?;
+            }
+            IdlType::Array(inner, len) => {
+                let items = value
+                    .as_array()
+                    .filter(|items| items.len() == *len)
+                    .ok_or_else(|| expected(&format!("an array of {}", len)))?;
+                self.encode_sequence(inner, items, out, depth)?;
+            }
+            IdlType::Defined(name) => {
+                let def = self.find_type(name).ok_or_else(|| format!("type {} is not defined", name))?;
+                self.encode_type_def(def, value, out, depth + 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn encode_sequence(&self, ty: &IdlType, items: &[Value], out: &mut Vec<u8>, depth: usize) -> Result<(), String> {
+        for (index, item) in items.iter().enumerate() {
+            self.encode_value(ty, item, out, depth + 1).map_err(|err| format!("[{}]: {}", index, err))?;
+        }
+        Ok(())
+    }
+
+    fn encode_type_def(&self, ty: &IdlTypeDefTy, value: &Value, out: &mut Vec<u8>, depth: usize) -> Result<(), String> {
+        match ty {
+            IdlTypeDefTy::Struct { fields } => self.encode_fields(fields.as_ref(), value, out, depth),
+            // A unit variant's name, or `{ name: fields }`, as decoded
+            IdlTypeDefTy::Enum { variants } => {
+                let (name, fields) = match value {
+                    Value::String(name) => (name.as_str(), &Value::Null),
+                    Value::Object(map) if map.len() == 1 => {
+                        let (name, fields) = map.iter().next().unwrap();
+                        (name.as_str(), fields)
+                    }
+                    _ => return Err(format!("expected an enum variant, got {}", value)),
+                };
+                let index = variants
+                    .iter()
+                    .position(|variant| variant.name == name)
+                    .ok_or_else(|| format!("unknown variant {}", name))?;
+                out.push(index as u8);
+                self.encode_fields(variants[index].fields.as_ref(), fields, out, depth)
+            }
+            IdlTypeDefTy::Type { alias } => self.encode_value(alias, value, out, depth + 1),
+        }
+    }
+
+    fn encode_fields(&self, fields: Option<&IdlFields>, value: &Value, out: &mut Vec<u8>, depth: usize) -> Result<(), String> {
+        match fields {
+            None => Ok(()),
+            Some(IdlFields::Named(fields)) => {
+                let map = value.as_object().ok_or_else(|| format!("expected an object, got {}", value))?;
+                for field in fields {
+                    let item = map.get(&field.name).unwrap_or(&Value::Null);
+                    self.encode_value(&field.ty, item, out, depth + 1)
+                        .map_err(|err| format!("{}: {}", field.name, err))?;
+                }
+                Ok(())
+            }
+            Some(IdlFields::Tuple(types)) => {
+                let items = value
+                    .as_array()
+                    .filter(|items| items.len() == types.len())
+                    .ok_or_else(|| format!("expected an array of {}, got {}", types.len(), value))?;
+                for (index, (ty, item)) in types.iter().zip(items).enumerate() {
+                    self.encode_value(ty, item, out, depth + 1).map_err(|err| format!("[{}]: {}", index, err))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// Integers are JSON numbers, or decimal strings past 2^53 and for 128-bit types
+fn integer<T: FromStr + TryFrom<u64> + TryFrom<i64>>(value: &Value) -> Option<T> {
+    match value {
+        Value::Number(number) => number
+            .as_u64()
+            .and_then(|number| T::try_from(number).ok())
+            .or_else(|| number.as_i64().and_then(|number| T::try_from(number).ok())),
+        Value::String(number) => number.parse().ok(),
+        _ => None,
+    }
+}
+
+fn encode_bytes(bytes: &[u8], out: &mut Vec<u8>) {
+    out.extend((bytes.len() as u32).to_le_bytes());
+    out.extend(bytes);
+}
+
+fn collect_types<'a>(ty: &'a IdlTypeDefTy, used: &mut Vec<&'a IdlType>) {
+    let fields: Vec<&IdlFields> = match ty {
+        IdlTypeDefTy::Struct { fields } => fields.iter().collect(),
+        IdlTypeDefTy::Enum { variants } => variants.iter().filter_map(|variant| variant.fields.as_ref()).collect(),
+        IdlTypeDefTy::Type { alias } => {
+            used.push(alias);
+            Vec::new()
+        }
+    };
+    for fields in fields {
+        match fields {
+            IdlFields::Named(fields) => used.extend(fields.iter().map(|field| &field.ty)),
+            IdlFields::Tuple(types) => used.extend(types),
+        }
+    }
+}
+
+/// Anchor's discriminator: the first 8 bytes of `sha256("{namespace}:{name}")`
+fn sighash(namespace: &str, name: &str) -> Vec<u8> {
+    Sha256::digest(format!("{}:{}", namespace, name))[..8].to_vec()
+}
+
+// Older IDLs name instructions in camelCase, but hash the Rust function's snake_case name
+fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (index, ch) in name.chars().enumerate() {
+        if ch.is_uppercase() {
+            if index > 0 {
+                snake.push('_');
+            }
+            snake.extend(ch.to_lowercase());
+        } else {
+            snake.push(ch);
+        }
+    }
+    snake
+}
+
+pub struct RegisteredIdl {
+    pub program_id: Pubkey,
+    pub idl: Idl,
+    /// The IDL exactly as it was provided
+    pub json: Value,
+    /// `file` for `IDL_DIR`, `api` for `POST /idl`
+    pub source: &'static str,
+}
+
+impl RegisteredIdl {
+    fn parse(json: Value, program_id: Option<Pubkey>, source: &'static str) -> Result<Self, String> {
+        let idl: Idl = serde_json::from_value(json.clone()).map_err(|err| format!("Invalid IDL: {}", err))?;
+        let program_id = match program_id {
+            Some(program_id) => program_id,
+            None => {
+                let address = idl.program_id().ok_or("IDL has no address; pass ?program_id=")?;
+                address.parse().map_err(|_| format!("Invalid IDL address: {}", address))?
+            }
+        };
+        idl.validate()?;
+        Ok(RegisteredIdl { program_id, idl, json, source })
+    }
+
+    fn summary(&self) -> IdlSummary {
+        IdlSummary {
+            program_id: self.program_id.to_string(),
+            name: self.idl.name().to_string(),
+            source: self.source.to_string(),
+            instructions: self.idl.instructions.len(),
+            accounts: self.idl.accounts.len(),
+            events: self.idl.events.len(),
+        }
+    }
+}
+
+/// Known IDLs by program id. Those registered through the API shadow files from
+/// `IDL_DIR` for the same program until they are deleted again.
+///
+/// The registry is shared by every cluster: an IDL applies to its program id wherever
+/// that id is deployed, even if another cluster runs a different program under it.
+#[derive(Default)]
+pub struct IdlRegistry {
+    files: HashMap<Pubkey, Arc<RegisteredIdl>>,
+    registered: RwLock<HashMap<Pubkey, Arc<RegisteredIdl>>>,
+}
+
+impl IdlRegistry {
+    /// Reads every `*.json` IDL in `dir`, then the registrations kept in `store`.
+    /// A bad file fails startup; a bad stored registration is skipped with a warning.
+    pub fn load(store: &Store, dir: Option<&FsPath>) -> Result<Self, String> {
+        let mut files = HashMap::new();
+        if let Some(dir) = dir {
+            let entries = std::fs::read_dir(dir).map_err(|err| format!("Failed to read {}: {}", dir.display(), err))?;
+            for entry in entries {
+                let path = entry.map_err(|err| format!("Failed to read {}: {}", dir.display(), err))?.path();
+                if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                    continue;
+                }
+                let contents =
+                    std::fs::read(&path).map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+                let json = serde_json::from_slice(&contents).map_err(|err| format!("{}: {}", path.display(), err))?;
+                let idl = RegisteredIdl::parse(json, None, "file").map_err(|err| format!("{}: {}", path.display(), err))?;
+                files.insert(idl.program_id, Arc::new(idl));
+            }
+        }
+
+        let mut registered = HashMap::new();
+        for (program_id, json) in store.list::<Value>(IDLS).map_err(|err| err.to_string())? {
+            let parsed = program_id
+                .parse()
+                .map_err(|_| format!("Invalid program id {}", program_id))
+                .and_then(|program_id| RegisteredIdl::parse(json, Some(program_id), "api"));
+            match parsed {
+                Ok(idl) => {
+                    registered.insert(idl.program_id, Arc::new(idl));
+                }
+                Err(err) => warn!("Skipping stored IDL for {}: {}", program_id, err),
+            }
+        }
+
+        Ok(IdlRegistry { files, registered: RwLock::new(registered) })
+    }
+
+    pub fn get(&self, program_id: &Pubkey) -> Option<Arc<RegisteredIdl>> {
+        let registered = self.registered.read().unwrap().get(program_id).cloned();
+        registered.or_else(|| self.files.get(program_id).cloned())
+    }
+
+    pub fn len(&self) -> usize {
+        let registered = self.registered.read().unwrap();
+        registered.len() + self.files.keys().filter(|program_id| !registered.contains_key(program_id)).count()
+    }
+
+    fn all(&self) -> Vec<Arc<RegisteredIdl>> {
+        let registered = self.registered.read().unwrap();
+        let mut all: Vec<_> = registered
+            .values()
+            .chain(self.files.values().filter(|idl| !registered.contains_key(&idl.program_id)))
+            .cloned()
+            .collect();
+        all.sort_by_key(|idl| idl.program_id.to_string());
+        all
+    }
+
+    /// Decodes an account owned by a program with a known IDL
+    pub fn decode_account(&self, owner: &Pubkey, data: &[u8]) -> Option<ParsedAccountData> {
+        let registered = self.get(owner)?;
+        let (name, info) = registered.idl.decode_account(data)?;
+        Some(ParsedAccountData {
+            program: registered.idl.name().to_string(),
+            parsed: json!({ "type": name, "info": info }),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterIdlQuery {
+    /// Overrides the IDL's own `address`
+    #[serde(default)]
+    pub program_id: Option<String>,
+}
+
+pub async fn register_idl(
+    State(state): State<AppState>,
+    Query(query): Query<RegisterIdlQuery>,
+    Json(json): Json<Value>,
+) -> Result<(StatusCode, Json<IdlSummary>), AppError> {
+    let program_id = query.program_id.as_deref().map(validate_pubkey).transpose()?;
+    let idl = RegisteredIdl::parse(json, program_id, "api").map_err(AppError::BadRequest)?;
+
+    state.store.put(IDLS, &idl.program_id.to_string(), &idl.json)?;
+    let summary = idl.summary();
+    state.idls.registered.write().unwrap().insert(idl.program_id, Arc::new(idl));
+
+    info!("Registered IDL {} for {}", summary.name, summary.program_id);
+    Ok((StatusCode::CREATED, Json(summary)))
+}
+
+pub async fn list_idls(State(state): State<AppState>) -> Json<Vec<IdlSummary>> {
+    Json(state.idls.all().iter().map(|idl| idl.summary()).collect())
+}
+
+pub async fn get_idl(State(state): State<AppState>, Path(program_id): Path<String>) -> Result<Json<Value>, AppError> {
+    let idl = state
+        .idls
+        .get(&validate_pubkey(&program_id)?)
+        .ok_or_else(|| AppError::NotFound(format!("No IDL registered for {}", program_id)))?;
+    Ok(Json(idl.json.clone()))
+}
+
+/// Removes an IDL registered through the API. A file from `IDL_DIR` for the same
+/// program takes over again; file IDLs themselves can only be removed from the directory.
+pub async fn delete_idl(State(state): State<AppState>, Path(program_id): Path<String>) -> Result<Json<Value>, AppError> {
+    let pubkey = validate_pubkey(&program_id)?;
+    let registered = state.idls.registered.read().unwrap().contains_key(&pubkey);
+    if !registered {
+        let message = if state.idls.files.contains_key(&pubkey) {
+            format!("IDL for {} comes from IDL_DIR and can't be deleted through the API", program_id)
+        } else {
+            format!("No IDL registered for {}", program_id)
+        };
+        return Err(AppError::NotFound(message));
+    }
+    state.store.remove(IDLS, &pubkey.to_string())?;
+    state.idls.registered.write().unwrap().remove(&pubkey);
+    info!("Deleted IDL for {}", program_id);
+    Ok(Json(json!({ "program_id": program_id, "deleted": true })))
+}
+
+/// Builds one of a registered program's instructions from JSON args, filling in
+/// fixed and PDA accounts. With `fee_payer` it also comes as an unsigned transaction.
+pub async fn build_instruction(
+    State(state): State<AppState>,
+    ClusterRpc(cluster): ClusterRpc,
+    Path((program_id, name)): Path<(String, String)>,
+    Json(request): Json<BuildInstructionRequest>,
+) -> Result<Json<BuiltInstructionResponse>, AppError> {
+    let program = validate_pubkey(&program_id)?;
+    let fee_payer = request.fee_payer.as_deref().map(validate_pubkey).transpose()?;
+    if request.nonce.is_some() && fee_payer.is_none() {
+        return Err(AppError::BadRequest("A nonce needs a fee_payer to build a transaction with".to_string()));
+    }
+    let registered = state
+        .idls
+        .get(&program)
+        .ok_or_else(|| AppError::NotFound(format!("No IDL registered for {}", program_id)))?;
+    let (instruction, names) = registered.idl.build_instruction(&program, &name, &request.args, &request.accounts)?;
+
+    let transaction = match fee_payer {
+        Some(fee_payer) => {
+            let nonce = DurableNonce::resolve(&cluster, request.nonce.as_ref(), &fee_payer).await?;
+            let instructions = std::slice::from_ref(&instruction);
+            let prepared =
+                handlers::prepare_transaction(&cluster, instructions, &fee_payer, request.fee_strategy, nonce.as_ref())
+                    .await?;
+            Some(prepared)
+        }
+        None => None,
+    };
+
+    info!("Built {} instruction for {}", name, program_id);
+    Ok(Json(BuiltInstructionResponse {
+        program_id,
+        instruction: name,
+        accounts: names
+            .into_iter()
+            .zip(&instruction.accounts)
+            .map(|(name, meta)| BuiltInstructionAccount {
+                name,
+                pubkey: meta.pubkey.to_string(),
+                signer: meta.is_signer,
+                writable: meta.is_writable,
+            })
+            .collect(),
+        data: BASE64_STANDARD.encode(&instruction.data),
+        transaction,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn idl(json: Value) -> Idl {
+        let idl: Idl = serde_json::from_value(json).unwrap();
+        idl.validate().unwrap();
+        idl
+    }
+
+    fn borsh_string(value: &str) -> Vec<u8> {
+        let mut bytes = (value.len() as u32).to_le_bytes().to_vec();
+        bytes.extend(value.as_bytes());
+        bytes
+    }
+
+    #[test]
+    fn decodes_instructions_from_the_prereq_idl() {
+        let json: Value = serde_json::from_str(include_str!("../../turbine_prereq2/turbin3_idl.json")).unwrap();
+        let registered = RegisteredIdl::parse(json, None, "file").unwrap();
+        assert_eq!(registered.program_id.to_string(), "TRBZyQHB3m68FGeVsqTK39Wm4xejadjVhP5MAZaKWDM");
+
+        let accounts: Vec<String> = (0..7).map(|_| Pubkey::new_unique().to_string()).collect();
+        let parsed = registered.idl.decode_instruction(&[77, 124, 82, 163, 21, 133, 181, 206], &accounts).unwrap();
+        assert_eq!(parsed["type"], "submit_rs");
+        assert_eq!(parsed["info"]["accounts"]["user"], accounts[0]);
+        assert_eq!(parsed["info"]["accounts"]["system_program"], accounts[6]);
+        assert!(registered.idl.decode_instruction(&[0; 8], &accounts).is_none());
+    }
+
+    #[test]
+    fn decodes_legacy_accounts_and_instructions_with_derived_discriminators() {
+        let idl = idl(json!({
+            "name": "week1_day1",
+            "instructions": [{
+                "name": "updatePdaData",
+                "accounts": [{ "name": "pdaAccount", "isMut": true, "isSigner": false }],
+                "args": [{ "name": "newData", "type": "string" }]
+            }],
+            "accounts": [{
+                "name": "PDAAccount",
+                "type": { "kind": "struct", "fields": [
+                    { "name": "data", "type": "string" },
+                    { "name": "bump", "type": "u8" },
+                    { "name": "authority", "type": "publicKey" }
+                ] }
+            }]
+        }));
+        let authority = Pubkey::new_unique();
+
+        let mut data = sighash("account", "PDAAccount");
+        data.extend(borsh_string("hello"));
+        data.push(254);
+        data.extend(authority.to_bytes());
+        data.resize(245, 0);
+        let (name, account) = idl.decode_account(&data).unwrap();
+        assert_eq!(name, "PDAAccount");
+        assert_eq!(account, json!({ "data": "hello", "bump": 254, "authority": authority.to_string() }));
+
+        let mut data = sighash("global", "update_pda_data");
+        data.extend(borsh_string("bye"));
+        let parsed = idl.decode_instruction(&data, &[authority.to_string()]).unwrap();
+        assert_eq!(parsed["type"], "updatePdaData");
+        assert_eq!(parsed["info"]["args"]["newData"], "bye");
+        assert_eq!(parsed["info"]["accounts"]["pdaAccount"], authority.to_string());
+    }
+
+    #[test]
+    fn decodes_defined_types_options_enums_and_events() {
+        let idl = idl(json!({
+            "address": "11111111111111111111111111111112",
+            "metadata": { "name": "week1_day3", "version": "0.1.0", "spec": "0.1.0" },
+            "instructions": [],
+            "accounts": [{ "name": "TokenRegistry", "discriminator": [1, 2, 3, 4, 5, 6, 7, 8] }],
+            "events": [{ "name": "Minted", "discriminator": [8, 7, 6, 5, 4, 3, 2, 1] }],
+            "types": [
+                { "name": "TokenRegistry", "type": { "kind": "struct", "fields": [
+                    { "name": "mint", "type": "pubkey" },
+                    { "name": "freeze_authority", "type": { "option": "pubkey" } },
+                    { "name": "total_supply", "type": "u64" },
+                    { "name": "created_at", "type": "i64" },
+                    { "name": "status", "type": { "defined": { "name": "Status" } } },
+                    { "name": "holders", "type": { "vec": "u16" } }
+                ] } },
+                { "name": "Status", "type": { "kind": "enum", "variants": [
+                    { "name": "Active" },
+                    { "name": "Paused", "fields": [{ "name": "until", "type": "i64" }] }
+                ] } },
+                { "name": "Minted", "type": { "kind": "struct", "fields": [
+                    { "name": "amount", "type": "u128" }
+                ] } }
+            ]
+        }));
+        let mint = Pubkey::new_unique();
+
+        let mut data = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        data.extend(mint.to_bytes());
+        data.push(0);
+        data.extend(1_000u64.to_le_bytes());
+        data.extend((-5i64).to_le_bytes());
+        data.push(1);
+        data.extend(99i64.to_le_bytes());
+        data.extend([2, 0, 0, 0, 7, 0, 9, 0]);
+        let (_, account) = idl.decode_account(&data).unwrap();
+        assert_eq!(
+            account,
+            json!({
+                "mint": mint.to_string(),
+                "freeze_authority": null,
+                "total_supply": 1000,
+                "created_at": -5,
+                "status": { "Paused": { "until": 99 } },
+                "holders": [7, 9]
+            })
+        );
+
+        let mut event = EVENT_IX_TAG.to_vec();
+        event.extend([8, 7, 6, 5, 4, 3, 2, 1]);
+        event.extend(u128::MAX.to_le_bytes());
+        let parsed = idl.decode_instruction(&event, &[]).unwrap();
+        assert_eq!(parsed["type"], "event");
+        assert_eq!(parsed["info"]["name"], "Minted");
+        assert_eq!(parsed["info"]["data"]["amount"], u128::MAX.to_string());
+    }
+
+    #[test]
+    fn bounds_sequences_of_zero_sized_elements() {
+        let idl = idl(json!({
+            "address": "11111111111111111111111111111112",
+            "metadata": { "name": "empty", "version": "0.1.0", "spec": "0.1.0" },
+            "instructions": [],
+            "accounts": [
+                { "name": "Few", "discriminator": [1, 1, 1, 1, 1, 1, 1, 1] },
+                { "name": "Many", "discriminator": [2, 2, 2, 2, 2, 2, 2, 2] }
+            ],
+            "types": [
+                { "name": "Empty", "type": { "kind": "struct", "fields": [] } },
+                { "name": "Few", "type": { "kind": "struct", "fields": [
+                    { "name": "items", "type": { "vec": { "defined": { "name": "Empty" } } } }
+                ] } },
+                { "name": "Many", "type": { "kind": "struct", "fields": [
+                    { "name": "items", "type": { "vec": { "array": [{ "defined": { "name": "Empty" } }, 1000] } } }
+                ] } }
+            ]
+        }));
+
+        // Empty elements take no bytes, so a length beyond the data is fine
+        let mut few = vec![1; 8];
+        few.extend(3u32.to_le_bytes());
+        assert_eq!(idl.decode_account(&few).unwrap().1, json!({ "items": [{}, {}, {}] }));
+
+        // A million elements out of a few hundred bytes
+        let mut many = vec![2; 8];
+        many.extend(1000u32.to_le_bytes());
+        many.extend([0; 1000]);
+        assert!(idl.decode_account(&many).is_none());
+    }
+
+    #[test]
+    fn rejects_idls_with_undefined_types() {
+        let idl: Idl = serde_json::from_value(json!({
+            "instructions": [{ "name": "init", "args": [{ "name": "config", "type": { "defined": "Config" } }] }]
+        }))
+        .unwrap();
+        assert_eq!(idl.validate().unwrap_err(), "Type Config is not defined");
+        assert!(RegisteredIdl::parse(json!({ "instructions": [] }), None, "api").is_err());
+    }
+
+    #[test]
+    fn builds_the_prereq_instruction_with_derived_pdas() {
+        let json: Value = serde_json::from_str(include_str!("../../turbine_prereq2/turbin3_idl.json")).unwrap();
+        let registered = RegisteredIdl::parse(json, None, "file").unwrap();
+        let program_id = registered.program_id;
+        let (user, mint, collection) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let accounts: HashMap<String, String> = [("user", user), ("mint", mint), ("collection", collection)]
+            .into_iter()
+            .map(|(name, pubkey)| (name.to_string(), pubkey.to_string()))
+            .collect();
+
+        let (instruction, names) =
+            registered.idl.build_instruction(&program_id, "submit_rs", &Map::new(), &accounts).unwrap();
+        assert_eq!(instruction.data, [77, 124, 82, 163, 21, 133, 181, 206]);
+        assert_eq!(names, ["user", "account", "mint", "collection", "authority", "mpl_core_program", "system_program"]);
+        let prereq = Pubkey::find_program_address(&[b"prereqs", user.as_ref()], &program_id).0;
+        let authority = Pubkey::find_program_address(&[b"collection", collection.as_ref()], &program_id).0;
+        let metas: Vec<(Pubkey, bool, bool)> =
+            instruction.accounts.iter().map(|meta| (meta.pubkey, meta.is_signer, meta.is_writable)).collect();
+        assert_eq!(
+            metas,
+            [
+                (user, true, true),
+                (prereq, false, true),
+                (mint, true, true),
+                (collection, false, true),
+                (authority, false, false),
+                ("CoREENxT6tW1HoK8ypY1SxRMZTcVPm7R94rH4PZNhX7d".parse().unwrap(), false, false),
+                (solana_sdk::system_program::ID, false, false),
+            ]
+        );
+
+        let mut partial = accounts.clone();
+        partial.remove("collection");
+        let err = registered.idl.build_instruction(&program_id, "submit_rs", &Map::new(), &partial).unwrap_err();
+        assert_eq!(err.to_string(), "Bad request: Missing accounts for submit_rs: collection, authority");
+    }
+
+    #[test]
+    fn encodes_args_and_legacy_seeds_as_they_decode() {
+        let idl = idl(json!({
+            "name": "vault",
+            "instructions": [{
+                "name": "deposit",
+                "accounts": [
+                    { "name": "owner", "isMut": true, "isSigner": true },
+                    { "name": "vault", "isMut": true, "isSigner": false, "pda": { "seeds": [
+                        { "kind": "const", "type": "string", "value": "vault" },
+                        { "kind": "account", "type": "publicKey", "path": "owner" },
+                        { "kind": "arg", "type": "u64", "path": "params.id" }
+                    ] } },
+                    { "name": "referrer", "isMut": false, "isSigner": false, "isOptional": true }
+                ],
+                "args": [
+                    { "name": "params", "type": { "defined": "Params" } },
+                    { "name": "memo", "type": { "option": "string" } }
+                ]
+            }],
+            "types": [
+                { "name": "Params", "type": { "kind": "struct", "fields": [
+                    { "name": "id", "type": "u64" },
+                    { "name": "amount", "type": "u128" },
+                    { "name": "mode", "type": { "defined": "Mode" } },
+                    { "name": "tags", "type": { "vec": "u8" } }
+                ] } },
+                { "name": "Mode", "type": { "kind": "enum", "variants": [
+                    { "name": "Instant" },
+                    { "name": "Delayed", "fields": [{ "name": "slots", "type": "u32" }] }
+                ] } }
+            ]
+        }));
+        let program_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let args = json!({
+            "params": { "id": 7, "amount": "340282366920938463463374607431768211455", "mode": { "Delayed": { "slots": 3 } }, "tags": [1, 2] },
+            "memo": null
+        });
+        let accounts = HashMap::from([("owner".to_string(), owner.to_string())]);
+
+        let (instruction, _) =
+            idl.build_instruction(&program_id, "deposit", args.as_object().unwrap(), &accounts).unwrap();
+        let vault = Pubkey::find_program_address(&[b"vault", owner.as_ref(), &7u64.to_le_bytes()], &program_id).0;
+        assert_eq!(instruction.accounts[1].pubkey, vault);
+        // Left-out optional accounts are passed as the program id
+        assert_eq!(instruction.accounts[2].pubkey, program_id);
+
+        let addresses: Vec<String> = instruction.accounts.iter().map(|meta| meta.pubkey.to_string()).collect();
+        let parsed = idl.decode_instruction(&instruction.data, &addresses).unwrap();
+        assert_eq!(parsed["info"]["args"], args);
+
+        let bad = json!({ "params": { "id": -1 } });
+        let err = idl.build_instruction(&program_id, "deposit", bad.as_object().unwrap(), &accounts).unwrap_err();
+        assert_eq!(err.to_string(), "Bad request: Invalid arg params: id: expected u64, got -1");
+    }
+}
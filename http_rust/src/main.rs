@@ -1,22 +1,116 @@
 use axum::{
+    extract::State,
+    middleware,
     response::Json,
-    routing::{get, post},
-    Router,
+    routing::{delete, get, post},
+    Extension, Router,
 };
-use solana_client::rpc_client::RpcClient;
-use std::sync::Arc;
+use std::{collections::BTreeMap, net::SocketAddr, sync::Arc, time::Duration};
 use tower_http::cors::{Any, CorsLayer};
 use tracing::{info, warn};
 
+mod address;
+mod auth;
+mod cluster;
+mod decode;
 mod error;
+mod fees;
 mod handlers;
+mod idempotency;
+mod idl;
+mod nonce;
+mod pool;
+mod pubsub;
+mod ratelimit;
+mod session;
+mod store;
+mod tracker;
 mod types;
+mod webhooks;
 
+use auth::{ApiKeys, RequireScope, Scope};
+use cluster::{Cluster, ClusterName};
 use handlers::*;
+use idl::IdlRegistry;
+use pool::RpcPool;
+use ratelimit::{Quota, RateLimiter, RateLimits};
+use session::{SessionConfig, Sessions};
+use store::Store;
+use webhooks::Destinations;
 
+// How often every pooled endpoint is probed for its slot
+const RPC_MONITOR_INTERVAL: Duration = Duration::from_secs(10);
+// How often the API key file is checked for changes
+const API_KEYS_RELOAD_INTERVAL: Duration = Duration::from_secs(5);
+// How often idle rate limit entries are dropped
+const RATE_LIMIT_PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+const DEFAULT_KEY_QUOTA: Quota = Quota { requests_per_minute: 600, rpc_credits_per_minute: 3000 };
+const DEFAULT_IP_QUOTA: Quota = Quota { requests_per_minute: 300, rpc_credits_per_minute: 1500 };
+// How often watched addresses are checked for new transactions
+const WEBHOOK_SCAN_INTERVAL: Duration = Duration::from_secs(15);
+// How often queued webhook deliveries are sent or retried
+const WEBHOOK_DELIVERY_INTERVAL: Duration = Duration::from_secs(2);
+// How often async-mode transactions are polled and rebroadcast
+const TRANSACTION_TRACK_INTERVAL: Duration = Duration::from_secs(2);
+// How often tracked transactions past their retention are dropped
+const TRANSACTION_PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+// How often expired idempotency keys are dropped
+const IDEMPOTENCY_PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+// How often expired sign-in nonces and sessions are dropped
+const SESSION_PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Shared handler state: one RPC pool and nonblocking client per cluster, so a
+/// slow node only parks the calling task instead of a tokio worker thread.
+/// Handlers get the cluster a request targets through `cluster::ClusterRpc`.
 #[derive(Clone)]
 pub struct AppState {
-    pub rpc_client: Arc<RpcClient>,
+    pub clusters: Arc<BTreeMap<String, Arc<Cluster>>>,
+    /// Cluster used by routes without a `/:cluster` prefix
+    pub default_cluster: String,
+    pub api_keys: Arc<ApiKeys>,
+    pub rate_limiter: Arc<RateLimiter>,
+    /// Persistent state: webhooks and their delivery queue
+    pub store: Arc<Store>,
+    /// Anchor IDLs used to decode accounts, instructions and events
+    pub idls: Arc<IdlRegistry>,
+    /// Sign-In With Solana sessions, accepted in place of API keys
+    pub sessions: Arc<Sessions>,
+    /// Hosts webhooks may deliver to
+    pub webhook_destinations: Arc<Destinations>,
+}
+
+impl AppState {
+    pub fn new(
+        clusters: Vec<Cluster>,
+        default_cluster: String,
+        api_keys: ApiKeys,
+        rate_limiter: RateLimiter,
+        store: Store,
+        idls: IdlRegistry,
+        sessions: SessionConfig,
+    ) -> Self {
+        let store = Arc::new(store);
+        AppState {
+            clusters: Arc::new(
+                clusters
+                    .into_iter()
+                    .map(|cluster| (cluster.name.clone(), Arc::new(cluster)))
+                    .collect(),
+            ),
+            default_cluster,
+            api_keys: Arc::new(api_keys),
+            rate_limiter: Arc::new(rate_limiter),
+            sessions: Arc::new(Sessions::new(store.clone(), sessions)),
+            store,
+            idls: Arc::new(idls),
+            webhook_destinations: Arc::new(Destinations::default()),
+        }
+    }
+
+    pub fn with_webhook_destinations(mut self, destinations: Destinations) -> Self {
+        self.webhook_destinations = Arc::new(destinations);
+        self
+    }
 }
 
 #[tokio::main]
@@ -27,40 +121,88 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Load environment variables
     dotenv::dotenv().ok();
     
-    // Get RPC URL from environment or use default
-    let rpc_url = std::env::var("SOLANA_RPC_URL")
-        .unwrap_or_else(|_| "https://api.devnet.solana.com".to_string());
-    
-    info!("Connecting to Solana RPC: {}", rpc_url);
+    // Resolve clusters: built-ins, custom ones from SOLANA_CLUSTERS, and the
+    // default cluster's RPC URLs (comma-separated pool) if overridden
+    let default_cluster = std::env::var("SOLANA_CLUSTER").unwrap_or_else(|_| "devnet".to_string());
+    let default_urls = std::env::var("SOLANA_RPC_URLS")
+        .or_else(|_| std::env::var("SOLANA_RPC_URL"))
+        .ok();
+    let cluster_urls = cluster::cluster_urls(
+        std::env::var("SOLANA_CLUSTERS").ok().as_deref(),
+        &default_cluster,
+        default_urls.as_deref(),
+    )?;
     
-    // Create RPC client
-    let rpc_client = Arc::new(RpcClient::new(rpc_url));
+    let default_ws_url = std::env::var("SOLANA_WS_URL").ok();
     
-    // Test connection
-    match rpc_client.get_version() {
-        Ok(version) => info!("Connected to Solana node: {:?}", version),
-        Err(e) => warn!("Failed to connect to Solana node: {}", e),
+    // Create one RPC pool per cluster and keep their health scores current. Pubsub
+    // goes to the first RPC URL's websocket endpoint unless SOLANA_WS_URL overrides it.
+    let mut clusters = Vec::new();
+    for (name, urls) in cluster_urls {
+        let ws_url = match &default_ws_url {
+            Some(ws_url) if name == default_cluster => ws_url.clone(),
+            _ => cluster::websocket_url(&urls[0]),
+        };
+        info!("Cluster {}: RPC pool {}, pubsub {}", name, urls.join(", "), ws_url);
+        let rpc_pool = Arc::new(RpcPool::new(&urls));
+        tokio::spawn(rpc_pool.clone().monitor(RPC_MONITOR_INTERVAL));
+        clusters.push(Cluster::new(name, rpc_pool, ws_url));
     }
     
+    // Load API keys; running without them takes an explicit AUTH_DISABLED=true
+    let api_keys = api_keys_from_env()?;
+    
+    // Quotas per API key and per client IP; RATE_LIMIT_*=0 disables a limit
+    let rate_limits = RateLimits {
+        per_key: quota_from_env("RATE_LIMIT_KEY", DEFAULT_KEY_QUOTA)?,
+        per_ip: quota_from_env("RATE_LIMIT_IP", DEFAULT_IP_QUOTA)?,
+        trust_forwarded_for: std::env::var("TRUST_FORWARDED_FOR").is_ok_and(|value| value == "true"),
+    };
+    info!("Rate limits: {:?}", rate_limits);
+    
+    // Open the embedded store
+    let store_path = std::env::var("STORE_PATH").unwrap_or_else(|_| "data/http_rust.redb".to_string());
+    let store = Store::open(&store_path)?;
+    info!("Store: {}", store_path);
+    
+    // Anchor IDLs from IDL_DIR plus those registered through POST /idl
+    let idl_dir = std::env::var("IDL_DIR").ok();
+    let idls = IdlRegistry::load(&store, idl_dir.as_deref().map(std::path::Path::new))?;
+    info!("Anchor IDLs loaded: {}", idls.len());
+    
+    // Sign-In With Solana: which domain messages must name and what a session may do
+    let sessions = session_config_from_env()?;
+    info!("Sign-in sessions: {:?}", sessions);
+    
+    // Webhooks only deliver to public addresses, plus any hosts allowed explicitly
+    let webhook_allowed_hosts: Vec<String> = std::env::var("WEBHOOK_ALLOWED_HOSTS")
+        .map(|hosts| hosts.split(',').map(str::trim).filter(|host| !host.is_empty()).map(str::to_string).collect())
+        .unwrap_or_default();
+    info!("Webhook hosts allowed besides public addresses: {:?}", webhook_allowed_hosts);
+    
     // Create app state
-    let state = AppState { rpc_client };
+    let rate_limiter = RateLimiter::new(rate_limits);
+    let state = AppState::new(clusters, default_cluster, api_keys, rate_limiter, store, idls, sessions)
+        .with_webhook_destinations(Destinations::new(webhook_allowed_hosts));
+    tokio::spawn(state.api_keys.clone().watch(API_KEYS_RELOAD_INTERVAL));
+    tokio::spawn(state.rate_limiter.clone().prune(RATE_LIMIT_PRUNE_INTERVAL));
+    tokio::spawn(webhooks::detect(state.clone(), WEBHOOK_SCAN_INTERVAL));
+    tokio::spawn(webhooks::deliver(state.store.clone(), state.webhook_destinations.clone(), WEBHOOK_DELIVERY_INTERVAL));
+    tokio::spawn(tracker::track(state.clone(), TRANSACTION_TRACK_INTERVAL));
+    tokio::spawn(tracker::prune(state.store.clone(), TRANSACTION_PRUNE_INTERVAL));
+    tokio::spawn(idempotency::prune(state.store.clone(), IDEMPOTENCY_PRUNE_INTERVAL));
+    tokio::spawn(session::prune(state.store.clone(), SESSION_PRUNE_INTERVAL));
     
-    // Configure CORS
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
+    // Test connection
+    let default = &state.clusters[&state.default_cluster];
+    default.rpc_pool.refresh_slots().await;
+    match default.rpc_client.get_version().await {
+        Ok(version) => info!("Connected to Solana node on {}: {:?}", default.name, version),
+        Err(e) => warn!("Failed to connect to Solana node on {}: {}", default.name, e),
+    }
     
-    // Build router
-    let app = Router::new()
-        .route("/", get(root))
-        .route("/health", get(health))
-        .route("/balance/:address", get(get_balance))
-        .route("/account/:address", get(get_account_info))
-        .route("/transfer", post(transfer_sol))
-        .route("/transaction/:signature", get(get_transaction))
-        .layer(cors)
-        .with_state(state);
+    let state_cluster_names = state.clusters.keys().cloned().collect::<Vec<_>>().join(", ");
+    let app = app(state);
     
     // Get port from environment or use default
     let port = std::env::var("PORT").unwrap_or_else(|_| "8080".to_string());
@@ -70,30 +212,229 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("🚀 Solana HTTP API server starting on {}", addr);
     info!("📖 API Documentation:");
     info!("  GET  /              - API information");
-    info!("  GET  /health        - Health check");
+    info!("  GET  /health        - Health check and RPC pool status");
+    info!("  GET  /auth/nonce    - Start a Sign-In With Solana login (POST /auth/verify to finish)");
+    info!("  GET  /auth/session  - Current session (DELETE to sign out)");
+    info!("  GET  /admin/usage   - Rate limit and RPC credit usage (admin)");
+    info!("  POST /idl           - Register an Anchor IDL (admin; also GET /idl, GET/DELETE /idl/:program_id)");
     info!("  GET  /balance/:addr - Get SOL balance");
     info!("  GET  /account/:addr - Get account info");
+    info!("  POST /balances      - Get SOL balances for up to 500 addresses");
+    info!("  POST /accounts      - Get account info for up to 500 addresses");
+    info!("  GET  /address/:addr/classify - Wallet, program, sysvar or PDA?");
+    info!("  POST /pda           - Derive a program address from typed seeds");
+    info!("  GET  /tokens/:owner - List SPL token accounts");
     info!("  POST /transfer      - Transfer SOL");
+    info!("  POST /transfer/prepare - Build unsigned SOL transfer");
+    info!("  POST /token/transfer - Transfer SPL tokens");
+    info!("  POST /transaction/submit - Submit client-signed transaction");
+    info!("  POST /nonce         - Create a durable nonce account (GET /nonce/:addr; POST .../advance, .../withdraw)");
+    info!("  POST /simulate      - Simulate transaction and preview fee");
+    info!("  POST /verify-message - Verify a raw or off-chain message signature");
     info!("  GET  /transaction/:sig - Get transaction info");
+    info!("  GET  /transaction/:sig/status - Status of a transaction sent with async=true");
+    info!("  POST /program/:id/instruction/:name - Build an instruction from a registered IDL");
+    info!("  GET  /history/:addr - Get transaction history");
+    info!("  GET  /ws            - WebSocket account, signature and logs subscriptions");
+    info!("  POST /webhooks      - Register a webhook for watched addresses (transfer)");
+    info!("  GET  /webhooks      - List webhooks (also GET /webhooks/:id; DELETE needs transfer)");
+    info!("  GET  /webhooks/dead-letters - Failed deliveries (POST .../:id/retry to requeue, transfer)");
+    info!("  Prefix any route except / and /health with /:cluster ({}) to target another cluster",
+          state_cluster_names);
     
     // Start server
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
     
     Ok(())
 }
 
+// Build the router with all API routes
+fn app(state: AppState) -> Router {
+    // Configure CORS
+    let cors = CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods(Any)
+        .allow_headers(Any);
+    
+    let mut router = Router::new()
+        .route("/", get(root))
+        .route("/health", get(health))
+        .merge(auth_routes(&state))
+        .merge(admin_routes(&state))
+        .merge(api_routes(&state));
+    
+    // Every API route is also served under each cluster's name
+    for name in state.clusters.keys() {
+        router = router.nest(
+            &format!("/{}", name),
+            api_routes(&state).layer(Extension(ClusterName(name.clone()))),
+        );
+    }
+    
+    router.layer(cors).with_state(state)
+}
+
+// Applies the per-IP rate limit, authenticates a group of routes against `scope`,
+// applies the per-key rate limit, then replays responses for repeated `Idempotency-Key`s.
+// Layers added later run first, so the IP limit also covers requests that fail authentication.
+fn guarded(routes: Router<AppState>, state: &AppState, scope: Scope) -> Router<AppState> {
+    let guard = RequireScope { api_keys: state.api_keys.clone(), sessions: state.sessions.clone(), scope };
+    routes
+        .route_layer(middleware::from_fn_with_state(state.store.clone(), idempotency::idempotency))
+        .route_layer(middleware::from_fn_with_state(state.rate_limiter.clone(), ratelimit::rate_limit_key))
+        .route_layer(middleware::from_fn_with_state(guard, auth::require_scope))
+        .route_layer(middleware::from_fn_with_state(state.rate_limiter.clone(), ratelimit::rate_limit_ip))
+}
+
+// Wallet sign-in is open to anyone, so it is only rate limited per client IP
+fn auth_routes(state: &AppState) -> Router<AppState> {
+    Router::new()
+        .route("/auth/nonce", get(session::get_nonce))
+        .route("/auth/verify", post(session::verify))
+        .route("/auth/session", get(session::get_session).delete(session::delete_session))
+        .route_layer(middleware::from_fn_with_state(state.rate_limiter.clone(), ratelimit::rate_limit_ip))
+}
+
+// Server-wide administration, not tied to a cluster
+fn admin_routes(state: &AppState) -> Router<AppState> {
+    let admin = Router::new()
+        .route("/admin/usage", get(ratelimit::get_usage))
+        .route("/idl", post(idl::register_idl).get(idl::list_idls))
+        .route("/idl/:program_id", get(idl::get_idl).delete(idl::delete_idl));
+    guarded(admin, state, Scope::Admin)
+}
+
+// Routes that talk to a cluster, grouped by the API key scope they require
+fn api_routes(state: &AppState) -> Router<AppState> {
+    let read = Router::new()
+        .route("/balance/:address", get(get_balance))
+        .route("/account/:address", get(get_account_info))
+        .route("/balances", post(get_balances))
+        .route("/accounts", post(get_accounts))
+        .route("/address/:address/classify", get(address::classify_address))
+        .route("/pda", post(address::derive_pda))
+        .route("/tokens/:owner", get(get_token_accounts))
+        .route("/transfer/prepare", post(prepare_transfer))
+        .route("/simulate", post(simulate_transaction))
+        .route("/verify-message", post(verify_message))
+        .route("/transaction/:signature", get(get_transaction))
+        .route("/transaction/:signature/status", get(tracker::get_transaction_status))
+        .route("/program/:program_id/instruction/:name", post(idl::build_instruction))
+        .route("/nonce/:address", get(nonce::get_nonce_account))
+        .route("/history/:address", get(get_history))
+        .route("/ws", get(pubsub::subscribe_ws))
+        .route("/webhooks", get(webhooks::list_webhooks))
+        .route("/webhooks/dead-letters", get(webhooks::list_dead_letters))
+        .route("/webhooks/:id", get(webhooks::get_webhook));
+    
+    let transfer = Router::new()
+        .route("/transfer", post(transfer_sol))
+        .route("/token/transfer", post(transfer_token))
+        .route("/transaction/submit", post(submit_transaction))
+        .route("/nonce", post(nonce::create_nonce_account))
+        .route("/nonce/:address/advance", post(nonce::advance_nonce_account))
+        .route("/nonce/:address/withdraw", post(nonce::withdraw_nonce_account))
+        .route("/webhooks", post(webhooks::create_webhook))
+        .route("/webhooks/dead-letters/:id/retry", post(webhooks::retry_dead_letter))
+        .route("/webhooks/:id", delete(webhooks::delete_webhook));
+    
+    guarded(read, state, Scope::Read).merge(guarded(transfer, state, Scope::Transfer))
+}
+
+// Reads `API_KEYS_FILE`, refusing to start without one unless `AUTH_DISABLED=true`
+fn api_keys_from_env() -> Result<ApiKeys, String> {
+    if let Ok(path) = std::env::var("API_KEYS_FILE") {
+        let api_keys = ApiKeys::load(&path)?;
+        info!("API key authentication enabled ({})", path);
+        return Ok(api_keys);
+    }
+    if std::env::var("AUTH_DISABLED").is_ok_and(|value| value == "true") {
+        warn!("**********************************************************************");
+        warn!("AUTH_DISABLED=true: API key authentication is OFF. Every route, including");
+        warn!("/transfer, /token/transfer and /transaction/submit, is open to anyone who");
+        warn!("can reach this server. Never run like this on a public address.");
+        warn!("**********************************************************************");
+        return Ok(ApiKeys::disabled());
+    }
+    Err("API_KEYS_FILE is not set; point it at an API key file, or set AUTH_DISABLED=true to run without authentication"
+        .to_string())
+}
+
+// Reads `AUTH_DOMAIN`, `SESSION_SCOPES` (comma-separated) and `SESSION_TTL_SECS`.
+// Sign-in is only enabled with an `AUTH_DOMAIN`; the other two are refused without one.
+fn session_config_from_env() -> Result<SessionConfig, String> {
+    let domain = std::env::var("AUTH_DOMAIN").ok().filter(|domain| !domain.is_empty());
+    if domain.is_none() {
+        if let Some(name) = ["SESSION_SCOPES", "SESSION_TTL_SECS"].into_iter().find(|name| std::env::var(name).is_ok()) {
+            return Err(format!("{} is set but sign-in is disabled; set AUTH_DOMAIN to the host clients sign in to", name));
+        }
+    }
+    let mut config = SessionConfig { domain, ..SessionConfig::default() };
+    if let Ok(scopes) = std::env::var("SESSION_SCOPES") {
+        config.scopes = scopes
+            .split(',')
+            .map(|scope| {
+                serde_json::from_value(serde_json::json!(scope.trim()))
+                    .map_err(|_| format!("SESSION_SCOPES: unknown scope {}", scope.trim()))
+            })
+            .collect::<Result<_, _>>()?;
+    }
+    if let Ok(ttl) = std::env::var("SESSION_TTL_SECS") {
+        let secs = ttl.parse().map_err(|_| format!("SESSION_TTL_SECS must be a whole number: {}", ttl))?;
+        config.ttl = Duration::from_secs(secs);
+    }
+    Ok(config)
+}
+
+// Reads `<prefix>_REQUESTS_PER_MINUTE` and `<prefix>_RPC_CREDITS_PER_MINUTE`
+fn quota_from_env(prefix: &str, default: Quota) -> Result<Quota, String> {
+    let read = |name: String, default: u32| match std::env::var(&name) {
+        Ok(value) => value.parse().map_err(|_| format!("{} must be a whole number: {}", name, value)),
+        Err(_) => Ok(default),
+    };
+    Ok(Quota {
+        requests_per_minute: read(format!("{}_REQUESTS_PER_MINUTE", prefix), default.requests_per_minute)?,
+        rpc_credits_per_minute: read(format!("{}_RPC_CREDITS_PER_MINUTE", prefix), default.rpc_credits_per_minute)?,
+    })
+}
+
 // Root endpoint
 async fn root() -> Json<serde_json::Value> {
     Json(serde_json::json!({
         "message": "Solana HTTP API Server",
         "version": "1.0.0",
         "description": "HTTP server for interacting with Solana blockchain",
+        "clusters": "Prefix any endpoint below with /{cluster} (e.g. /mainnet-beta/balance/...) to target a non-default cluster",
+        "commitment": "Read endpoints accept ?commitment=processed|confirmed|finalized",
+        "authentication": "When API keys are configured, send X-API-Key or Authorization: Bearer with every endpoint below",
         "endpoints": {
-            "health": "GET /health - Health check",
+            "health": "GET /health - Health check and RPC pool status",
+            "auth_nonce": "GET /auth/nonce - Nonce and domain for a Sign-In With Solana message",
+            "auth_verify": "POST /auth/verify - Verify a signed sign-in message and get a session token usable in place of an API key",
+            "auth_session": "GET /auth/session - The current session; DELETE /auth/session signs out",
+            "usage": "GET /admin/usage - Rate limit and RPC credit usage per API key and client IP (admin scope)",
+            "idl": "POST /idl - Register an Anchor IDL used to decode accounts, instructions and events; GET /idl, GET/DELETE /idl/{program_id} (admin scope)",
             "balance": "GET /balance/{address} - Get SOL balance for address",
-            "account": "GET /account/{address} - Get account information",
+            "account": "GET /account/{address}?data=true - Get account information, with raw data on request",
+            "balances": "POST /balances - Get SOL balances for up to 500 addresses, with per-address errors",
+            "accounts": "POST /accounts?data=true - Get account information for up to 500 addresses, with per-address errors and up to 16 KiB of raw data each on request",
+            "classify": "GET /address/{address}/classify - Whether an address is on the curve, a known program, a sysvar, executable or a likely PDA",
+            "pda": "POST /pda - Derive a program address and bump from a program id and typed seeds",
+            "tokens": "GET /tokens/{owner} - List SPL token accounts owned by address",
             "transfer": "POST /transfer - Transfer SOL between addresses",
-            "transaction": "GET /transaction/{signature} - Get transaction details"
+            "token_transfer": "POST /token/transfer - Transfer SPL tokens, creating the recipient's token account if needed",
+            "prepare_transfer": "POST /transfer/prepare - Build an unsigned SOL transfer for client-side signing",
+            "submit_transaction": "POST /transaction/submit - Submit a client-signed transaction",
+            "nonce": "POST /nonce - Create a durable nonce account; GET /nonce/{address}, POST /nonce/{address}/advance and /withdraw. Pass \"nonce\": {\"account\": ...} to any transaction-building endpoint to use it instead of a recent blockhash",
+            "simulate": "POST /simulate - Simulate a transfer or base64 transaction and preview its fee",
+            "verify_message": "POST /verify-message - Check a message signature, raw or in the Solana off-chain message format, and report which matched",
+            "transaction": "GET /transaction/{signature} - Get transaction details",
+            "transaction_status": "GET /transaction/{signature}/status - Status history of a transaction sent with \"async\": true",
+            "build_instruction": "POST /program/{program_id}/instruction/{name} - Build an instruction, with PDAs derived, from the program's registered IDL; add fee_payer for an unsigned transaction",
+            "history": "GET /history/{address}?before=&until=&limit=&expand=true - Get paginated transaction history",
+            "webhooks": "POST /webhooks - Register a webhook URL on a public address for a set of addresses; GET /webhooks, GET/DELETE /webhooks/{id} (POST and DELETE need transfer scope)",
+            "dead_letters": "GET /webhooks/dead-letters - Deliveries that ran out of retries; POST /webhooks/dead-letters/{id}/retry to requeue (transfer scope)",
+            "ws": "GET /ws - WebSocket subscriptions to account changes, signature confirmations and logs mentioning an address"
         },
         "examples": {
             "balance": "/balance/11111111111111111111111111111111",
@@ -103,19 +444,322 @@ async fn root() -> Json<serde_json::Value> {
                 "body": {
                     "from": "source_address",
                     "to": "destination_address", 
-                    "amount": 0.1,
+                    "amount": "0.1",
                     "private_key": "base58_encoded_private_key"
                 }
+            },
+            "prepare_transfer": {
+                "method": "POST",
+                "url": "/transfer/prepare",
+                "body": {
+                    "from": "source_address",
+                    "to": "destination_address",
+                    "amount": "0.1"
+                }
+            },
+            "submit_transaction": {
+                "method": "POST",
+                "url": "/transaction/submit",
+                "body": {
+                    "transaction": "base64_encoded_signed_transaction"
+                }
             }
         }
     }))
 }
 
 // Health check endpoint
-async fn health() -> Json<serde_json::Value> {
+async fn health(State(state): State<AppState>) -> Json<serde_json::Value> {
+    let clusters: serde_json::Map<String, serde_json::Value> = state
+        .clusters
+        .values()
+        .map(|cluster| {
+            let endpoints = cluster.rpc_pool.status();
+            let healthy = endpoints.iter().any(|endpoint| endpoint.healthy);
+            let health = serde_json::json!({
+                "healthy": healthy,
+                "rpc_endpoints": endpoints,
+                "pubsub_subscriptions": cluster.pubsub.topic_count(),
+            });
+            (cluster.name.clone(), health)
+        })
+        .collect();
+    let healthy = clusters[&state.default_cluster]["healthy"].as_bool().unwrap_or(false);
+    
     Json(serde_json::json!({
-        "status": "healthy",
+        "status": if healthy { "healthy" } else { "degraded" },
         "timestamp": chrono::Utc::now().to_rfc3339(),
-        "service": "solana-http-api"
+        "service": "solana-http-api",
+        "default_cluster": state.default_cluster,
+        "clusters": clusters
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use axum::{body::Body, http::{Request, StatusCode}};
+    use solana_client::{
+        client_error::Result as ClientResult,
+        rpc_request::RpcRequest,
+        rpc_sender::{RpcSender, RpcTransportStats},
+    };
+    use std::time::{Duration, Instant};
+    use tower::ServiceExt;
+
+    const RPC_LATENCY: Duration = Duration::from_millis(200);
+    const CONCURRENT_REQUESTS: usize = 500;
+
+    /// Stand-in for a slow RPC node: every call waits before answering.
+    struct SlowSender;
+
+    #[async_trait]
+    impl RpcSender for SlowSender {
+        async fn send(&self, request: RpcRequest, params: serde_json::Value) -> ClientResult<serde_json::Value> {
+            tokio::time::sleep(RPC_LATENCY).await;
+            match request {
+                RpcRequest::GetBalance => Ok(serde_json::json!({ "context": { "slot": 1 }, "value": 42 })),
+                // Every account exists and holds 42 lamports, except the System Program's
+                RpcRequest::GetMultipleAccounts => {
+                    let accounts: Vec<_> = params[0]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .map(|address| {
+                            (address != "11111111111111111111111111111111").then(|| serde_json::json!({
+                                "lamports": 42,
+                                "owner": "11111111111111111111111111111111",
+                                "data": ["", "base64"],
+                                "executable": false,
+                                "rentEpoch": 0,
+                                "space": 0,
+                            }))
+                        })
+                        .collect();
+                    Ok(serde_json::json!({ "context": { "slot": 1 }, "value": accounts }))
+                }
+                RpcRequest::GetVersion => Ok(serde_json::json!({ "solana-core": "1.18.26" })),
+                _ => Ok(serde_json::Value::Null),
+            }
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            RpcTransportStats::default()
+        }
+
+        fn url(&self) -> String {
+            "slow-sender".to_string()
+        }
+    }
+
+    /// State with `cluster_names` served by slow fake nodes, authentication off
+    /// as with `AUTH_DISABLED=true`, and webhooks allowed to reach example.com
+    pub(crate) fn test_state(cluster_names: &[&str], rate_limits: RateLimits) -> AppState {
+        let clusters = cluster_names
+            .iter()
+            .map(|name| {
+                let rpc_pool = RpcPool::from_senders(vec![(name.to_string(), Box::new(SlowSender))]);
+                Cluster::new(name.to_string(), Arc::new(rpc_pool), "ws://127.0.0.1:9".to_string())
+            })
+            .collect();
+        AppState::new(
+            clusters,
+            cluster_names[0].to_string(),
+            ApiKeys::disabled(),
+            RateLimiter::new(rate_limits),
+            Store::in_memory(),
+            IdlRegistry::default(),
+            SessionConfig::default(),
+        )
+        .with_webhook_destinations(Destinations::new(["example.com".to_string()]))
+    }
+
+    fn test_app(cluster_names: &[&str], rate_limits: RateLimits) -> Router {
+        app(test_state(cluster_names, rate_limits))
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn balance_throughput_under_slow_rpc() {
+        let router = test_app(&["devnet"], RateLimits::unlimited());
+
+        let started = Instant::now();
+        let mut requests = tokio::task::JoinSet::new();
+        for _ in 0..CONCURRENT_REQUESTS {
+            let router = router.clone();
+            requests.spawn(async move {
+                let request = Request::get("/balance/11111111111111111111111111111111")
+                    .body(Body::empty())
+                    .unwrap();
+                router.oneshot(request).await.unwrap().status()
+            });
+        }
+        while let Some(status) = requests.join_next().await {
+            assert_eq!(status.unwrap(), StatusCode::OK);
+        }
+        let elapsed = started.elapsed();
+
+        // Four workers blocking on each call would need 25s; overlapping waits finish in a couple of latencies.
+        assert!(
+            elapsed < RPC_LATENCY * 10,
+            "requests were serialized: {} requests in {:?} ({:.0} req/s) with {:?} RPC latency",
+            CONCURRENT_REQUESTS,
+            elapsed,
+            CONCURRENT_REQUESTS as f64 / elapsed.as_secs_f64(),
+            RPC_LATENCY,
+        );
+    }
+
+    #[tokio::test]
+    async fn routes_cluster_prefixes_and_commitment() {
+        let router = test_app(&["devnet", "staging"], RateLimits::unlimited());
+        let address = "11111111111111111111111111111111";
+
+        let cases = [
+            (format!("/balance/{}", address), StatusCode::OK),
+            (format!("/staging/balance/{}", address), StatusCode::OK),
+            (format!("/unknown/balance/{}", address), StatusCode::NOT_FOUND),
+            (format!("/balance/{}?commitment=processed", address), StatusCode::OK),
+            (format!("/balance/{}?commitment=recent", address), StatusCode::BAD_REQUEST),
+            (format!("/staging/history/{}?limit=5&expand=true&commitment=processed", address), StatusCode::BAD_REQUEST),
+        ];
+        for (uri, expected) in cases {
+            let request = Request::get(&uri).body(Body::empty()).unwrap();
+            let status = router.clone().oneshot(request).await.unwrap().status();
+            assert_eq!(status, expected, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn rate_limits_clients_and_reports_usage() {
+        let per_ip = Quota { requests_per_minute: 1, rpc_credits_per_minute: 0 };
+        let router = test_app(&["devnet"], RateLimits { per_ip, ..RateLimits::unlimited() });
+        let client: SocketAddr = "10.1.2.3:5000".parse().unwrap();
+        let balance = || {
+            let mut request = Request::get("/balance/11111111111111111111111111111111")
+                .body(Body::empty())
+                .unwrap();
+            request.extensions_mut().insert(axum::extract::ConnectInfo(client));
+            request
+        };
+
+        assert_eq!(router.clone().oneshot(balance()).await.unwrap().status(), StatusCode::OK);
+        let rejected = router.clone().oneshot(balance()).await.unwrap();
+        assert_eq!(rejected.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(rejected.headers()["retry-after"], "60");
+
+        let usage = router
+            .oneshot(Request::get("/admin/usage").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(usage.into_body(), usize::MAX).await.unwrap();
+        let usage: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(usage["subjects"][0]["subject"], "ip:10.1.2.3");
+        assert_eq!(usage["subjects"][0]["requests"], 1);
+        assert_eq!(usage["subjects"][0]["rejected"], 1);
+        // getBalance, plus the client's one-off getVersion probe
+        assert!(usage["subjects"][0]["rpc_credits"].as_u64().unwrap() >= 1);
+    }
+
+    #[tokio::test]
+    async fn registers_and_lists_webhooks_per_cluster() {
+        let router = test_app(&["devnet", "staging"], RateLimits::unlimited());
+        let body = serde_json::json!({
+            "url": "https://example.com/hook",
+            "addresses": ["11111111111111111111111111111111"],
+        });
+        let request = Request::post("/staging/webhooks")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let created: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(created["cluster"], "staging");
+        assert_eq!(created["secret"].as_str().unwrap().len(), 64);
+
+        let list = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+        let listed = router.clone().oneshot(list("/staging/webhooks")).await.unwrap();
+        let body = axum::body::to_bytes(listed.into_body(), usize::MAX).await.unwrap();
+        let listed: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(listed[0]["id"], created["id"]);
+        assert!(listed[0].get("secret").is_none());
+
+        let other_cluster = router.clone().oneshot(list(&format!("/webhooks/{}", created["id"].as_str().unwrap()))).await.unwrap();
+        assert_eq!(other_cluster.status(), StatusCode::NOT_FOUND);
+        let dead_letters = router.oneshot(list("/staging/webhooks/dead-letters")).await.unwrap();
+        assert_eq!(dead_letters.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn replays_requests_with_the_same_idempotency_key() {
+        let router = test_app(&["devnet"], RateLimits::unlimited());
+        let create = |addresses: &[&str]| {
+            let body = serde_json::json!({ "url": "https://example.com/hook", "addresses": addresses });
+            Request::post("/webhooks")
+                .header("content-type", "application/json")
+                .header("idempotency-key", "retry-1")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let address = "11111111111111111111111111111111";
+
+        let first = router.clone().oneshot(create(&[address])).await.unwrap();
+        assert_eq!(first.status(), StatusCode::CREATED);
+        let first = axum::body::to_bytes(first.into_body(), usize::MAX).await.unwrap();
+
+        let retry = router.clone().oneshot(create(&[address])).await.unwrap();
+        assert_eq!(retry.status(), StatusCode::CREATED);
+        assert_eq!(retry.headers()["idempotent-replayed"], "true");
+        let retry = axum::body::to_bytes(retry.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(first, retry);
+
+        let listed = router.clone().oneshot(Request::get("/webhooks").body(Body::empty()).unwrap()).await.unwrap();
+        let listed = axum::body::to_bytes(listed.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&listed).unwrap().as_array().unwrap().len(), 1);
+
+        let reused = router.oneshot(create(&["SysvarC1ock11111111111111111111111111111111"])).await.unwrap();
+        assert_eq!(reused.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn batches_account_lookups_with_per_address_errors() {
+        let router = test_app(&["devnet"], RateLimits::unlimited());
+        let mut addresses: Vec<String> = (0..250).map(|_| solana_sdk::pubkey::Pubkey::new_unique().to_string()).collect();
+        addresses.insert(3, "not-an-address".to_string());
+        addresses.push("11111111111111111111111111111111".to_string());
+        let post = |uri: &str| {
+            Request::post(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::json!({ "addresses": addresses }).to_string()))
+                .unwrap()
+        };
+
+        let response = router.clone().oneshot(post("/balances")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let results = serde_json::from_slice::<serde_json::Value>(&body).unwrap()["results"].clone();
+        let results = results.as_array().unwrap();
+        assert_eq!(results.len(), 252);
+        assert_eq!(results[0]["address"], addresses[0]);
+        assert_eq!(results[0]["lamports"], 42);
+        assert_eq!(results[3]["error"], "Invalid public key");
+        assert_eq!(results[251]["lamports"], 0);
+        assert_eq!(results[251]["balance"], "0");
+
+        let response = router.clone().oneshot(post("/accounts")).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let results = serde_json::from_slice::<serde_json::Value>(&body).unwrap()["results"].clone();
+        assert_eq!(results[250]["owner"], "11111111111111111111111111111111");
+        assert_eq!(results[250]["address"], addresses[250]);
+        assert!(results[250].get("data").is_none());
+        assert_eq!(results[251]["error"], "Wallet not found");
+
+        let response = router.oneshot(post("/accounts?data=true")).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let results = serde_json::from_slice::<serde_json::Value>(&body).unwrap()["results"].clone();
+        assert_eq!(results[250]["data"], "");
+        assert_eq!(results[250]["data_truncated"], false);
+    }
+}
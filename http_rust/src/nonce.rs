@@ -0,0 +1,264 @@
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    response::Json,
+};
+use solana_client::nonce_utils::{nonblocking::data_from_account, Error as NonceError};
+use solana_sdk::{
+    account::Account,
+    commitment_config::CommitmentConfig,
+    hash::Hash,
+    instruction::Instruction,
+    nonce::{state::Data, State as NonceState},
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    system_instruction,
+    transaction::{uses_durable_nonce, Transaction},
+};
+use tracing::info;
+
+use crate::{
+    auth::Caller,
+    cluster::{Cluster, ClusterRpc},
+    error::AppError,
+    fees,
+    handlers::{parse_keypair, BLOCKHASH_COMMITMENT},
+    types::*,
+};
+
+/// A nonce account checked to be usable in place of a recent blockhash
+#[derive(Debug, Clone)]
+pub struct DurableNonce {
+    pub account: Pubkey,
+    pub authority: Pubkey,
+    pub blockhash: Hash,
+}
+
+impl DurableNonce {
+    /// Fetches the nonce account in `option`, whose authority defaults to `fee_payer`,
+    /// and checks the authority matches the one stored in it
+    pub async fn resolve(
+        cluster: &Cluster,
+        option: Option<&NonceOption>,
+        fee_payer: &Pubkey,
+    ) -> Result<Option<Self>, AppError> {
+        let Some(option) = option else { return Ok(None) };
+        let account = validate_pubkey(&option.account)?;
+        let authority = match &option.authority {
+            Some(authority) => validate_pubkey(authority)?,
+            None => *fee_payer,
+        };
+        let (_, data) = fetch(cluster, &account, CommitmentConfig::confirmed()).await?;
+        if data.authority != authority {
+            return Err(AppError::BadRequest(format!(
+                "Nonce account {} is controlled by {}, not {}",
+                account, data.authority, authority
+            )));
+        }
+        Ok(Some(DurableNonce { account, authority, blockhash: data.blockhash() }))
+    }
+
+    /// Prepends the advance instruction, which the runtime requires to come first
+    pub fn with_advance(nonce: Option<&Self>, instructions: &[Instruction]) -> Vec<Instruction> {
+        let mut advanced = Vec::with_capacity(instructions.len() + 1);
+        if let Some(nonce) = nonce {
+            advanced.push(system_instruction::advance_nonce_account(&nonce.account, &nonce.authority));
+        }
+        advanced.extend_from_slice(instructions);
+        advanced
+    }
+}
+
+// Nonce account and its initialized state
+async fn fetch(cluster: &Cluster, address: &Pubkey, commitment: CommitmentConfig) -> Result<(Account, Data), AppError> {
+    let account = cluster
+        .rpc_client
+        .get_account_with_commitment(address, commitment)
+        .await?
+        .value
+        .ok_or_else(|| AppError::NotFound(format!("Nonce account not found: {}", address)))?;
+    let data = data_from_account(&account).map_err(|err| match err {
+        NonceError::InvalidStateForOperation => {
+            AppError::BadRequest(format!("Nonce account {} is not initialized", address))
+        }
+        _ => AppError::BadRequest(format!("{} is not a nonce account", address)),
+    })?;
+    Ok((account, data))
+}
+
+/// Whether a durable nonce `transaction` can still land: its nonce hasn't been advanced
+/// and the account hasn't been closed. `None` for transactions that use a recent blockhash.
+pub async fn is_nonce_current(cluster: &Cluster, transaction: &Transaction) -> Result<Option<bool>, AppError> {
+    let Some(advance) = uses_durable_nonce(transaction) else { return Ok(None) };
+    let message = &transaction.message;
+    let Some(account) = advance.accounts.first().and_then(|index| message.account_keys.get(*index as usize)) else {
+        return Ok(None);
+    };
+    let account = cluster
+        .rpc_client
+        .get_account_with_commitment(account, CommitmentConfig::processed())
+        .await?
+        .value;
+    let data = account.as_ref().and_then(|account| data_from_account(account).ok());
+    Ok(Some(data.is_some_and(|data| data.blockhash() == message.recent_blockhash)))
+}
+
+pub async fn get_nonce_account(
+    ClusterRpc(cluster): ClusterRpc,
+    Path(address): Path<String>,
+    Query(query): Query<CommitmentQuery>,
+) -> Result<Json<NonceAccountResponse>, AppError> {
+    let pubkey = validate_pubkey(&address)?;
+    let (account, data) = fetch(&cluster, &pubkey, query.commitment()?).await?;
+    Ok(Json(nonce_account_response(address, &account, &data)))
+}
+
+fn nonce_account_response(address: String, account: &Account, data: &Data) -> NonceAccountResponse {
+    NonceAccountResponse {
+        address,
+        authority: data.authority.to_string(),
+        nonce: data.blockhash().to_string(),
+        lamports_per_signature: data.fee_calculator.lamports_per_signature,
+        lamports: account.lamports,
+    }
+}
+
+/// Creates and initializes a nonce account at a fresh address, funded by `payer`
+pub async fn create_nonce_account(
+    ClusterRpc(cluster): ClusterRpc,
+    caller: Caller,
+    Json(create_req): Json<CreateNonceRequest>,
+) -> Result<(StatusCode, Json<CreatedNonceResponse>), AppError> {
+    let payer = validate_pubkey(&create_req.payer)?;
+    caller.authorize_wallet(&payer)?;
+    let authority = match &create_req.authority {
+        Some(authority) => validate_pubkey(authority)?,
+        None => payer,
+    };
+    let keypair = parse_keypair(create_req.private_key.as_deref(), &payer)?;
+
+    let rent_exempt = cluster
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(NonceState::size())
+        .await?;
+    let lamports = create_req.lamports.unwrap_or(rent_exempt);
+    if lamports < rent_exempt {
+        return Err(AppError::InvalidAmount(format!(
+            "A nonce account needs at least {} lamports to be rent exempt",
+            rent_exempt
+        )));
+    }
+
+    let nonce_keypair = Keypair::new();
+    let nonce = nonce_keypair.pubkey();
+    let instructions = system_instruction::create_nonce_account(&payer, &nonce, &authority, lamports);
+    let signature = send(&cluster, &instructions, &keypair, &[&nonce_keypair], create_req.fee_strategy).await?;
+
+    info!("Created nonce account {} with authority {}: {}", nonce, authority, signature);
+
+    Ok((StatusCode::CREATED, Json(CreatedNonceResponse {
+        address: nonce.to_string(),
+        authority: authority.to_string(),
+        lamports,
+        signature: signature.to_string(),
+    })))
+}
+
+/// Moves the nonce on, invalidating every transaction signed with the current one
+pub async fn advance_nonce_account(
+    ClusterRpc(cluster): ClusterRpc,
+    caller: Caller,
+    Path(address): Path<String>,
+    Json(advance_req): Json<AdvanceNonceRequest>,
+) -> Result<Json<TransferResponse>, AppError> {
+    let pubkey = validate_pubkey(&address)?;
+    let (_, data) = fetch(&cluster, &pubkey, CommitmentConfig::confirmed()).await?;
+    caller.authorize_wallet(&data.authority)?;
+    let keypair = parse_keypair(advance_req.private_key.as_deref(), &data.authority)?;
+
+    let instruction = system_instruction::advance_nonce_account(&pubkey, &data.authority);
+    let signature = send(&cluster, &[instruction], &keypair, &[], advance_req.fee_strategy).await?;
+
+    info!("Advanced nonce account {}: {}", pubkey, signature);
+
+    Ok(Json(TransferResponse {
+        signature: signature.to_string(),
+        success: true,
+        message: format!("Advanced nonce account {}", pubkey),
+    }))
+}
+
+pub async fn withdraw_nonce_account(
+    ClusterRpc(cluster): ClusterRpc,
+    caller: Caller,
+    Path(address): Path<String>,
+    Json(withdraw_req): Json<WithdrawNonceRequest>,
+) -> Result<Json<TransferResponse>, AppError> {
+    let pubkey = validate_pubkey(&address)?;
+    let to = validate_pubkey(&withdraw_req.to)?;
+    let lamports = withdraw_req.amount.to_lamports()?;
+    let (account, data) = fetch(&cluster, &pubkey, CommitmentConfig::confirmed()).await?;
+    caller.authorize_wallet(&data.authority)?;
+    let keypair = parse_keypair(withdraw_req.private_key.as_deref(), &data.authority)?;
+
+    // The runtime also rejects partial withdrawals that would leave it below rent exemption
+    if lamports > account.lamports {
+        return Err(AppError::InvalidAmount(format!(
+            "Nonce account {} only holds {} lamports",
+            pubkey, account.lamports
+        )));
+    }
+
+    let instruction = system_instruction::withdraw_nonce_account(&pubkey, &data.authority, &to, lamports);
+    let signature = send(&cluster, &[instruction], &keypair, &[], withdraw_req.fee_strategy).await?;
+
+    info!("Withdrew {} lamports from nonce account {} to {}: {}", lamports, pubkey, to, signature);
+
+    Ok(Json(TransferResponse {
+        signature: signature.to_string(),
+        success: true,
+        message: format!("Withdrew {} SOL from nonce account {}", lamports_to_sol(lamports), pubkey),
+    }))
+}
+
+// Budgets, signs and confirms a transaction paid for by `payer`
+async fn send(
+    cluster: &Cluster,
+    instructions: &[Instruction],
+    payer: &Keypair,
+    signers: &[&Keypair],
+    fee_strategy: fees::FeeStrategy,
+) -> Result<Signature, AppError> {
+    let (instructions, _) =
+        fees::with_compute_budget(&cluster.rpc_client, instructions, &payer.pubkey(), fee_strategy).await?;
+    let (recent_blockhash, _) =
+        cluster.rpc_client.get_latest_blockhash_with_commitment(BLOCKHASH_COMMITMENT).await?;
+    let mut keypairs = vec![payer];
+    keypairs.extend_from_slice(signers);
+    let transaction =
+        Transaction::new_signed_with_payer(&instructions, Some(&payer.pubkey()), &keypairs, recent_blockhash);
+    Ok(cluster.rpc_client.send_and_confirm_transaction(&transaction).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prepends_the_advance_instruction() {
+        let nonce =
+            DurableNonce { account: Pubkey::new_unique(), authority: Pubkey::new_unique(), blockhash: Hash::new_unique() };
+        let payer = Pubkey::new_unique();
+        let transfer = system_instruction::transfer(&payer, &Pubkey::new_unique(), 1);
+
+        let unchanged = DurableNonce::with_advance(None, std::slice::from_ref(&transfer));
+        assert_eq!(unchanged, vec![transfer.clone()]);
+
+        let instructions = DurableNonce::with_advance(Some(&nonce), std::slice::from_ref(&transfer));
+        let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer));
+        transaction.message.recent_blockhash = nonce.blockhash;
+        let advance = uses_durable_nonce(&transaction).expect("advance instruction comes first");
+        assert_eq!(transaction.message.account_keys[advance.accounts[0] as usize], nonce.account);
+        assert_eq!(instructions[1], transfer);
+    }
+}
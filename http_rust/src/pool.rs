@@ -0,0 +1,346 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind, Result as ClientResult},
+    nonblocking::rpc_client::RpcClient,
+    rpc_client::RpcClientConfig,
+    rpc_custom_error::JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+    rpc_request::{RpcError, RpcRequest},
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use solana_rpc_client::http_sender::HttpSender;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+use crate::ratelimit;
+
+/// Weight of the newest sample in the latency and error-rate moving averages
+const EWMA_WEIGHT: f64 = 0.2;
+/// Endpoints failing more often than this are only used when nothing else is left
+const MAX_HEALTHY_ERROR_RATE: f64 = 0.5;
+/// Endpoints this many slots behind the best known slot are treated as unhealthy
+const MAX_HEALTHY_SLOT_LAG: u64 = 50;
+
+type BoxedSender = Box<dyn RpcSender + Send + Sync + 'static>;
+
+/// A set of RPC endpoints that reads are routed across by health score.
+///
+/// The pool is exposed to handlers as an ordinary `RpcClient` (see [`RpcPool::client`]),
+/// so failover happens underneath every RPC call without handlers knowing about it.
+pub struct RpcPool {
+    endpoints: Vec<Endpoint>,
+}
+
+struct Endpoint {
+    url: String,
+    sender: BoxedSender,
+    stats: Mutex<EndpointStats>,
+}
+
+#[derive(Default)]
+struct EndpointStats {
+    latency_ms: Option<f64>,
+    error_rate: f64,
+    slot: Option<u64>,
+    requests: u64,
+    errors: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EndpointStatus {
+    pub url: String,
+    pub healthy: bool,
+    pub latency_ms: Option<f64>,
+    pub error_rate: f64,
+    pub slot: Option<u64>,
+    pub slot_lag: Option<u64>,
+    pub requests: u64,
+    pub errors: u64,
+}
+
+impl RpcPool {
+    pub fn new(urls: &[String]) -> Self {
+        Self::from_senders(
+            urls.iter()
+                .map(|url| (url.clone(), Box::new(HttpSender::new(url)) as BoxedSender))
+                .collect(),
+        )
+    }
+
+    pub fn from_senders(senders: Vec<(String, BoxedSender)>) -> Self {
+        RpcPool {
+            endpoints: senders
+                .into_iter()
+                .map(|(url, sender)| Endpoint {
+                    url,
+                    sender,
+                    stats: Mutex::new(EndpointStats::default()),
+                })
+                .collect(),
+        }
+    }
+
+    /// An `RpcClient` whose every request goes through this pool
+    pub fn client(self: &Arc<Self>) -> RpcClient {
+        RpcClient::new_sender(PoolSender { pool: self.clone() }, RpcClientConfig::default())
+    }
+
+    pub fn status(&self) -> Vec<EndpointStatus> {
+        let best_slot = self.best_slot();
+        self.endpoints
+            .iter()
+            .map(|endpoint| {
+                let stats = endpoint.stats.lock().unwrap();
+                let slot_lag = stats.slot.zip(best_slot).map(|(slot, best)| best.saturating_sub(slot));
+                EndpointStatus {
+                    url: endpoint.url.clone(),
+                    healthy: is_healthy(&stats, slot_lag),
+                    latency_ms: stats.latency_ms,
+                    error_rate: stats.error_rate,
+                    slot: stats.slot,
+                    slot_lag,
+                    requests: stats.requests,
+                    errors: stats.errors,
+                }
+            })
+            .collect()
+    }
+
+    /// Polls every endpoint's slot forever so slot lag stays current between requests.
+    pub async fn monitor(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            self.refresh_slots().await;
+        }
+    }
+
+    pub async fn refresh_slots(&self) {
+        let probes = self.endpoints.iter().map(|endpoint| async move {
+            let started = Instant::now();
+            let result = endpoint
+                .sender
+                .send(RpcRequest::GetSlot, serde_json::json!([{ "commitment": "confirmed" }]))
+                .await;
+            match result.map(|value| value.as_u64()) {
+                Ok(Some(slot)) => {
+                    endpoint.record_success(started.elapsed());
+                    endpoint.stats.lock().unwrap().slot = Some(slot);
+                }
+                Ok(None) => endpoint.record_failure(),
+                Err(err) => {
+                    warn!("RPC endpoint {} failed slot probe: {}", endpoint.url, err);
+                    endpoint.record_failure();
+                }
+            }
+        });
+        futures::future::join_all(probes).await;
+    }
+
+    fn best_slot(&self) -> Option<u64> {
+        self.endpoints
+            .iter()
+            .filter_map(|endpoint| endpoint.stats.lock().unwrap().slot)
+            .max()
+    }
+
+    // Endpoint indices, healthiest first
+    fn ranked(&self) -> Vec<usize> {
+        let best_slot = self.best_slot();
+        let mut scored: Vec<(bool, f64, usize)> = self
+            .endpoints
+            .iter()
+            .enumerate()
+            .map(|(index, endpoint)| {
+                let stats = endpoint.stats.lock().unwrap();
+                let slot_lag = stats.slot.zip(best_slot).map(|(slot, best)| best.saturating_sub(slot));
+                (!is_healthy(&stats, slot_lag), score(&stats, slot_lag), index)
+            })
+            .collect();
+        scored.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)).then(a.2.cmp(&b.2)));
+        scored.into_iter().map(|(_, _, index)| index).collect()
+    }
+}
+
+impl Endpoint {
+    fn record_success(&self, elapsed: Duration) {
+        let mut stats = self.stats.lock().unwrap();
+        let latency = elapsed.as_secs_f64() * 1000.0;
+        stats.latency_ms = Some(match stats.latency_ms {
+            Some(average) => average + EWMA_WEIGHT * (latency - average),
+            None => latency,
+        });
+        stats.error_rate -= EWMA_WEIGHT * stats.error_rate;
+        stats.requests += 1;
+    }
+
+    fn record_failure(&self) {
+        let mut stats = self.stats.lock().unwrap();
+        stats.error_rate += EWMA_WEIGHT * (1.0 - stats.error_rate);
+        stats.requests += 1;
+        stats.errors += 1;
+    }
+}
+
+fn is_healthy(stats: &EndpointStats, slot_lag: Option<u64>) -> bool {
+    stats.error_rate <= MAX_HEALTHY_ERROR_RATE && slot_lag.unwrap_or(0) <= MAX_HEALTHY_SLOT_LAG
+}
+
+// Lower is better: latency, inflated by recent errors and slot lag
+fn score(stats: &EndpointStats, slot_lag: Option<u64>) -> f64 {
+    let latency = stats.latency_ms.unwrap_or(0.0);
+    (latency + 1.0) * (1.0 + 10.0 * stats.error_rate) * (1.0 + slot_lag.unwrap_or(0) as f64 / 10.0)
+}
+
+// Transactions must not be broadcast twice by accident; everything else is a read
+fn is_idempotent(request: RpcRequest) -> bool {
+    !matches!(request, RpcRequest::SendTransaction | RpcRequest::RequestAirdrop)
+}
+
+// Errors that say something about the endpoint rather than the request itself
+fn is_endpoint_failure(err: &ClientError) -> bool {
+    match err.kind() {
+        ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_) => true,
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => {
+            *code == JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY
+        }
+        _ => false,
+    }
+}
+
+struct PoolSender {
+    pool: Arc<RpcPool>,
+}
+
+#[async_trait]
+impl RpcSender for PoolSender {
+    async fn send(&self, request: RpcRequest, params: serde_json::Value) -> ClientResult<serde_json::Value> {
+        let attempts = if is_idempotent(request) { self.pool.endpoints.len() } else { 1 };
+        let mut last_err = None;
+        for index in self.pool.ranked().into_iter().take(attempts) {
+            let endpoint = &self.pool.endpoints[index];
+            ratelimit::record_rpc_call();
+            let started = Instant::now();
+            match endpoint.sender.send(request, params.clone()).await {
+                Err(err) if is_endpoint_failure(&err) => {
+                    warn!("RPC endpoint {} failed {}: {}", endpoint.url, request, err);
+                    endpoint.record_failure();
+                    last_err = Some(err);
+                }
+                result => {
+                    endpoint.record_success(started.elapsed());
+                    return result;
+                }
+            }
+        }
+        if attempts > 1 {
+            info!("All RPC endpoints failed {}", request);
+        }
+        Err(last_err.unwrap_or_else(|| ClientErrorKind::Custom("No RPC endpoints configured".to_string()).into()))
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.pool
+            .endpoints
+            .iter()
+            .map(|endpoint| endpoint.sender.get_transport_stats())
+            .fold(RpcTransportStats::default(), |mut total, stats| {
+                total.request_count += stats.request_count;
+                total.elapsed_time += stats.elapsed_time;
+                total.rate_limited_time += stats.rate_limited_time;
+                total
+            })
+    }
+
+    fn url(&self) -> String {
+        self.pool
+            .ranked()
+            .first()
+            .map(|index| self.pool.endpoints[*index].url.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Answers `getSlot` with a fixed slot, or fails every call like an unreachable node.
+    struct FakeSender {
+        slot: u64,
+        down: bool,
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl RpcSender for FakeSender {
+        async fn send(&self, request: RpcRequest, _params: serde_json::Value) -> ClientResult<serde_json::Value> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.down {
+                return Err(std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "down").into());
+            }
+            match request {
+                RpcRequest::GetVersion => Ok(serde_json::json!({ "solana-core": "1.18.26" })),
+                _ => Ok(serde_json::json!(self.slot)),
+            }
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            RpcTransportStats::default()
+        }
+
+        fn url(&self) -> String {
+            "fake".to_string()
+        }
+    }
+
+    fn pool(endpoints: &[(u64, bool)]) -> (Arc<RpcPool>, Vec<Arc<AtomicUsize>>) {
+        let calls: Vec<_> = endpoints.iter().map(|_| Arc::new(AtomicUsize::new(0))).collect();
+        let senders = endpoints
+            .iter()
+            .zip(&calls)
+            .enumerate()
+            .map(|(index, ((slot, down), calls))| {
+                let sender = FakeSender { slot: *slot, down: *down, calls: calls.clone() };
+                (format!("endpoint-{}", index), Box::new(sender) as BoxedSender)
+            })
+            .collect();
+        (Arc::new(RpcPool::from_senders(senders)), calls)
+    }
+
+    #[tokio::test]
+    async fn fails_over_reads_to_a_working_endpoint() {
+        let (pool, calls) = pool(&[(100, true), (100, false)]);
+        let client = pool.client();
+
+        assert_eq!(client.get_slot().await.unwrap(), 100);
+        assert_eq!(calls[0].load(Ordering::SeqCst), 1);
+
+        // The failed endpoint now ranks last, so the next read goes straight to the healthy one
+        assert_eq!(client.get_slot().await.unwrap(), 100);
+        assert_eq!(calls[0].load(Ordering::SeqCst), 1);
+        assert_eq!(pool.status()[0].errors, 1);
+    }
+
+    #[tokio::test]
+    async fn ranks_lagging_endpoints_as_unhealthy() {
+        let (pool, _) = pool(&[(100, false), (1_000, false)]);
+        pool.refresh_slots().await;
+
+        let status = pool.status();
+        assert!(!status[0].healthy);
+        assert_eq!(status[0].slot_lag, Some(900));
+        assert!(status[1].healthy);
+        assert_eq!(pool.ranked(), vec![1, 0]);
+    }
+
+    #[test]
+    fn only_retries_idempotent_requests() {
+        assert!(is_idempotent(RpcRequest::GetBalance));
+        assert!(!is_idempotent(RpcRequest::SendTransaction));
+    }
+}
@@ -0,0 +1,463 @@
+use axum::{
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    response::Response,
+};
+use futures::{stream::BoxStream, SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::rpc_config::{
+    RpcAccountInfoConfig, RpcSignatureSubscribeConfig, RpcTransactionLogsConfig, RpcTransactionLogsFilter,
+};
+use solana_pubsub_client::nonblocking::pubsub_client::PubsubClient;
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::{
+    sync::{broadcast, mpsc, oneshot},
+    task::JoinHandle,
+};
+use tracing::{info, warn};
+
+use crate::{auth::WS_PROTOCOL, cluster::ClusterRpc, types::*};
+
+/// Notifications buffered per topic before slow sockets start missing some
+const TOPIC_BUFFER: usize = 64;
+/// Outgoing messages buffered per socket
+const SOCKET_BUFFER: usize = 256;
+const MAX_SUBSCRIPTIONS_PER_SOCKET: usize = 100;
+const INITIAL_RESUBSCRIBE_DELAY: Duration = Duration::from_millis(500);
+const MAX_RESUBSCRIBE_DELAY: Duration = Duration::from_secs(30);
+
+/// Something a client can subscribe to. Identical topics share one upstream subscription.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Topic {
+    Account(Pubkey, CommitmentConfig),
+    Signature(Signature, CommitmentConfig),
+    /// Logs of transactions mentioning an address
+    Logs(Pubkey, CommitmentConfig),
+}
+
+impl Topic {
+    fn kind(&self) -> &'static str {
+        match self {
+            Topic::Account(..) => "account",
+            Topic::Signature(..) => "signature",
+            Topic::Logs(..) => "logs",
+        }
+    }
+}
+
+/// What upstream delivered for a topic, fanned out to every subscribed socket.
+#[derive(Debug, Clone)]
+enum Event {
+    Notification(Value),
+    /// The upstream subscription failed and is being retried
+    Error(String),
+}
+
+struct TopicEntry {
+    id: u64,
+    sender: broadcast::Sender<Event>,
+    subscribers: usize,
+    // Dropped when the last subscriber leaves, which stops the upstream task
+    _cancel: oneshot::Sender<()>,
+}
+
+/// One cluster's upstream `PubsubClient`, shared by every downstream socket.
+///
+/// The connection is opened on first use and reopened after it drops. Each distinct
+/// [`Topic`] is subscribed upstream once and unsubscribed when its last listener leaves.
+pub struct PubsubHub {
+    ws_url: String,
+    client: tokio::sync::Mutex<Option<Arc<PubsubClient>>>,
+    topics: Mutex<HashMap<Topic, TopicEntry>>,
+    next_topic_id: AtomicU64,
+}
+
+impl PubsubHub {
+    pub fn new(ws_url: String) -> Self {
+        PubsubHub {
+            ws_url,
+            client: tokio::sync::Mutex::new(None),
+            topics: Mutex::new(HashMap::new()),
+            next_topic_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Number of distinct upstream subscriptions
+    pub fn topic_count(&self) -> usize {
+        self.topics.lock().unwrap().len()
+    }
+
+    fn subscribe(self: &Arc<Self>, topic: Topic) -> (broadcast::Receiver<Event>, TopicHandle) {
+        let mut topics = self.topics.lock().unwrap();
+        let entry = topics.entry(topic.clone()).or_insert_with(|| {
+            let id = self.next_topic_id.fetch_add(1, Ordering::Relaxed);
+            let (sender, _) = broadcast::channel(TOPIC_BUFFER);
+            let (cancel, cancelled) = oneshot::channel();
+            tokio::spawn(self.clone().run_topic(topic.clone(), id, sender.clone(), cancelled));
+            TopicEntry { id, sender, subscribers: 0, _cancel: cancel }
+        });
+        entry.subscribers += 1;
+        let handle = TopicHandle { hub: self.clone(), topic, id: entry.id };
+        (entry.sender.subscribe(), handle)
+    }
+
+    fn release(&self, topic: &Topic, id: u64) {
+        let mut topics = self.topics.lock().unwrap();
+        if let Some(entry) = topics.get_mut(topic).filter(|entry| entry.id == id) {
+            entry.subscribers -= 1;
+            if entry.subscribers == 0 {
+                topics.remove(topic);
+            }
+        }
+    }
+
+    async fn client(&self) -> Result<Arc<PubsubClient>, String> {
+        let mut client = self.client.lock().await;
+        if let Some(client) = client.as_ref() {
+            return Ok(client.clone());
+        }
+        let connected = Arc::new(
+            PubsubClient::new(&self.ws_url)
+                .await
+                .map_err(|err| format!("Failed to connect to {}: {}", self.ws_url, err))?,
+        );
+        info!("Connected to pubsub endpoint {}", self.ws_url);
+        *client = Some(connected.clone());
+        Ok(connected)
+    }
+
+    // Drops a connection that stopped working so the next subscription reconnects
+    async fn reset_client(&self, failed: &Arc<PubsubClient>) {
+        let mut client = self.client.lock().await;
+        if client.as_ref().is_some_and(|client| Arc::ptr_eq(client, failed)) {
+            *client = None;
+        }
+    }
+
+    // Keeps `topic` subscribed upstream until cancelled, resubscribing with backoff
+    // whenever the subscription or the connection fails.
+    async fn run_topic(
+        self: Arc<Self>,
+        topic: Topic,
+        id: u64,
+        sender: broadcast::Sender<Event>,
+        mut cancelled: oneshot::Receiver<()>,
+    ) {
+        let mut delay = INITIAL_RESUBSCRIBE_DELAY;
+        loop {
+            match self.forward(&topic, &sender, &mut cancelled).await {
+                Ok(Forwarded::Cancelled) => return,
+                // Signature subscriptions end upstream after their one notification
+                Ok(Forwarded::Ended { notified: true }) if matches!(topic, Topic::Signature(..)) => break,
+                Ok(Forwarded::Ended { .. }) => {
+                    warn!("Pubsub {} subscription ended, resubscribing", topic.kind());
+                    let _ = sender.send(Event::Error("Upstream subscription ended, resubscribing".to_string()));
+                }
+                Err(err) => {
+                    warn!("Pubsub {} subscription failed: {}", topic.kind(), err);
+                    let _ = sender.send(Event::Error(err));
+                }
+            }
+            tokio::select! {
+                _ = &mut cancelled => return,
+                _ = tokio::time::sleep(delay) => {}
+            }
+            delay = (delay * 2).min(MAX_RESUBSCRIBE_DELAY);
+        }
+        // Finished: forget the topic so later subscribers start a fresh upstream subscription
+        let mut topics = self.topics.lock().unwrap();
+        if topics.get(&topic).is_some_and(|entry| entry.id == id) {
+            topics.remove(&topic);
+        }
+    }
+
+    async fn forward(
+        &self,
+        topic: &Topic,
+        sender: &broadcast::Sender<Event>,
+        cancelled: &mut oneshot::Receiver<()>,
+    ) -> Result<Forwarded, String> {
+        let client = self.client().await?;
+        let forwarded = subscribe_and_pump(&client, topic, sender, cancelled).await;
+        // Anything but a signature's natural end means the connection may be gone
+        let finished = matches!(
+            (topic, &forwarded),
+            (Topic::Signature(..), Ok(Forwarded::Ended { notified: true })) | (_, Ok(Forwarded::Cancelled))
+        );
+        if !finished {
+            self.reset_client(&client).await;
+        }
+        forwarded
+    }
+}
+
+async fn subscribe_and_pump(
+    client: &PubsubClient,
+    topic: &Topic,
+    sender: &broadcast::Sender<Event>,
+    cancelled: &mut oneshot::Receiver<()>,
+) -> Result<Forwarded, String> {
+    let subscribe_error = |err| format!("Failed to subscribe to {}: {}", topic.kind(), err);
+    match *topic {
+        Topic::Account(address, commitment) => {
+            let config = RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::JsonParsed),
+                commitment: Some(commitment),
+                ..RpcAccountInfoConfig::default()
+            };
+            let (stream, unsubscribe) = client
+                .account_subscribe(&address, Some(config))
+                .await
+                .map_err(subscribe_error)?;
+            Ok(pump(stream, unsubscribe, sender, cancelled).await)
+        }
+        Topic::Signature(signature, commitment) => {
+            let config = RpcSignatureSubscribeConfig {
+                commitment: Some(commitment),
+                enable_received_notification: Some(false),
+            };
+            let (stream, unsubscribe) = client
+                .signature_subscribe(&signature, Some(config))
+                .await
+                .map_err(subscribe_error)?;
+            Ok(pump(stream, unsubscribe, sender, cancelled).await)
+        }
+        Topic::Logs(address, commitment) => {
+            let filter = RpcTransactionLogsFilter::Mentions(vec![address.to_string()]);
+            let config = RpcTransactionLogsConfig { commitment: Some(commitment) };
+            let (stream, unsubscribe) = client
+                .logs_subscribe(filter, config)
+                .await
+                .map_err(subscribe_error)?;
+            Ok(pump(stream, unsubscribe, sender, cancelled).await)
+        }
+    }
+}
+
+enum Forwarded {
+    Cancelled,
+    Ended { notified: bool },
+}
+
+// Relays upstream notifications until the stream ends or every listener has left
+async fn pump<T: Serialize>(
+    mut stream: BoxStream<'_, T>,
+    unsubscribe: Box<dyn FnOnce() -> futures::future::BoxFuture<'static, ()> + Send>,
+    sender: &broadcast::Sender<Event>,
+    cancelled: &mut oneshot::Receiver<()>,
+) -> Forwarded {
+    let mut notified = false;
+    loop {
+        tokio::select! {
+            _ = &mut *cancelled => {
+                unsubscribe().await;
+                return Forwarded::Cancelled;
+            }
+            item = stream.next() => match item {
+                Some(item) => {
+                    notified = true;
+                    let value = serde_json::to_value(item).unwrap_or(Value::Null);
+                    let _ = sender.send(Event::Notification(value));
+                }
+                None => return Forwarded::Ended { notified },
+            },
+        }
+    }
+}
+
+/// A socket's claim on a topic; releasing the last one unsubscribes upstream.
+struct TopicHandle {
+    hub: Arc<PubsubHub>,
+    topic: Topic,
+    id: u64,
+}
+
+impl Drop for TopicHandle {
+    fn drop(&mut self) {
+        self.hub.release(&self.topic, self.id);
+    }
+}
+
+/// Messages a client sends over `/ws`
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+enum ClientMessage {
+    Subscribe {
+        #[serde(flatten)]
+        topic: TopicRequest,
+        #[serde(default)]
+        commitment: Option<String>,
+    },
+    Unsubscribe { subscription: u64 },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum TopicRequest {
+    Account { address: String },
+    Signature { signature: String },
+    Logs { address: String },
+}
+
+impl TopicRequest {
+    fn topic(&self, commitment: Option<&str>) -> Result<Topic, String> {
+        let commitment = parse_commitment(commitment).map_err(|err| err.to_string())?;
+        let pubkey = |address: &str| validate_pubkey(address).map_err(|err| err.to_string());
+        Ok(match self {
+            TopicRequest::Account { address } => Topic::Account(pubkey(address)?, commitment),
+            TopicRequest::Signature { signature } => Topic::Signature(
+                signature.parse().map_err(|_| format!("Invalid signature: {}", signature))?,
+                commitment,
+            ),
+            TopicRequest::Logs { address } => Topic::Logs(pubkey(address)?, commitment),
+        })
+    }
+}
+
+// WebSocket endpoint: account, signature and logs subscriptions on the request's cluster.
+// Selecting WS_PROTOCOL lets browsers that sent their key as a subprotocol complete the handshake.
+pub async fn subscribe_ws(ClusterRpc(cluster): ClusterRpc, ws: WebSocketUpgrade) -> Response {
+    let hub = cluster.pubsub.clone();
+    ws.protocols([WS_PROTOCOL]).on_upgrade(move |socket| serve_socket(socket, hub))
+}
+
+async fn serve_socket(socket: WebSocket, hub: Arc<PubsubHub>) {
+    let (mut sink, mut incoming) = socket.split();
+    let (outgoing, mut outgoing_rx) = mpsc::channel::<Value>(SOCKET_BUFFER);
+    let writer = tokio::spawn(async move {
+        while let Some(message) = outgoing_rx.recv().await {
+            if sink.send(Message::Text(message.to_string())).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut subscriptions: HashMap<u64, JoinHandle<()>> = HashMap::new();
+    let mut next_subscription = 1;
+    while let Some(Ok(message)) = incoming.next().await {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        subscriptions.retain(|_, forwarder| !forwarder.is_finished());
+
+        let reply = match serde_json::from_str::<ClientMessage>(&text) {
+            Err(err) => json!({ "type": "error", "message": format!("Invalid message: {}", err) }),
+            Ok(ClientMessage::Subscribe { topic, commitment }) => match topic.topic(commitment.as_deref()) {
+                Err(message) => json!({ "type": "error", "message": message }),
+                Ok(_) if subscriptions.len() >= MAX_SUBSCRIPTIONS_PER_SOCKET => json!({
+                    "type": "error",
+                    "message": format!("At most {} subscriptions per connection", MAX_SUBSCRIPTIONS_PER_SOCKET),
+                }),
+                Ok(topic) => {
+                    let subscription = next_subscription;
+                    next_subscription += 1;
+                    let kind = topic.kind();
+                    let (receiver, handle) = hub.subscribe(topic);
+                    let forwarder = tokio::spawn(forward_to_socket(subscription, receiver, handle, outgoing.clone()));
+                    subscriptions.insert(subscription, forwarder);
+                    json!({ "type": "subscribed", "subscription": subscription, "kind": kind })
+                }
+            },
+            Ok(ClientMessage::Unsubscribe { subscription }) => match subscriptions.remove(&subscription) {
+                Some(forwarder) => {
+                    forwarder.abort();
+                    json!({ "type": "unsubscribed", "subscription": subscription })
+                }
+                None => json!({ "type": "error", "message": format!("Unknown subscription: {}", subscription) }),
+            },
+        };
+        if outgoing.send(reply).await.is_err() {
+            break;
+        }
+    }
+
+    for forwarder in subscriptions.values() {
+        forwarder.abort();
+    }
+    writer.abort();
+}
+
+// Copies one topic's events onto a socket; dropping `_handle` releases the topic
+async fn forward_to_socket(
+    subscription: u64,
+    mut receiver: broadcast::Receiver<Event>,
+    _handle: TopicHandle,
+    outgoing: mpsc::Sender<Value>,
+) {
+    loop {
+        let message = match receiver.recv().await {
+            Ok(Event::Notification(result)) => {
+                json!({ "type": "notification", "subscription": subscription, "result": result })
+            }
+            Ok(Event::Error(message)) => {
+                json!({ "type": "error", "subscription": subscription, "message": message })
+            }
+            Err(broadcast::error::RecvError::Lagged(missed)) => json!({
+                "type": "error",
+                "subscription": subscription,
+                "message": format!("Connection too slow, {} notifications dropped", missed),
+            }),
+            Err(broadcast::error::RecvError::Closed) => {
+                let _ = outgoing.send(json!({ "type": "closed", "subscription": subscription })).await;
+                return;
+            }
+        };
+        if outgoing.send(message).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_client_messages() {
+        let address = Pubkey::new_unique();
+        let message = json!({ "action": "subscribe", "kind": "logs", "address": address.to_string(), "commitment": "finalized" });
+        match serde_json::from_value::<ClientMessage>(message).unwrap() {
+            ClientMessage::Subscribe { topic, commitment } => assert_eq!(
+                topic.topic(commitment.as_deref()).unwrap(),
+                Topic::Logs(address, CommitmentConfig::finalized())
+            ),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let bad_signature = json!({ "action": "subscribe", "kind": "signature", "signature": "nope" });
+        match serde_json::from_value::<ClientMessage>(bad_signature).unwrap() {
+            ClientMessage::Subscribe { topic, commitment } => assert!(topic.topic(commitment.as_deref()).is_err()),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(serde_json::from_value::<ClientMessage>(json!({ "action": "subscribe", "kind": "slot" })).is_err());
+    }
+
+    #[tokio::test]
+    async fn shares_one_upstream_subscription_per_topic() {
+        // Nothing listens here, so upstream tasks just keep retrying in the background
+        let hub = Arc::new(PubsubHub::new("ws://127.0.0.1:9".to_string()));
+        let account = Topic::Account(Pubkey::new_unique(), CommitmentConfig::confirmed());
+
+        let (_first, first_handle) = hub.subscribe(account.clone());
+        let (_second, second_handle) = hub.subscribe(account.clone());
+        let (_logs, logs_handle) = hub.subscribe(Topic::Logs(Pubkey::new_unique(), CommitmentConfig::confirmed()));
+        assert_eq!(hub.topic_count(), 2);
+
+        drop(first_handle);
+        assert_eq!(hub.topic_count(), 2);
+        drop(second_handle);
+        drop(logs_handle);
+        assert_eq!(hub.topic_count(), 0);
+    }
+}
@@ -0,0 +1,444 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    middleware::Next,
+    response::{Json, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    net::{IpAddr, SocketAddr},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{auth::Caller, error::AppError, AppState};
+
+/// Subjects idle for this long are forgotten; their buckets would be full again anyway
+const IDLE_SUBJECT_TTL: Duration = Duration::from_secs(600);
+
+tokio::task_local! {
+    // RPC credits used by the request currently being handled on this task
+    static RPC_CREDITS: Arc<AtomicU64>;
+}
+
+/// Counts one upstream RPC call against the current request, if it is rate limited.
+///
+/// Called by the RPC pool for every attempt, so failover retries are billed too.
+pub fn record_rpc_call() {
+    let _ = RPC_CREDITS.try_with(|credits| credits.fetch_add(1, Ordering::Relaxed));
+}
+
+/// Spawns `future` on its own task, still billing its RPC calls to the current request.
+pub fn spawn_billed<F>(future: F) -> tokio::task::JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    match RPC_CREDITS.try_with(Arc::clone) {
+        Ok(credits) => tokio::spawn(RPC_CREDITS.scope(credits, future)),
+        Err(_) => tokio::spawn(future),
+    }
+}
+
+/// Client IP of a request, as seen by [`rate_limit_ip`], for later layers to read
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+/// Per-minute allowance for one API key or client IP. Zero means unlimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quota {
+    pub requests_per_minute: u32,
+    pub rpc_credits_per_minute: u32,
+}
+
+impl Quota {
+    pub const UNLIMITED: Quota = Quota { requests_per_minute: 0, rpc_credits_per_minute: 0 };
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimits {
+    /// Default quota for API keys without their own `rate_limit`
+    pub per_key: Quota,
+    pub per_ip: Quota,
+    /// Take the client IP from `X-Forwarded-For` (only behind a trusted proxy)
+    pub trust_forwarded_for: bool,
+}
+
+impl RateLimits {
+    pub fn unlimited() -> Self {
+        RateLimits { per_key: Quota::UNLIMITED, per_ip: Quota::UNLIMITED, trust_forwarded_for: false }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Subject {
+    /// An API key or session, by its unique [`ApiKey::id`](crate::auth::ApiKey::id)
+    Key(String),
+    Ip(IpAddr),
+}
+
+impl fmt::Display for Subject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Subject::Key(id) => write!(f, "key:{}", id),
+            Subject::Ip(ip) => write!(f, "ip:{}", ip),
+        }
+    }
+}
+
+/// Token bucket holding up to one minute of allowance. RPC credits are charged
+/// after the fact, so the balance may go negative and block later requests.
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    per_minute: u32,
+}
+
+impl Bucket {
+    fn new(per_minute: u32) -> Self {
+        Bucket { tokens: per_minute as f64, per_minute }
+    }
+
+    fn refill(&mut self, elapsed: Duration) {
+        let per_minute = self.per_minute as f64;
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * per_minute / 60.0).min(per_minute);
+    }
+
+    // Time until `amount` tokens are available, or `None` if they already are
+    fn wait_for(&self, amount: f64) -> Option<Duration> {
+        if self.per_minute == 0 || self.tokens >= amount {
+            return None;
+        }
+        Some(Duration::from_secs_f64((amount - self.tokens) * 60.0 / self.per_minute as f64))
+    }
+
+    fn remaining(&self) -> Option<u64> {
+        (self.per_minute > 0).then(|| self.tokens.max(0.0) as u64)
+    }
+}
+
+#[derive(Debug)]
+struct Usage {
+    quota: Quota,
+    requests: Bucket,
+    rpc_credits: Bucket,
+    updated: Instant,
+    total_requests: u64,
+    total_rpc_credits: u64,
+    rejected: u64,
+}
+
+impl Usage {
+    fn new(quota: Quota) -> Self {
+        Usage {
+            quota,
+            requests: Bucket::new(quota.requests_per_minute),
+            rpc_credits: Bucket::new(quota.rpc_credits_per_minute),
+            updated: Instant::now(),
+            total_requests: 0,
+            total_rpc_credits: 0,
+            rejected: 0,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.updated);
+        self.requests.refill(elapsed);
+        self.rpc_credits.refill(elapsed);
+        self.updated = now;
+    }
+
+    // Picks up quota changes, e.g. after the API key file was reloaded
+    fn set_quota(&mut self, quota: Quota) {
+        if self.quota != quota {
+            self.quota = quota;
+            self.requests = Bucket::new(quota.requests_per_minute);
+            self.rpc_credits = Bucket::new(quota.rpc_credits_per_minute);
+        }
+    }
+
+    fn wait(&self) -> Option<Duration> {
+        // A request needs one request token and a credit balance that isn't overdrawn
+        self.requests.wait_for(1.0).max(self.rpc_credits.wait_for(0.0))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubjectUsage {
+    pub subject: String,
+    pub limits: Quota,
+    pub requests: u64,
+    pub rpc_credits: u64,
+    pub rejected: u64,
+    /// `None` when the corresponding limit is disabled
+    pub requests_remaining: Option<u64>,
+    pub rpc_credits_remaining: Option<u64>,
+}
+
+/// Request and RPC credit quotas per API key and per client IP.
+pub struct RateLimiter {
+    limits: RateLimits,
+    usage: Mutex<HashMap<Subject, Usage>>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        RateLimiter { limits, usage: Mutex::new(HashMap::new()) }
+    }
+
+    /// Takes one request from every subject's quota, or none if any of them is exhausted.
+    fn admit(&self, subjects: &[(Subject, Quota)]) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut usage = self.usage.lock().unwrap();
+        let mut wait = None;
+        for (subject, quota) in subjects {
+            let entry = usage.entry(subject.clone()).or_insert_with(|| Usage::new(*quota));
+            entry.set_quota(*quota);
+            entry.refill(now);
+            wait = wait.max(entry.wait());
+        }
+        for (subject, _) in subjects {
+            let entry = usage.get_mut(subject).unwrap();
+            match wait {
+                Some(_) => entry.rejected += 1,
+                None => {
+                    entry.requests.tokens -= 1.0;
+                    entry.total_requests += 1;
+                }
+            }
+        }
+        wait.map_or(Ok(()), Err)
+    }
+
+    fn admit_or_reject(&self, subjects: &[(Subject, Quota)]) -> Result<(), AppError> {
+        self.admit(subjects).map_err(|wait| {
+            let subject = subjects.iter().map(|(subject, _)| subject.to_string()).collect::<Vec<_>>().join(", ");
+            AppError::RateLimited {
+                message: format!("Quota exhausted for {}", subject),
+                retry_after_secs: wait.as_secs_f64().ceil().max(1.0) as u64,
+            }
+        })
+    }
+
+    fn charge(&self, subjects: &[(Subject, Quota)], rpc_credits: u64) {
+        let mut usage = self.usage.lock().unwrap();
+        for (subject, _) in subjects {
+            if let Some(entry) = usage.get_mut(subject) {
+                entry.rpc_credits.tokens -= rpc_credits as f64;
+                entry.total_rpc_credits += rpc_credits;
+            }
+        }
+    }
+
+    /// Usage of every tracked subject, heaviest RPC users first
+    pub fn usage(&self) -> Vec<SubjectUsage> {
+        let now = Instant::now();
+        let mut usage = self.usage.lock().unwrap();
+        let mut report: Vec<SubjectUsage> = usage
+            .iter_mut()
+            .map(|(subject, entry)| {
+                entry.refill(now);
+                SubjectUsage {
+                    subject: subject.to_string(),
+                    limits: entry.quota,
+                    requests: entry.total_requests,
+                    rpc_credits: entry.total_rpc_credits,
+                    rejected: entry.rejected,
+                    requests_remaining: entry.requests.remaining(),
+                    rpc_credits_remaining: entry.rpc_credits.remaining(),
+                }
+            })
+            .collect();
+        report.sort_by(|a, b| b.rpc_credits.cmp(&a.rpc_credits).then_with(|| a.subject.cmp(&b.subject)));
+        report
+    }
+
+    /// Forgets idle subjects forever so one-off client IPs don't accumulate.
+    pub async fn prune(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let now = Instant::now();
+            self.usage
+                .lock()
+                .unwrap()
+                .retain(|_, entry| now.duration_since(entry.updated) < IDLE_SUBJECT_TTL);
+        }
+    }
+
+    fn client_ip(&self, request: &Request) -> Option<IpAddr> {
+        if self.limits.trust_forwarded_for {
+            let forwarded = request
+                .headers()
+                .get("x-forwarded-for")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.split(',').next())
+                .and_then(|ip| ip.trim().parse().ok());
+            if forwarded.is_some() {
+                return forwarded;
+            }
+        }
+        request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())
+    }
+}
+
+/// Enforces the client IP's quota and bills it for the RPC calls the request makes.
+/// Runs before authentication, so requests with a missing or wrong key count too.
+pub async fn rate_limit_ip(
+    State(limiter): State<Arc<RateLimiter>>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let client_ip = limiter.client_ip(&request);
+    let subjects: Vec<_> = client_ip.map(|ip| (Subject::Ip(ip), limiter.limits.per_ip)).into_iter().collect();
+    limiter.admit_or_reject(&subjects)?;
+    if let Some(ip) = client_ip {
+        request.extensions_mut().insert(ClientIp(ip));
+    }
+
+    let rpc_credits = Arc::new(AtomicU64::new(0));
+    let response = RPC_CREDITS.scope(rpc_credits.clone(), next.run(request)).await;
+    limiter.charge(&subjects, rpc_credits.load(Ordering::Relaxed));
+    Ok(response)
+}
+
+/// Enforces the caller's API key quota. Runs after authentication, which provides
+/// the [`Caller`], and inside [`rate_limit_ip`], which counts the RPC calls.
+pub async fn rate_limit_key(
+    State(limiter): State<Arc<RateLimiter>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(Caller(Some(api_key))) = request.extensions().get::<Caller>() else {
+        return Ok(next.run(request).await);
+    };
+    let quota = api_key.rate_limit.unwrap_or(limiter.limits.per_key);
+    let subjects = [(Subject::Key(api_key.id.clone()), quota)];
+    limiter.admit_or_reject(&subjects)?;
+
+    let rpc_credits = RPC_CREDITS.try_with(Arc::clone).ok();
+    let before = rpc_credits.as_ref().map_or(0, |credits| credits.load(Ordering::Relaxed));
+    let response = next.run(request).await;
+    if let Some(credits) = rpc_credits {
+        limiter.charge(&subjects, credits.load(Ordering::Relaxed) - before);
+    }
+    Ok(response)
+}
+
+// Admin endpoint: current usage per API key and client IP
+pub async fn get_usage(State(state): State<AppState>) -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "subjects": state.rate_limiter.usage() }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: &str) -> Subject {
+        Subject::Key(id.to_string())
+    }
+
+    #[test]
+    fn rejects_once_requests_are_exhausted() {
+        let limiter = RateLimiter::new(RateLimits::unlimited());
+        let quota = Quota { requests_per_minute: 2, rpc_credits_per_minute: 0 };
+        let subjects = [(key("script"), quota)];
+
+        assert!(limiter.admit(&subjects).is_ok());
+        assert!(limiter.admit(&subjects).is_ok());
+        let wait = limiter.admit(&subjects).unwrap_err();
+        // One request comes back every 30 seconds
+        assert!(wait <= Duration::from_secs(30) && wait > Duration::from_secs(29));
+
+        let usage = limiter.usage();
+        assert_eq!((usage[0].requests, usage[0].rejected), (2, 1));
+    }
+
+    #[test]
+    fn overdrawn_rpc_credits_block_the_next_request() {
+        let limiter = RateLimiter::new(RateLimits::unlimited());
+        let quota = Quota { requests_per_minute: 0, rpc_credits_per_minute: 60 };
+        let subjects = [(key("indexer"), quota)];
+
+        assert!(limiter.admit(&subjects).is_ok());
+        limiter.charge(&subjects, 90);
+        // 30 credits in debt at one credit per second
+        let wait = limiter.admit(&subjects).unwrap_err();
+        assert!(wait > Duration::from_secs(29) && wait <= Duration::from_secs(30));
+        assert_eq!(limiter.usage()[0].rpc_credits, 90);
+    }
+
+    #[test]
+    fn a_rejection_does_not_consume_other_quotas() {
+        let limiter = RateLimiter::new(RateLimits::unlimited());
+        let ip = Subject::Ip("10.0.0.1".parse().unwrap());
+        let roomy = Quota { requests_per_minute: 10, rpc_credits_per_minute: 0 };
+        let tight = Quota { requests_per_minute: 1, rpc_credits_per_minute: 0 };
+
+        assert!(limiter.admit(&[(key("a"), tight), (ip.clone(), roomy)]).is_ok());
+        assert!(limiter.admit(&[(key("a"), tight), (ip.clone(), roomy)]).is_err());
+        assert!(limiter.admit(&[(key("b"), tight), (ip, roomy)]).is_ok());
+
+        let usage = limiter.usage();
+        let ip_usage = usage.iter().find(|usage| usage.subject == "ip:10.0.0.1").unwrap();
+        assert_eq!(ip_usage.requests, 2);
+        assert_eq!(ip_usage.requests_remaining, Some(8));
+    }
+
+    #[tokio::test]
+    async fn limits_clients_before_authentication() {
+        use crate::{
+            auth::{require_scope, ApiKeys, RequireScope, Scope},
+            session::{SessionConfig, Sessions},
+            store::Store,
+        };
+        use axum::{body::Body, http::StatusCode, middleware, routing::get, Router};
+        use tower::ServiceExt;
+
+        let path = std::env::temp_dir().join(format!("api-keys-ratelimit-{}.json", std::process::id()));
+        std::fs::write(&path, serde_json::json!({ "keys": [] }).to_string()).unwrap();
+        let guard = RequireScope {
+            api_keys: Arc::new(ApiKeys::load(&path).unwrap()),
+            sessions: Arc::new(Sessions::new(Arc::new(Store::in_memory()), SessionConfig::default())),
+            scope: Scope::Read,
+        };
+        let per_ip = Quota { requests_per_minute: 1, rpc_credits_per_minute: 0 };
+        let limiter = Arc::new(RateLimiter::new(RateLimits { per_ip, ..RateLimits::unlimited() }));
+        let router = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .route_layer(middleware::from_fn_with_state(guard, require_scope))
+            .route_layer(middleware::from_fn_with_state(limiter.clone(), rate_limit_ip));
+        let guess = || {
+            let mut request = axum::http::Request::get("/").header("x-api-key", "guess").body(Body::empty()).unwrap();
+            request.extensions_mut().insert(ConnectInfo("10.0.0.9:4000".parse::<SocketAddr>().unwrap()));
+            request
+        };
+
+        assert_eq!(router.clone().oneshot(guess()).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(router.oneshot(guess()).await.unwrap().status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(limiter.usage()[0].rejected, 1);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn counts_rpc_calls_within_a_request_scope() {
+        let credits = Arc::new(AtomicU64::new(0));
+        RPC_CREDITS
+            .scope(credits.clone(), async {
+                record_rpc_call();
+                record_rpc_call();
+            })
+            .await;
+        // Outside a rate-limited request nothing is counted
+        record_rpc_call();
+        assert_eq!(credits.load(Ordering::Relaxed), 2);
+    }
+}
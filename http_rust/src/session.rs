@@ -0,0 +1,437 @@
+use axum::{extract::State, http::HeaderMap, response::Json};
+use chrono::{DateTime, Utc};
+use redb::TableDefinition;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{collections::HashSet, sync::Arc, time::Duration};
+use tracing::{info, warn};
+
+use crate::{
+    auth::{presented_key, ApiKey, Scope},
+    error::AppError,
+    store::{Store, Table},
+    types::*,
+    AppState,
+};
+
+/// Sign-in nonces until they are used or expire
+const NONCES: Table = TableDefinition::new("auth_nonces");
+/// Sessions by SHA-256 of their token, so the store never holds a usable token
+const SESSIONS: Table = TableDefinition::new("sessions");
+
+/// Tells session tokens apart from API keys
+const TOKEN_PREFIX: &str = "siws_";
+/// How long a nonce waits for its signed message
+const NONCE_TTL: Duration = Duration::from_secs(300);
+/// Nonces handed out and neither used nor expired; `/auth/nonce` needs no key, so this bounds the table
+const MAX_OUTSTANDING_NONCES: u64 = 10_000;
+/// Tolerated clock difference between the wallet and the server
+const CLOCK_SKEW: Duration = Duration::from_secs(60);
+const MESSAGE_HEADER: &str = " wants you to sign in with your Solana account:";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Nonce {
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Session {
+    address: String,
+    created_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+}
+
+/// What a wallet gets by signing in (`AUTH_DOMAIN`, `SESSION_SCOPES`, `SESSION_TTL_SECS`).
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    /// The `domain` sign-in messages must name; sign-in is disabled without one
+    pub domain: Option<String>,
+    pub scopes: Vec<Scope>,
+    pub ttl: Duration,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig { domain: None, scopes: vec![Scope::Read], ttl: Duration::from_secs(24 * 3600) }
+    }
+}
+
+/// Sign-In With Solana sessions. A session token works wherever an API key does,
+/// with the configured scopes and spending limited to the signed-in wallet.
+pub struct Sessions {
+    store: Arc<Store>,
+    config: SessionConfig,
+}
+
+impl Sessions {
+    pub fn new(store: Arc<Store>, config: SessionConfig) -> Self {
+        Sessions { store, config }
+    }
+
+    /// Resolves a session token presented like an API key. Returns `None` for
+    /// anything that isn't a session token, which is left to [`crate::auth::ApiKeys`].
+    pub fn authenticate(&self, headers: &HeaderMap) -> Result<Option<Arc<ApiKey>>, AppError> {
+        let Some((_, session)) = self.session(headers)? else { return Ok(None) };
+        let wallet = validate_pubkey(&session.address)?;
+        Ok(Some(Arc::new(ApiKey {
+            name: format!("wallet:{}", session.address),
+            id: format!("wallet:{}", session.address),
+            scopes: self.config.scopes.iter().copied().collect(),
+            allowed_wallets: Some(HashSet::from([wallet])),
+            expires_at: Some(session.expires_at),
+            rate_limit: None,
+        })))
+    }
+
+    // The session behind the presented token and its store key
+    fn session(&self, headers: &HeaderMap) -> Result<Option<(String, Session)>, AppError> {
+        let Some(token) = presented_key(headers).filter(|token| token.starts_with(TOKEN_PREFIX)) else {
+            return Ok(None);
+        };
+        let key = token_hash(token);
+        match self.store.get::<Session>(SESSIONS, &key)? {
+            Some(session) if session.expires_at > Utc::now() => Ok(Some((key, session))),
+            _ => Err(AppError::Unauthorized("Invalid or expired session".to_string())),
+        }
+    }
+
+    fn issue_nonce(&self) -> Result<(String, DateTime<Utc>), AppError> {
+        if self.store.len(NONCES)? >= MAX_OUTSTANDING_NONCES {
+            // Make room by dropping expired nonces before turning anyone away
+            let now = Utc::now();
+            let mut soonest = now + to_chrono(NONCE_TTL);
+            for (key, nonce) in self.store.list::<Nonce>(NONCES)? {
+                if nonce.expires_at <= now {
+                    self.store.remove(NONCES, &key)?;
+                } else {
+                    soonest = soonest.min(nonce.expires_at);
+                }
+            }
+            if self.store.len(NONCES)? >= MAX_OUTSTANDING_NONCES {
+                return Err(AppError::RateLimited {
+                    message: "Too many sign-ins in progress; try again shortly".to_string(),
+                    retry_after_secs: (soonest - now).num_seconds().max(1) as u64,
+                });
+            }
+        }
+        let nonce = random_hex(16);
+        let expires_at = Utc::now() + to_chrono(NONCE_TTL);
+        self.store.put(NONCES, &nonce, &Nonce { expires_at })?;
+        Ok((nonce, expires_at))
+    }
+
+    /// Checks a signed sign-in message, uses up its nonce and opens a session.
+    fn sign_in(&self, sign_in_req: &SignInRequest) -> Result<(String, Session), AppError> {
+        let domain = self.domain()?;
+        let message = SignInMessage::parse(&sign_in_req.message).map_err(AppError::BadRequest)?;
+        if message.domain != domain {
+            return Err(AppError::Unauthorized(format!("Message is for {}, not {}", message.domain, domain)));
+        }
+        let wallet = validate_pubkey(&message.address)?;
+        let signature = decode_signature(&sign_in_req.signature)?;
+        if !signature.verify(wallet.as_ref(), sign_in_req.message.as_bytes()) {
+            return Err(AppError::Unauthorized(format!("Signature does not match the message and {}", wallet)));
+        }
+
+        let now = Utc::now();
+        let skew = to_chrono(CLOCK_SKEW);
+        if message.issued_at > now + skew {
+            return Err(AppError::Unauthorized("Message is issued in the future".to_string()));
+        }
+        if message.not_before.is_some_and(|not_before| not_before > now + skew) {
+            return Err(AppError::Unauthorized("Message is not valid yet".to_string()));
+        }
+        if message.expiration_time.is_some_and(|expiration| expiration <= now) {
+            return Err(AppError::Unauthorized("Message has expired".to_string()));
+        }
+
+        // Checked and removed in one transaction so a nonce can't be used twice
+        self.store.write(|txn| match txn.get::<Nonce>(NONCES, &message.nonce)? {
+            Some(nonce) if nonce.expires_at > now => txn.remove(NONCES, &message.nonce).map(|_| ()),
+            _ => Err(AppError::Unauthorized("Unknown, used or expired nonce".to_string())),
+        })?;
+
+        let token = format!("{}{}", TOKEN_PREFIX, random_hex(32));
+        let expires_at = now + to_chrono(self.config.ttl);
+        let session = Session { address: message.address, created_at: now, expires_at };
+        self.store.put(SESSIONS, &token_hash(&token), &session)?;
+        Ok((token, session))
+    }
+
+    // Never the request's Host, which the client controls
+    fn domain(&self) -> Result<&str, AppError> {
+        self.config
+            .domain
+            .as_deref()
+            .ok_or_else(|| AppError::NotFound("Sign-in is disabled; set AUTH_DOMAIN to enable it".to_string()))
+    }
+
+    fn response(&self, token: Option<String>, session: Session) -> SessionResponse {
+        SessionResponse {
+            token,
+            address: session.address,
+            scopes: self.config.scopes.clone(),
+            expires_at: session.expires_at.to_rfc3339(),
+        }
+    }
+}
+
+/// The fields of a Sign-In With Solana message that the server checks:
+///
+/// ```text
+/// {domain} wants you to sign in with your Solana account:
+/// {address}
+///
+/// {statement}
+///
+/// URI: ...
+/// Nonce: ...
+/// Issued At: ...
+/// Expiration Time: ...
+/// ```
+#[derive(Debug)]
+struct SignInMessage {
+    domain: String,
+    address: String,
+    nonce: String,
+    issued_at: DateTime<Utc>,
+    expiration_time: Option<DateTime<Utc>>,
+    not_before: Option<DateTime<Utc>>,
+}
+
+impl SignInMessage {
+    fn parse(message: &str) -> Result<Self, String> {
+        let mut lines = message.lines();
+        let domain = lines
+            .next()
+            .and_then(|line| line.strip_suffix(MESSAGE_HEADER))
+            .ok_or("Not a Sign-In With Solana message")?;
+        let address = lines.next().ok_or("Message has no address")?;
+
+        // The statement is a single optional line, so the fields follow the last blank line
+        let fields = message.rsplit_once("\n\n").map_or("", |(_, fields)| fields);
+        let (mut nonce, mut issued_at, mut expiration_time, mut not_before) = (None, None, None, None);
+        for line in fields.lines() {
+            if line == "Resources:" || line.starts_with("- ") {
+                continue;
+            }
+            let (name, value) = line.split_once(": ").ok_or_else(|| format!("Malformed message line: {}", line))?;
+            let slot = match name {
+                "Nonce" => {
+                    nonce = Some(value.to_string());
+                    continue;
+                }
+                "Issued At" => &mut issued_at,
+                "Expiration Time" => &mut expiration_time,
+                "Not Before" => &mut not_before,
+                _ => continue,
+            };
+            let time = DateTime::parse_from_rfc3339(value).map_err(|_| format!("{} is not an RFC 3339 time", name))?;
+            *slot = Some(time.with_timezone(&Utc));
+        }
+
+        Ok(SignInMessage {
+            domain: domain.to_string(),
+            address: address.to_string(),
+            nonce: nonce.ok_or("Message has no Nonce")?,
+            issued_at: issued_at.ok_or("Message has no Issued At")?,
+            expiration_time,
+            not_before,
+        })
+    }
+}
+
+fn token_hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token))
+}
+
+fn to_chrono(duration: Duration) -> chrono::Duration {
+    chrono::Duration::from_std(duration).expect("session durations fit in chrono::Duration")
+}
+
+/// Starts a sign-in: the nonce to put in the message, and the domain it must name.
+pub async fn get_nonce(State(state): State<AppState>) -> Result<Json<NonceResponse>, AppError> {
+    let domain = state.sessions.domain()?.to_string();
+    let (nonce, expires_at) = state.sessions.issue_nonce()?;
+    Ok(Json(NonceResponse { nonce, domain, expires_at: expires_at.to_rfc3339() }))
+}
+
+pub async fn verify(
+    State(state): State<AppState>,
+    Json(sign_in_req): Json<SignInRequest>,
+) -> Result<Json<SessionResponse>, AppError> {
+    let (token, session) = state.sessions.sign_in(&sign_in_req)?;
+    info!("Signed in {} until {}", session.address, session.expires_at);
+    Ok(Json(state.sessions.response(Some(token), session)))
+}
+
+pub async fn get_session(State(state): State<AppState>, headers: HeaderMap) -> Result<Json<SessionResponse>, AppError> {
+    let (_, session) = state
+        .sessions
+        .session(&headers)?
+        .ok_or_else(|| AppError::Unauthorized("Missing session token".to_string()))?;
+    Ok(Json(state.sessions.response(None, session)))
+}
+
+/// Signs out: the token stops working immediately.
+pub async fn delete_session(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let (key, session) = state
+        .sessions
+        .session(&headers)?
+        .ok_or_else(|| AppError::Unauthorized("Missing session token".to_string()))?;
+    state.store.remove(SESSIONS, &key)?;
+    info!("Signed out {}", session.address);
+    Ok(Json(serde_json::json!({ "address": session.address, "revoked": true })))
+}
+
+/// Forgets expired nonces and sessions so the tables don't grow without bound.
+pub async fn prune(store: Arc<Store>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let now = Utc::now();
+        let result = store
+            .list::<Nonce>(NONCES)
+            .and_then(|nonces| {
+                for (key, nonce) in nonces {
+                    if nonce.expires_at <= now {
+                        store.remove(NONCES, &key)?;
+                    }
+                }
+                store.list::<Session>(SESSIONS)
+            })
+            .and_then(|sessions| {
+                for (key, session) in sessions {
+                    if session.expires_at <= now {
+                        store.remove(SESSIONS, &key)?;
+                    }
+                }
+                Ok(())
+            });
+        if let Err(err) = result {
+            warn!("Session pruning failed: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{header::AUTHORIZATION, HeaderValue};
+    use base64::{prelude::BASE64_STANDARD, Engine};
+    use solana_sdk::{
+        pubkey::Pubkey,
+        signature::{Keypair, Signer},
+    };
+
+    fn message(domain: &str, address: &str, nonce: &str, expiration: DateTime<Utc>) -> String {
+        format!(
+            "{}{}\n{}\n\nSign in to the dashboard: read-only\n\nURI: https://{}\nVersion: 1\nChain ID: devnet\n\
+             Nonce: {}\nIssued At: {}\nExpiration Time: {}\nResources:\n- https://{}/terms",
+            domain,
+            MESSAGE_HEADER,
+            address,
+            domain,
+            nonce,
+            Utc::now().to_rfc3339(),
+            expiration.to_rfc3339(),
+            domain
+        )
+    }
+
+    fn signed(keypair: &Keypair, message: String) -> SignInRequest {
+        let signature = keypair.sign_message(message.as_bytes());
+        SignInRequest { message, signature: signature.to_string() }
+    }
+
+    fn sessions() -> Sessions {
+        let config = SessionConfig { domain: Some("app.example".to_string()), ..SessionConfig::default() };
+        Sessions::new(Arc::new(Store::in_memory()), config)
+    }
+
+    #[test]
+    fn signs_in_once_per_nonce_and_authenticates_the_session() {
+        let sessions = sessions();
+        let keypair = Keypair::new();
+        let address = keypair.pubkey().to_string();
+        let later = Utc::now() + chrono::Duration::minutes(10);
+
+        let (nonce, _) = sessions.issue_nonce().unwrap();
+        let wrong_domain = signed(&keypair, message("evil.example", &address, &nonce, later));
+        let err = sessions.sign_in(&wrong_domain).unwrap_err();
+        assert_eq!(err.to_string(), "Unauthorized: Message is for evil.example, not app.example");
+        let request = signed(&keypair, message("app.example", &address, &nonce, later));
+        let stranger = Keypair::new().sign_message(request.message.as_bytes());
+        let forged = SignInRequest { signature: stranger.to_string(), ..request };
+        assert!(sessions.sign_in(&forged).is_err());
+
+        let signature = keypair.sign_message(forged.message.as_bytes());
+        let request = SignInRequest { signature: BASE64_STANDARD.encode(signature), ..forged };
+        let (token, session) = sessions.sign_in(&request).unwrap();
+        assert_eq!(session.address, address);
+        let reused = sessions.sign_in(&request).unwrap_err();
+        assert_eq!(reused.to_string(), "Unauthorized: Unknown, used or expired nonce");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_str(&format!("Bearer {}", token)).unwrap());
+        let api_key = sessions.authenticate(&headers).unwrap().unwrap();
+        assert_eq!(api_key.name, format!("wallet:{}", address));
+        assert!(api_key.has_scope(Scope::Read) && !api_key.has_scope(Scope::Transfer));
+        assert_eq!(api_key.allowed_wallets, Some(HashSet::from([keypair.pubkey()])));
+
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer siws_unknown"));
+        assert!(sessions.authenticate(&headers).is_err());
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer an-api-key"));
+        assert!(sessions.authenticate(&headers).unwrap().is_none());
+
+        let (nonce, _) = sessions.issue_nonce().unwrap();
+        let earlier = Utc::now() - chrono::Duration::seconds(1);
+        let expired = signed(&keypair, message("app.example", &address, &nonce, earlier));
+        let err = sessions.sign_in(&expired).unwrap_err();
+        assert_eq!(err.to_string(), "Unauthorized: Message has expired");
+    }
+
+    #[test]
+    fn refuses_sign_in_without_a_domain_and_caps_outstanding_nonces() {
+        let disabled = Sessions::new(Arc::new(Store::in_memory()), SessionConfig::default());
+        assert!(matches!(disabled.domain(), Err(AppError::NotFound(_))));
+
+        let sessions = sessions();
+        let expires_at = Utc::now() + to_chrono(NONCE_TTL);
+        sessions
+            .store
+            .write(|txn| {
+                for index in 0..MAX_OUTSTANDING_NONCES {
+                    txn.put(NONCES, &index.to_string(), &Nonce { expires_at })?;
+                }
+                Ok(())
+            })
+            .unwrap();
+        assert!(matches!(sessions.issue_nonce(), Err(AppError::RateLimited { .. })));
+
+        // Expired nonces make room again
+        sessions.store.put(NONCES, "0", &Nonce { expires_at: Utc::now() - chrono::Duration::seconds(1) }).unwrap();
+        assert!(sessions.issue_nonce().is_ok());
+    }
+
+    #[test]
+    fn parses_messages_with_and_without_a_statement() {
+        let address = Pubkey::new_unique().to_string();
+        let parsed = SignInMessage::parse(&format!(
+            "localhost:8080{}\n{}\n\nNonce: abc12345\nIssued At: 2026-01-01T00:00:00Z",
+            MESSAGE_HEADER, address
+        ))
+        .unwrap();
+        assert_eq!(parsed.domain, "localhost:8080");
+        assert_eq!(parsed.address, address);
+        assert_eq!(parsed.nonce, "abc12345");
+        assert_eq!(parsed.expiration_time, None);
+
+        assert!(SignInMessage::parse("hello").is_err());
+        let missing_nonce = format!("a{}\n{}\n\nIssued At: 2026-01-01T00:00:00Z", MESSAGE_HEADER, address);
+        assert_eq!(SignInMessage::parse(&missing_nonce).unwrap_err(), "Message has no Nonce");
+    }
+}
@@ -0,0 +1,145 @@
+use redb::{Database, ReadableTable, ReadableTableMetadata, TableDefinition, TableError};
+use serde::{de::DeserializeOwned, Serialize};
+use std::path::Path;
+
+use crate::error::AppError;
+
+/// A table of JSON-encoded values keyed by string. Each feature declares its own.
+pub type Table = TableDefinition<'static, &'static str, &'static [u8]>;
+
+/// Embedded key-value store for state that must survive restarts (`STORE_PATH`).
+pub struct Store {
+    db: Database,
+}
+
+impl Store {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .map_err(|err| format!("Failed to create {}: {}", parent.display(), err))?;
+        }
+        let db = Database::create(path).map_err(|err| format!("Failed to open store {}: {}", path.display(), err))?;
+        Ok(Store { db })
+    }
+
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        let db = redb::Builder::new()
+            .create_with_backend(redb::backends::InMemoryBackend::new())
+            .unwrap();
+        Store { db }
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, table: Table, key: &str) -> Result<Option<T>, AppError> {
+        let txn = self.db.begin_read().map_err(store_error)?;
+        let table = match txn.open_table(table) {
+            Ok(table) => table,
+            Err(TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(err) => return Err(store_error(err)),
+        };
+        let value = table.get(key).map_err(store_error)?;
+        value.map(|value| decode(value.value())).transpose()
+    }
+
+    /// Every entry of `table`, in key order
+    pub fn list<T: DeserializeOwned>(&self, table: Table) -> Result<Vec<(String, T)>, AppError> {
+        let txn = self.db.begin_read().map_err(store_error)?;
+        let table = match txn.open_table(table) {
+            Ok(table) => table,
+            Err(TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(err) => return Err(store_error(err)),
+        };
+        let mut entries = Vec::new();
+        for entry in table.iter().map_err(store_error)? {
+            let (key, value) = entry.map_err(store_error)?;
+            entries.push((key.value().to_string(), decode(value.value())?));
+        }
+        Ok(entries)
+    }
+
+    /// Number of entries in `table`, without reading them
+    pub fn len(&self, table: Table) -> Result<u64, AppError> {
+        let txn = self.db.begin_read().map_err(store_error)?;
+        match txn.open_table(table) {
+            Ok(table) => table.len().map_err(store_error),
+            Err(TableError::TableDoesNotExist(_)) => Ok(0),
+            Err(err) => Err(store_error(err)),
+        }
+    }
+
+    pub fn put<T: Serialize>(&self, table: Table, key: &str, value: &T) -> Result<(), AppError> {
+        self.write(|txn| txn.put(table, key, value))
+    }
+
+    pub fn remove(&self, table: Table, key: &str) -> Result<bool, AppError> {
+        self.write(|txn| txn.remove(table, key))
+    }
+
+    /// Runs `f` in one write transaction; nothing is committed if it fails.
+    pub fn write<R>(&self, f: impl FnOnce(&WriteTxn) -> Result<R, AppError>) -> Result<R, AppError> {
+        let txn = WriteTxn(self.db.begin_write().map_err(store_error)?);
+        let result = f(&txn)?;
+        txn.0.commit().map_err(store_error)?;
+        Ok(result)
+    }
+}
+
+pub struct WriteTxn(redb::WriteTransaction);
+
+impl WriteTxn {
+    pub fn get<T: DeserializeOwned>(&self, table: Table, key: &str) -> Result<Option<T>, AppError> {
+        let table = self.0.open_table(table).map_err(store_error)?;
+        let value = table.get(key).map_err(store_error)?;
+        value.map(|value| decode(value.value())).transpose()
+    }
+
+    pub fn put<T: Serialize>(&self, table: Table, key: &str, value: &T) -> Result<(), AppError> {
+        let bytes = serde_json::to_vec(value).map_err(|err| AppError::Internal(format!("Store encode: {}", err)))?;
+        let mut table = self.0.open_table(table).map_err(store_error)?;
+        table.insert(key, bytes.as_slice()).map_err(store_error)?;
+        Ok(())
+    }
+
+    pub fn remove(&self, table: Table, key: &str) -> Result<bool, AppError> {
+        let mut table = self.0.open_table(table).map_err(store_error)?;
+        let removed = table.remove(key).map_err(store_error)?.is_some();
+        Ok(removed)
+    }
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, AppError> {
+    serde_json::from_slice(bytes).map_err(|err| AppError::Internal(format!("Store decode: {}", err)))
+}
+
+fn store_error(err: impl Into<redb::Error>) -> AppError {
+    AppError::Internal(format!("Store: {}", err.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ITEMS: Table = TableDefinition::new("items");
+
+    #[test]
+    fn round_trips_values_and_rolls_back_failed_writes() {
+        let store = Store::in_memory();
+        assert_eq!(store.get::<u64>(ITEMS, "a").unwrap(), None);
+        assert!(store.list::<u64>(ITEMS).unwrap().is_empty());
+
+        store.put(ITEMS, "b", &2u64).unwrap();
+        store.put(ITEMS, "a", &1u64).unwrap();
+        assert_eq!(store.list::<u64>(ITEMS).unwrap(), vec![("a".to_string(), 1), ("b".to_string(), 2)]);
+
+        let failed = store.write(|txn| {
+            txn.remove(ITEMS, "a")?;
+            Err::<(), _>(AppError::Internal("abort".to_string()))
+        });
+        assert!(failed.is_err());
+        assert_eq!(store.get::<u64>(ITEMS, "a").unwrap(), Some(1));
+
+        assert!(store.remove(ITEMS, "a").unwrap());
+        assert!(!store.remove(ITEMS, "a").unwrap());
+    }
+}
@@ -0,0 +1,481 @@
+use axum::{
+    extract::{Path, State},
+    response::Json,
+};
+use chrono::{DateTime, Utc};
+use redb::TableDefinition;
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_config::RpcSendTransactionConfig;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    signature::Signature,
+    transaction::{Transaction, TransactionError},
+};
+use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+use tracing::{info, warn};
+
+use crate::{
+    cluster::{Cluster, ClusterRpc},
+    error::AppError,
+    nonce,
+    store::{Store, Table},
+    types::*,
+    AppState,
+};
+
+/// Async-mode transactions by signature
+const TRACKED: Table = TableDefinition::new("tracked_transactions");
+/// Cluster of each tracked transaction without a final status, so polls skip finished ones
+const PENDING: Table = TableDefinition::new("pending_transactions");
+
+/// `getSignatureStatuses` accepts at most this many signatures per call
+const STATUS_BATCH: usize = 256;
+/// How long a transaction with a final status stays readable
+const RETENTION: Duration = Duration::from_secs(24 * 3600);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TrackedTransaction {
+    signature: String,
+    cluster: String,
+    status: TrackedStatus,
+    slot: Option<u64>,
+    error: Option<String>,
+    /// Unknown for client-signed transactions; expiry then falls back to `isBlockhashValid`
+    last_valid_block_height: Option<u64>,
+    broadcasts: u32,
+    history: Vec<StatusChange>,
+    /// Base64 wire transaction for rebroadcasting, dropped once the status is final
+    transaction: Option<String>,
+    updated_at: DateTime<Utc>,
+}
+
+impl TrackedTransaction {
+    fn set_status(&mut self, status: TrackedStatus, slot: Option<u64>, error: Option<String>) {
+        let now = Utc::now();
+        self.status = status;
+        self.slot = slot;
+        self.error = error;
+        self.updated_at = now;
+        self.history.push(StatusChange { status, slot, at: now.to_rfc3339() });
+        if status.is_final() {
+            self.transaction = None;
+        }
+    }
+}
+
+/// What one poll of the cluster means for a tracked transaction
+#[derive(Debug, PartialEq)]
+enum Step {
+    Advance(TrackedStatus, Option<u64>, Option<String>),
+    Rebroadcast,
+    Wait,
+}
+
+fn next_step(current: TrackedStatus, status: Option<&TransactionStatus>, expired: bool) -> Step {
+    let Some(status) = status else {
+        // A dropped transaction can't land once its blockhash is too old to be accepted
+        return if expired { Step::Advance(TrackedStatus::Expired, None, None) } else { Step::Rebroadcast };
+    };
+    let observed = observed_status(status);
+    if observed == current {
+        Step::Wait
+    } else {
+        Step::Advance(observed, Some(status.slot), status.err.as_ref().map(TransactionError::to_string))
+    }
+}
+
+fn observed_status(status: &TransactionStatus) -> TrackedStatus {
+    if status.err.is_some() {
+        return TrackedStatus::Failed;
+    }
+    match status.confirmation_status() {
+        TransactionConfirmationStatus::Processed => TrackedStatus::Processed,
+        TransactionConfirmationStatus::Confirmed => TrackedStatus::Confirmed,
+        TransactionConfirmationStatus::Finalized => TrackedStatus::Finalized,
+    }
+}
+
+/// Sends `transaction` once with preflight checks and starts tracking it.
+/// Errors from the send itself (bad signature, insufficient funds) go straight
+/// back to the caller; nothing is tracked for them.
+pub async fn submit(
+    store: &Store,
+    cluster: &Cluster,
+    transaction: &Transaction,
+    last_valid_block_height: Option<u64>,
+) -> Result<Signature, AppError> {
+    let signature = cluster.rpc_client.send_transaction(transaction).await?;
+    let now = Utc::now();
+    let record = TrackedTransaction {
+        signature: signature.to_string(),
+        cluster: cluster.name.clone(),
+        status: TrackedStatus::Sent,
+        slot: None,
+        error: None,
+        last_valid_block_height,
+        broadcasts: 1,
+        history: vec![StatusChange { status: TrackedStatus::Sent, slot: None, at: now.to_rfc3339() }],
+        transaction: Some(encode_transaction(transaction)?),
+        updated_at: now,
+    };
+    store.write(|txn| {
+        txn.put(TRACKED, &record.signature, &record)?;
+        txn.put(PENDING, &record.signature, &record.cluster)
+    })?;
+    info!("Tracking transaction {} on {}", signature, cluster.name);
+    Ok(signature)
+}
+
+/// Polls every pending async-mode transaction forever, recording status
+/// changes and rebroadcasting the ones that haven't landed yet.
+pub async fn track(state: AppState, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        if let Err(err) = track_once(&state).await {
+            warn!("Transaction tracking failed: {}", err);
+        }
+    }
+}
+
+async fn track_once(state: &AppState) -> Result<(), AppError> {
+    let mut pending: BTreeMap<String, Vec<TrackedTransaction>> = BTreeMap::new();
+    for (signature, cluster_name) in state.store.list::<String>(PENDING)? {
+        match state.store.get::<TrackedTransaction>(TRACKED, &signature)? {
+            Some(record) if !record.status.is_final() => pending.entry(cluster_name).or_default().push(record),
+            _ => {
+                state.store.remove(PENDING, &signature)?;
+            }
+        }
+    }
+
+    for (cluster_name, records) in pending {
+        let Some(cluster) = state.clusters.get(&cluster_name) else { continue };
+        if let Err(err) = poll_cluster(&state.store, cluster, records).await {
+            warn!("Transaction tracking on {} failed: {}", cluster_name, err);
+        }
+    }
+    Ok(())
+}
+
+async fn poll_cluster(store: &Store, cluster: &Cluster, records: Vec<TrackedTransaction>) -> Result<(), AppError> {
+    let block_height = cluster
+        .rpc_client
+        .get_block_height_with_commitment(CommitmentConfig::confirmed())
+        .await?;
+
+    let records: Vec<(Signature, TrackedTransaction)> = records
+        .into_iter()
+        .filter_map(|record| match parse_signature(&record.signature) {
+            Ok(signature) => Some((signature, record)),
+            Err(err) => {
+                warn!("Skipping tracked transaction {}: {}", record.signature, err);
+                None
+            }
+        })
+        .collect();
+
+    for chunk in records.chunks(STATUS_BATCH) {
+        let signatures: Vec<Signature> = chunk.iter().map(|(signature, _)| *signature).collect();
+        let statuses = cluster.rpc_client.get_signature_statuses(&signatures).await?.value;
+
+        // One bad record must not hold up the rest
+        for ((_, record), status) in chunk.iter().zip(statuses) {
+            if let Err(err) = poll_record(store, cluster, record.clone(), status, block_height).await {
+                warn!("Tracking transaction {} failed: {}", record.signature, err);
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn poll_record(
+    store: &Store,
+    cluster: &Cluster,
+    mut record: TrackedTransaction,
+    status: Option<TransactionStatus>,
+    block_height: u64,
+) -> Result<(), AppError> {
+    let expired = match status {
+        Some(_) => false,
+        None => is_expired(cluster, &record, block_height).await?,
+    };
+    match next_step(record.status, status.as_ref(), expired) {
+        Step::Wait => return Ok(()),
+        Step::Advance(status, slot, error) => {
+            info!("Transaction {} is {:?}", record.signature, status);
+            record.set_status(status, slot, error);
+        }
+        Step::Rebroadcast => {
+            let Some(encoded) = &record.transaction else { return Ok(()) };
+            let transaction = decode_transaction(encoded)?;
+            // Preflight already passed on the first send; a rebroadcast only needs to reach a leader
+            let config = RpcSendTransactionConfig {
+                skip_preflight: true,
+                max_retries: Some(0),
+                ..RpcSendTransactionConfig::default()
+            };
+            if let Err(err) = cluster.rpc_client.send_transaction_with_config(&transaction, config).await {
+                warn!("Rebroadcast of {} failed: {}", record.signature, err);
+            }
+            record.broadcasts += 1;
+            record.updated_at = Utc::now();
+        }
+    }
+    store.write(|txn| {
+        txn.put(TRACKED, &record.signature, &record)?;
+        if record.status.is_final() {
+            txn.remove(PENDING, &record.signature)?;
+        }
+        Ok(())
+    })
+}
+
+/// Forgets transactions whose final status is past the retention period, and indexes
+/// unfinished ones missing from the pending table, such as those tracked before it existed.
+pub async fn prune(store: Arc<Store>, interval: Duration) {
+    let retention = chrono::Duration::from_std(RETENTION).expect("retention fits in chrono::Duration");
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let now = Utc::now();
+        let result = store.list::<TrackedTransaction>(TRACKED).and_then(|records| {
+            for (signature, record) in records {
+                if !record.status.is_final() {
+                    store.put(PENDING, &signature, &record.cluster)?;
+                } else if now - record.updated_at > retention {
+                    store.remove(TRACKED, &signature)?;
+                }
+            }
+            Ok(())
+        });
+        if let Err(err) = result {
+            warn!("Tracked transaction pruning failed: {}", err);
+        }
+    }
+}
+
+async fn is_expired(cluster: &Cluster, record: &TrackedTransaction, block_height: u64) -> Result<bool, AppError> {
+    if let Some(last_valid_block_height) = record.last_valid_block_height {
+        return Ok(block_height > last_valid_block_height);
+    }
+    let Some(encoded) = &record.transaction else { return Ok(true) };
+    let transaction = decode_transaction(encoded)?;
+    // A durable nonce transaction stays valid until its nonce moves on. Landing moves it too,
+    // so a moved nonce only means expiry if the transaction still can't be found.
+    if let Some(current) = nonce::is_nonce_current(cluster, &transaction).await? {
+        if current {
+            return Ok(false);
+        }
+        let status = cluster.rpc_client.get_signature_statuses(&[transaction.signatures[0]]).await?.value;
+        return Ok(status.first().is_none_or(Option::is_none));
+    }
+    let valid = cluster
+        .rpc_client
+        .is_blockhash_valid(&transaction.message.recent_blockhash, CommitmentConfig::processed())
+        .await?;
+    Ok(!valid)
+}
+
+pub async fn get_transaction_status(
+    State(state): State<AppState>,
+    ClusterRpc(cluster): ClusterRpc,
+    Path(signature): Path<String>,
+) -> Result<Json<TransactionStatusResponse>, AppError> {
+    let sig = parse_signature(&signature)?;
+
+    let tracked: Option<TrackedTransaction> = state.store.get(TRACKED, &signature)?;
+    if let Some(record) = tracked.filter(|record| record.cluster == cluster.name) {
+        return Ok(Json(TransactionStatusResponse {
+            signature: record.signature,
+            tracked: true,
+            status: record.status,
+            slot: record.slot,
+            error: record.error,
+            broadcasts: record.broadcasts,
+            last_valid_block_height: record.last_valid_block_height,
+            history: record.history,
+        }));
+    }
+
+    // Not sent in async mode: report what the cluster knows, searching past the recent-status cache
+    let status = cluster
+        .rpc_client
+        .get_signature_statuses_with_history(&[sig])
+        .await?
+        .value
+        .pop()
+        .flatten()
+        .ok_or_else(|| AppError::NotFound(format!("Transaction not found: {}", signature)))?;
+    Ok(Json(TransactionStatusResponse {
+        signature,
+        tracked: false,
+        status: observed_status(&status),
+        slot: Some(status.slot),
+        error: status.err.as_ref().map(TransactionError::to_string),
+        broadcasts: 0,
+        last_valid_block_height: None,
+        history: Vec::new(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool::RpcPool;
+    use async_trait::async_trait;
+    use serde_json::{json, Value};
+    use solana_client::{
+        client_error::Result as ClientResult,
+        rpc_request::RpcRequest,
+        rpc_sender::{RpcSender, RpcTransportStats},
+    };
+    use solana_sdk::{hash::Hash, signature::Keypair, signer::Signer, system_instruction};
+    use std::sync::{Arc, Mutex};
+
+    /// A cluster whose block height and status for the one transaction under test are set by the test
+    #[derive(Clone, Default)]
+    struct Chain {
+        block_height: Arc<Mutex<u64>>,
+        status: Arc<Mutex<Value>>,
+        sends: Arc<Mutex<u32>>,
+        signature: Arc<Mutex<String>>,
+    }
+
+    #[async_trait]
+    impl RpcSender for Chain {
+        async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+            let context = json!({ "slot": 1 });
+            Ok(match request {
+                RpcRequest::GetVersion => json!({ "solana-core": "1.18.26" }),
+                RpcRequest::GetBlockHeight => json!(*self.block_height.lock().unwrap()),
+                RpcRequest::GetSignatureStatuses => {
+                    let count = params[0].as_array().map_or(1, Vec::len);
+                    json!({ "context": context, "value": vec![self.status.lock().unwrap().clone(); count] })
+                }
+                RpcRequest::SendTransaction => {
+                    *self.sends.lock().unwrap() += 1;
+                    json!(self.signature.lock().unwrap().clone())
+                }
+                other => panic!("unexpected request {:?}", other),
+            })
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            RpcTransportStats::default()
+        }
+
+        fn url(&self) -> String {
+            "chain".to_string()
+        }
+    }
+
+    fn status(slot: u64, confirmation: &str, err: Value) -> Value {
+        json!({ "slot": slot, "confirmations": null, "status": { "Ok": null }, "err": err, "confirmationStatus": confirmation })
+    }
+
+    fn tracked(store: &Store) -> Vec<TrackedTransaction> {
+        store.list::<TrackedTransaction>(TRACKED).unwrap().into_iter().map(|(_, record)| record).collect()
+    }
+
+    async fn poll(store: &Store, cluster: &Cluster) {
+        let pending: Vec<String> = store.list::<String>(PENDING).unwrap().into_iter().map(|(signature, _)| signature).collect();
+        let records = tracked(store).into_iter().filter(|record| pending.contains(&record.signature)).collect();
+        poll_cluster(store, cluster, records).await.unwrap();
+    }
+
+    fn chain_cluster() -> (Chain, Cluster) {
+        let chain = Chain::default();
+        let pool = Arc::new(RpcPool::from_senders(vec![("chain".to_string(), Box::new(chain.clone()))]));
+        (chain, Cluster::new("devnet".to_string(), pool, "ws://127.0.0.1:9".to_string()))
+    }
+
+    fn signed_transfer() -> Transaction {
+        let payer = Keypair::new();
+        let transfer = system_instruction::transfer(&payer.pubkey(), &Keypair::new().pubkey(), 1);
+        Transaction::new_signed_with_payer(&[transfer], Some(&payer.pubkey()), &[&payer], Hash::new_unique())
+    }
+
+    #[test]
+    fn steps_only_on_status_changes() {
+        let confirmed: TransactionStatus = serde_json::from_value(status(7, "confirmed", Value::Null)).unwrap();
+        assert_eq!(next_step(TrackedStatus::Sent, None, false), Step::Rebroadcast);
+        assert_eq!(next_step(TrackedStatus::Sent, None, true), Step::Advance(TrackedStatus::Expired, None, None));
+        assert_eq!(
+            next_step(TrackedStatus::Processed, Some(&confirmed), false),
+            Step::Advance(TrackedStatus::Confirmed, Some(7), None)
+        );
+        assert_eq!(next_step(TrackedStatus::Confirmed, Some(&confirmed), false), Step::Wait);
+
+        let failed: TransactionStatus =
+            serde_json::from_value(status(7, "processed", json!("AccountInUse"))).unwrap();
+        assert_eq!(
+            next_step(TrackedStatus::Sent, Some(&failed), false),
+            Step::Advance(TrackedStatus::Failed, Some(7), Some(TransactionError::AccountInUse.to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn rebroadcasts_until_finalized_or_expired() {
+        let (chain, cluster) = chain_cluster();
+        let store = Store::in_memory();
+
+        let transaction = signed_transfer();
+        *chain.signature.lock().unwrap() = transaction.signatures[0].to_string();
+        *chain.block_height.lock().unwrap() = 50;
+        submit(&store, &cluster, &transaction, Some(100)).await.unwrap();
+
+        // Not seen yet and still valid: sent again
+        poll(&store, &cluster).await;
+        assert_eq!(*chain.sends.lock().unwrap(), 2);
+        assert_eq!(tracked(&store)[0].broadcasts, 2);
+
+        *chain.status.lock().unwrap() = status(60, "confirmed", Value::Null);
+        poll(&store, &cluster).await;
+        poll(&store, &cluster).await;
+        *chain.status.lock().unwrap() = status(60, "finalized", Value::Null);
+        poll(&store, &cluster).await;
+        assert_eq!(*chain.sends.lock().unwrap(), 2);
+
+        let record = &tracked(&store)[0];
+        let history: Vec<_> = record.history.iter().map(|change| change.status).collect();
+        assert_eq!(history, [TrackedStatus::Sent, TrackedStatus::Confirmed, TrackedStatus::Finalized]);
+        assert_eq!(record.slot, Some(60));
+        assert!(record.transaction.is_none());
+        assert!(store.list::<String>(PENDING).unwrap().is_empty());
+
+        // A transaction that never lands expires once the chain passes its last valid height
+        store.remove(TRACKED, &record.signature).unwrap();
+        *chain.status.lock().unwrap() = Value::Null;
+        submit(&store, &cluster, &transaction, Some(100)).await.unwrap();
+        *chain.block_height.lock().unwrap() = 101;
+        poll(&store, &cluster).await;
+        assert_eq!(tracked(&store)[0].status, TrackedStatus::Expired);
+    }
+
+    #[tokio::test]
+    async fn skips_records_that_fail_to_poll() {
+        let (chain, cluster) = chain_cluster();
+        let store = Store::in_memory();
+        *chain.status.lock().unwrap() = Value::Null;
+        *chain.block_height.lock().unwrap() = 101;
+
+        let transaction = signed_transfer();
+        *chain.signature.lock().unwrap() = transaction.signatures[0].to_string();
+        submit(&store, &cluster, &transaction, Some(100)).await.unwrap();
+
+        // Without a last valid height, expiry needs the stored transaction, which won't decode
+        let mut broken = tracked(&store)[0].clone();
+        broken.signature = Signature::new_unique().to_string();
+        broken.last_valid_block_height = None;
+        broken.transaction = Some("not a transaction".to_string());
+        store.put(TRACKED, &broken.signature, &broken).unwrap();
+        store.put(PENDING, &broken.signature, &broken.cluster).unwrap();
+
+        poll(&store, &cluster).await;
+        let status = |signature: &str| store.get::<TrackedTransaction>(TRACKED, signature).unwrap().unwrap().status;
+        assert_eq!(status(&transaction.signatures[0].to_string()), TrackedStatus::Expired);
+        assert_eq!(status(&broken.signature), TrackedStatus::Sent);
+    }
+}
@@ -1,24 +1,301 @@
+use base64::{prelude::BASE64_STANDARD, Engine};
 use serde::{Deserialize, Serialize};
-use solana_sdk::pubkey::Pubkey;
-use std::str::FromStr;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::{Transaction, TransactionError, TransactionVersion},
+};
+use solana_transaction_status::TransactionConfirmationStatus;
+use std::{collections::HashMap, str::FromStr};
 
-use crate::error::AppError;
+use crate::{auth::Scope, error::AppError, fees::FeeStrategy};
+
+/// Number of fractional digits in a SOL amount (1 SOL = 10^9 lamports)
+pub const SOL_DECIMALS: u8 = 9;
 
 // Request types
+
+/// A transfer amount given either as a decimal SOL string (`"0.3"`) or as
+/// integer `lamports`. Exactly one of the two must be set.
+#[derive(Debug, Deserialize)]
+pub struct AmountSpec {
+    #[serde(default)]
+    pub amount: Option<String>,
+    #[serde(default)]
+    pub lamports: Option<u64>,
+}
+
+impl AmountSpec {
+    pub fn to_lamports(&self) -> Result<u64, AppError> {
+        resolve_amount(self.amount.as_deref(), self.lamports, SOL_DECIMALS, "lamports")
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct TransferRequest {
     pub from: String,
     pub to: String,
-    pub amount: f64,
+    #[serde(flatten)]
+    pub amount: AmountSpec,
+    #[serde(default)]
+    pub private_key: Option<String>,
+    #[serde(default)]
+    pub fee_strategy: FeeStrategy,
+    /// Use a durable nonce instead of a recent blockhash
+    #[serde(default)]
+    pub nonce: Option<NonceOption>,
+    /// Return once the transaction is sent instead of waiting for confirmation
+    #[serde(default, rename = "async")]
+    pub async_mode: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PrepareTransferRequest {
+    pub from: String,
+    pub to: String,
+    #[serde(flatten)]
+    pub amount: AmountSpec,
+    #[serde(default)]
+    pub fee_strategy: FeeStrategy,
+    /// Use a durable nonce instead of a recent blockhash
+    #[serde(default)]
+    pub nonce: Option<NonceOption>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenTransferRequest {
+    /// Owner wallet of the source token account
+    pub from: String,
+    /// Recipient wallet; its associated token account is created if missing
+    pub to: String,
+    pub mint: String,
+    /// Decimal amount in token units (`"1.5"`); mutually exclusive with `raw_amount`
+    #[serde(default)]
+    pub amount: Option<String>,
+    /// Amount in the mint's base units
+    #[serde(default)]
+    pub raw_amount: Option<u64>,
+    #[serde(default)]
+    pub private_key: Option<String>,
+    #[serde(default)]
+    pub fee_strategy: FeeStrategy,
+    /// Use a durable nonce instead of a recent blockhash
+    #[serde(default)]
+    pub nonce: Option<NonceOption>,
+    #[serde(default, rename = "async")]
+    pub async_mode: bool,
+}
+
+impl TokenTransferRequest {
+    pub fn to_base_units(&self, decimals: u8) -> Result<u64, AppError> {
+        resolve_amount(self.amount.as_deref(), self.raw_amount, decimals, "raw_amount")
+    }
+}
+
+/// `"nonce": { "account": "...", "authority": "..." }` on a transaction-building request.
+/// The authority defaults to the fee payer.
+#[derive(Debug, Deserialize)]
+pub struct NonceOption {
+    pub account: String,
+    #[serde(default)]
+    pub authority: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateNonceRequest {
+    /// Funds the nonce account and pays the fee
+    pub payer: String,
+    /// Defaults to the payer
+    #[serde(default)]
+    pub authority: Option<String>,
+    /// Defaults to the rent-exempt minimum
+    #[serde(default)]
+    pub lamports: Option<u64>,
+    #[serde(default)]
+    pub private_key: Option<String>,
+    #[serde(default)]
+    pub fee_strategy: FeeStrategy,
+}
+
+/// Body of `POST /nonce/{address}/advance`; the authority pays the fee
+#[derive(Debug, Deserialize)]
+pub struct AdvanceNonceRequest {
+    /// The nonce authority's key
+    #[serde(default)]
+    pub private_key: Option<String>,
+    #[serde(default)]
+    pub fee_strategy: FeeStrategy,
+}
+
+/// Body of `POST /nonce/{address}/withdraw`; the authority pays the fee
+#[derive(Debug, Deserialize)]
+pub struct WithdrawNonceRequest {
+    pub to: String,
+    #[serde(flatten)]
+    pub amount: AmountSpec,
+    /// The nonce authority's key
     #[serde(default)]
     pub private_key: Option<String>,
+    #[serde(default)]
+    pub fee_strategy: FeeStrategy,
+}
+
+/// `?commitment=processed|confirmed|finalized`; defaults to `confirmed`.
+#[derive(Debug, Default, Deserialize)]
+pub struct CommitmentQuery {
+    #[serde(default)]
+    pub commitment: Option<String>,
+}
+
+impl CommitmentQuery {
+    pub fn commitment(&self) -> Result<CommitmentConfig, AppError> {
+        parse_commitment(self.commitment.as_deref())
+    }
+    
+    /// For RPC methods that reject `processed` (transaction and signature lookups)
+    pub fn confirmed_commitment(&self) -> Result<CommitmentConfig, AppError> {
+        parse_confirmed_commitment(self.commitment.as_deref())
+    }
+}
+
+/// `?commitment=` plus `?data=true` to include raw account data
+#[derive(Debug, Default, Deserialize)]
+pub struct AccountQuery {
+    #[serde(default)]
+    pub commitment: Option<String>,
+    #[serde(default)]
+    pub data: bool,
+}
+
+impl AccountQuery {
+    pub fn commitment(&self) -> Result<CommitmentConfig, AppError> {
+        parse_commitment(self.commitment.as_deref())
+    }
+}
+
+/// Body of `POST /balances` and `POST /accounts`
+#[derive(Debug, Deserialize)]
+pub struct BatchRequest {
+    pub addresses: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    /// Start searching backwards from this signature (exclusive)
+    #[serde(default)]
+    pub before: Option<String>,
+    /// Stop once this signature is reached (exclusive)
+    #[serde(default)]
+    pub until: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// Fetch each transaction to report fees and SOL balance changes
+    #[serde(default)]
+    pub expand: bool,
+    /// `confirmed` (default) or `finalized`
+    #[serde(default)]
+    pub commitment: Option<String>,
+}
+
+/// Either a base64 transaction or a transfer intent to be built server-side.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum SimulateRequest {
+    Transaction { transaction: String },
+    Transfer(PrepareTransferRequest),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BuildInstructionRequest {
+    /// Instruction arguments by IDL name, as JSON
+    #[serde(default)]
+    pub args: serde_json::Map<String, serde_json::Value>,
+    /// Account addresses by IDL name; fixed-address and PDA accounts can be left out
+    #[serde(default)]
+    pub accounts: HashMap<String, String>,
+    /// Also return an unsigned transaction paid for by this wallet
+    #[serde(default)]
+    pub fee_payer: Option<String>,
+    #[serde(default)]
+    pub fee_strategy: FeeStrategy,
+    /// Use a durable nonce instead of a recent blockhash
+    #[serde(default)]
+    pub nonce: Option<NonceOption>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PdaRequest {
+    pub program_id: String,
+    pub seeds: Vec<PdaSeed>,
+}
+
+/// A typed seed: `{ "type": "string", "value": "prereqs" }`
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum PdaSeed {
+    /// UTF-8 bytes
+    String(String),
+    /// The 32 bytes of a base58 public key
+    Pubkey(String),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    /// Raw bytes, optionally `0x`-prefixed
+    Hex(String),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SignInRequest {
+    /// The Sign-In With Solana message exactly as the wallet signed it
+    pub message: String,
+    /// ed25519 signature over the message, base58 or base64
+    pub signature: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyMessageRequest {
+    pub pubkey: String,
+    pub message: String,
+    #[serde(default)]
+    pub encoding: MessageEncoding,
+    /// base58 or base64
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageEncoding {
+    #[default]
+    Utf8,
+    Base64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubmitTransactionRequest {
+    /// Base64-encoded, bincode-serialized signed transaction
+    pub transaction: String,
+    #[serde(default, rename = "async")]
+    pub async_mode: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookRequest {
+    /// http(s) URL that receives a POST per new transaction
+    pub url: String,
+    pub addresses: Vec<String>,
+    /// HMAC key for the payload signature; generated when omitted
+    #[serde(default)]
+    pub secret: Option<String>,
 }
 
 // Response types
 #[derive(Debug, Serialize)]
 pub struct BalanceResponse {
     pub address: String,
-    pub balance: f64,
+    /// Exact SOL balance as a decimal string
+    pub balance: String,
     pub lamports: u64,
 }
 
@@ -29,6 +306,78 @@ pub struct AccountInfoResponse {
     pub owner: String,
     pub executable: bool,
     pub rent_epoch: u64,
+    pub data_len: usize,
+    /// Raw account data, base64-encoded; only with `?data=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    /// Whether `data` was cut short of `data_len` bytes, as batch lookups do
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_truncated: Option<bool>,
+    /// Decoded data for known account layouts
+    pub parsed: Option<ParsedAccountData>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ParsedAccountData {
+    /// Parser that recognised the data, e.g. `spl-token-2022`, `stake`, `mpl-token-metadata`
+    pub program: String,
+    /// `type` and `info`, as in the RPC's `jsonParsed` account encoding
+    #[serde(flatten)]
+    pub parsed: serde_json::Value,
+}
+
+/// One entry per requested address, in request order
+#[derive(Debug, Serialize)]
+pub struct BatchResponse<T> {
+    pub results: Vec<BatchResult<T>>,
+}
+
+/// Either the same object the single-address endpoint returns, or an error
+/// for just that address
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum BatchResult<T> {
+    Ok(T),
+    Err(BatchError),
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchError {
+    pub address: String,
+    pub error: String,
+    pub message: String,
+}
+
+impl BatchError {
+    /// Same `error`/`message` pair the single-address endpoint would answer with
+    pub fn new(address: String, err: AppError) -> Self {
+        BatchError {
+            address,
+            error: err.label().to_string(),
+            message: err.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenAccountResponse {
+    pub address: String,
+    pub mint: String,
+    /// Token program that owns the account (SPL Token or Token-2022)
+    pub program: String,
+    /// Raw base-unit amount
+    pub amount: String,
+    pub decimals: u8,
+    pub ui_amount: String,
+    pub frozen: bool,
+    /// Whether this is the owner's canonical associated token account for the mint
+    pub is_associated: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenPortfolioResponse {
+    pub owner: String,
+    pub accounts: Vec<TokenAccountResponse>,
 }
 
 #[derive(Debug, Serialize)]
@@ -38,14 +387,339 @@ pub struct TransferResponse {
     pub message: String,
 }
 
+#[derive(Debug, Serialize)]
+pub struct PreparedTransactionResponse {
+    /// Base64-encoded, bincode-serialized unsigned transaction
+    pub transaction: String,
+    pub blockhash: String,
+    pub fee_payer: String,
+    /// Absent for durable nonce transactions, which don't expire
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_valid_block_height: Option<u64>,
+    /// Nonce account whose advance instruction comes first
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce_account: Option<String>,
+    pub compute_unit_limit: u32,
+    /// Priority fee in micro-lamports per compute unit; 0 when none was set
+    pub compute_unit_price: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NonceAccountResponse {
+    pub address: String,
+    pub authority: String,
+    /// The stored durable nonce, used as the transaction's blockhash
+    pub nonce: String,
+    pub lamports_per_signature: u64,
+    pub lamports: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreatedNonceResponse {
+    pub address: String,
+    pub authority: String,
+    pub lamports: u64,
+    pub signature: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BuiltInstructionResponse {
+    pub program_id: String,
+    pub instruction: String,
+    pub accounts: Vec<BuiltInstructionAccount>,
+    /// Discriminator followed by the borsh-encoded args, base64-encoded
+    pub data: String,
+    /// Only with `fee_payer`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction: Option<PreparedTransactionResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BuiltInstructionAccount {
+    /// Name in the IDL
+    pub name: String,
+    pub pubkey: String,
+    pub signer: bool,
+    pub writable: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PdaResponse {
+    pub address: String,
+    pub bump: u8,
+    pub program_id: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AddressClassification {
+    pub address: String,
+    pub kind: AddressKind,
+    /// On the ed25519 curve, so some private key may sign for it
+    pub on_curve: bool,
+    /// Name of a well-known program or one with a registered IDL
+    pub program: Option<String>,
+    pub sysvar: Option<String>,
+    pub exists: bool,
+    pub owner: Option<String>,
+    pub executable: bool,
+    /// Off the curve and not a known program or sysvar, so most likely derived by a program
+    pub likely_pda: bool,
+}
+
+/// Best guess at what an address is, from the most to the least specific
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressKind {
+    Sysvar,
+    Program,
+    Pda,
+    /// On the curve with no account, or a system-owned one
+    Wallet,
+    /// On the curve and owned by a program, like a mint created from a keypair
+    Account,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NonceResponse {
+    pub nonce: String,
+    /// What the message's first line must name
+    pub domain: String,
+    pub expires_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionResponse {
+    /// Only returned when the session is created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    pub address: String,
+    pub scopes: Vec<Scope>,
+    pub expires_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VerifyMessageResponse {
+    pub pubkey: String,
+    pub valid: bool,
+    /// How the message was signed; `null` when neither format matched
+    pub format: Option<SignedMessageFormat>,
+    /// Header of the off-chain message that matched
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offchain: Option<OffchainHeader>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignedMessageFormat {
+    /// The message bytes themselves, as wallet `signMessage` signs them
+    Raw,
+    /// Wrapped in the Solana off-chain message header, as Ledger and `solana sign-offchain-message` sign
+    Offchain,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OffchainHeader {
+    pub version: u8,
+    /// `restricted_ascii`, `limited_utf8` or `extended_utf8`
+    pub format: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistoryEntry {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub success: bool,
+    pub error: Option<String>,
+    pub memo: Option<String>,
+    pub confirmation_status: Option<TransactionConfirmationStatus>,
+    // Only populated with `expand=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee: Option<u64>,
+    /// Signed exact SOL change for the queried address
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance_change: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance_change_lamports: Option<i64>,
+    /// Why `fee` and `balance_change` are missing from an expanded entry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expand_error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistoryResponse {
+    pub address: String,
+    pub transactions: Vec<HistoryEntry>,
+    /// Pass as `before` to fetch the next page; absent on the last page
+    pub next_before: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimulationResponse {
+    pub success: bool,
+    pub error: Option<SimulationError>,
+    pub logs: Vec<String>,
+    pub units_consumed: Option<u64>,
+    /// Fee in lamports from `getFeeForMessage`
+    pub fee: u64,
+    pub fee_sol: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimulationError {
+    pub message: String,
+    /// Index of the failing instruction, for instruction errors
+    pub instruction_index: Option<u8>,
+    /// The node's `TransactionError`, as returned by the RPC
+    pub details: TransactionError,
+}
+
+impl From<TransactionError> for SimulationError {
+    fn from(err: TransactionError) -> Self {
+        let instruction_index = match &err {
+            TransactionError::InstructionError(index, _) => Some(*index),
+            _ => None,
+        };
+        SimulationError {
+            message: err.to_string(),
+            instruction_index,
+            details: err,
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct TransactionResponse {
     pub signature: String,
     pub slot: u64,
     pub block_time: Option<i64>,
+    pub version: Option<TransactionVersion>,
     pub success: bool,
+    pub error: Option<String>,
     pub fee: Option<u64>,
+    pub compute_units_consumed: Option<u64>,
+    /// Static account keys followed by any addresses loaded from lookup tables
     pub accounts: Vec<String>,
+    pub loaded_addresses: Option<LoadedAddressesResponse>,
+    pub instructions: Vec<DecodedInstruction>,
+    /// Anchor events logged by programs with a registered IDL
+    pub events: Vec<DecodedEvent>,
+    pub log_messages: Vec<String>,
+    pub balance_changes: Vec<BalanceChange>,
+    pub token_balance_changes: Vec<TokenBalanceChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LoadedAddressesResponse {
+    pub writable: Vec<String>,
+    pub readonly: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DecodedInstruction {
+    pub program_id: String,
+    /// Program name, present when the instruction could be decoded
+    pub program: Option<String>,
+    pub accounts: Vec<String>,
+    /// Raw instruction data, base58-encoded
+    pub data: String,
+    pub parsed: Option<serde_json::Value>,
+    pub stack_height: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub inner_instructions: Vec<DecodedInstruction>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DecodedEvent {
+    pub program_id: String,
+    /// Name from the program's IDL
+    pub program: String,
+    pub name: String,
+    pub data: serde_json::Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IdlSummary {
+    pub program_id: String,
+    pub name: String,
+    /// `api` for IDLs registered through `POST /idl`, `file` for `IDL_DIR`
+    pub source: String,
+    pub instructions: usize,
+    pub accounts: usize,
+    pub events: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BalanceChange {
+    pub address: String,
+    pub pre_lamports: u64,
+    pub post_lamports: u64,
+    /// Signed exact SOL change
+    pub change: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenBalanceChange {
+    pub address: String,
+    pub mint: String,
+    pub owner: Option<String>,
+    pub decimals: u8,
+    pub pre_amount: String,
+    pub post_amount: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookResponse {
+    pub id: String,
+    pub url: String,
+    pub cluster: String,
+    pub addresses: Vec<String>,
+    pub created_at: String,
+    /// Only returned when the webhook is created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+/// Where a submitted transaction stands. `sent` is the only state the cluster
+/// doesn't report; `finalized`, `expired` and `failed` are final.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackedStatus {
+    Sent,
+    Processed,
+    Confirmed,
+    Finalized,
+    /// The blockhash expired before the transaction landed; it can no longer execute
+    Expired,
+    Failed,
+}
+
+impl TrackedStatus {
+    pub fn is_final(self) -> bool {
+        matches!(self, TrackedStatus::Finalized | TrackedStatus::Expired | TrackedStatus::Failed)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusChange {
+    pub status: TrackedStatus,
+    pub slot: Option<u64>,
+    pub at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TransactionStatusResponse {
+    pub signature: String,
+    /// Whether the transaction was submitted in async mode and is followed by the tracker
+    pub tracked: bool,
+    pub status: TrackedStatus,
+    pub slot: Option<u64>,
+    pub error: Option<String>,
+    /// Times the tracker sent the transaction, including the first send
+    pub broadcasts: u32,
+    pub last_valid_block_height: Option<u64>,
+    /// Every status change in order, starting with `sent`
+    pub history: Vec<StatusChange>,
 }
 
 // Utility functions
@@ -54,10 +728,212 @@ pub fn validate_pubkey(address: &str) -> Result<Pubkey, AppError> {
         .map_err(|_| AppError::InvalidPublicKey(format!("Invalid public key: {}", address)))
 }
 
-pub fn lamports_to_sol(lamports: u64) -> f64 {
-    lamports as f64 / 1_000_000_000.0
+pub fn parse_commitment(commitment: Option<&str>) -> Result<CommitmentConfig, AppError> {
+    match commitment {
+        None | Some("confirmed") => Ok(CommitmentConfig::confirmed()),
+        Some("processed") => Ok(CommitmentConfig::processed()),
+        Some("finalized") => Ok(CommitmentConfig::finalized()),
+        Some(other) => Err(AppError::BadRequest(format!(
+            "Invalid commitment: {} (expected processed, confirmed or finalized)", other
+        ))),
+    }
+}
+
+pub fn parse_confirmed_commitment(commitment: Option<&str>) -> Result<CommitmentConfig, AppError> {
+    let commitment = parse_commitment(commitment)?;
+    if commitment == CommitmentConfig::processed() {
+        return Err(AppError::BadRequest(
+            "This endpoint supports confirmed or finalized commitment only".to_string(),
+        ));
+    }
+    Ok(commitment)
+}
+
+/// Picks between a decimal amount and its base-unit form; exactly one must be set and non-zero.
+fn resolve_amount(
+    decimal: Option<&str>,
+    base_units: Option<u64>,
+    decimals: u8,
+    base_units_field: &str,
+) -> Result<u64, AppError> {
+    let amount = match (decimal, base_units) {
+        (Some(decimal), None) => parse_decimal_amount(decimal, decimals)?,
+        (None, Some(base_units)) => base_units,
+        (Some(_), Some(_)) => {
+            return Err(AppError::InvalidAmount(format!(
+                "Specify either amount or {}, not both", base_units_field
+            )))
+        }
+        (None, None) => return Err(AppError::InvalidAmount("Amount is required".to_string())),
+    };
+    if amount == 0 {
+        return Err(AppError::InvalidAmount("Amount must be greater than 0".to_string()));
+    }
+    Ok(amount)
+}
+
+/// Formats lamports as an exact decimal SOL string, without trailing zeros.
+pub fn lamports_to_sol(lamports: u64) -> String {
+    format_decimal_amount(lamports, SOL_DECIMALS)
+}
+
+/// Formats a signed lamport change as an exact decimal SOL string (`"-0.5"`).
+pub fn lamport_change_to_sol(change: i64) -> String {
+    let sol = lamports_to_sol(change.unsigned_abs());
+    if change < 0 {
+        format!("-{}", sol)
+    } else {
+        sol
+    }
+}
+
+/// Formats a base-unit amount as an exact decimal string with `decimals` fractional digits,
+/// without trailing zeros.
+pub fn format_decimal_amount(amount: u64, decimals: u8) -> String {
+    let decimals = decimals as usize;
+    let digits = format!("{:0>width$}", amount, width = decimals + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - decimals);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        whole.to_string()
+    } else {
+        format!("{}.{}", whole, fraction)
+    }
+}
+
+/// Parses a decimal string into base units by shifting the decimal point as text, so no
+/// precision is lost. Rejects more than `decimals` fractional digits and `u64` overflow.
+pub fn parse_decimal_amount(value: &str, decimals: u8) -> Result<u64, AppError> {
+    let (whole, fraction) = match value.split_once('.') {
+        Some((whole, fraction)) => (whole, fraction),
+        None => (value, ""),
+    };
+    if whole.is_empty()
+        || !whole.bytes().all(|b| b.is_ascii_digit())
+        || !fraction.bytes().all(|b| b.is_ascii_digit())
+        || (value.contains('.') && fraction.is_empty())
+    {
+        return Err(AppError::InvalidAmount(format!("Invalid amount: {}", value)));
+    }
+    if fraction.len() > decimals as usize {
+        return Err(AppError::InvalidAmount(format!(
+            "Amount supports at most {} decimal places: {}", decimals, value
+        )));
+    }
+    
+    // Only digits remain, so a parse failure can only mean overflow
+    format!("{}{:0<width$}", whole, fraction, width = decimals as usize)
+        .parse()
+        .map_err(|_| AppError::InvalidAmount(format!("Amount overflows u64: {}", value)))
+}
+
+pub fn parse_signature(signature: &str) -> Result<Signature, AppError> {
+    Signature::from_str(signature)
+        .map_err(|_| AppError::InvalidSignature(format!("Invalid signature: {}", signature)))
 }
 
-pub fn sol_to_lamports(sol: f64) -> u64 {
-    (sol * 1_000_000_000.0) as u64
-} 
\ No newline at end of file
+/// A signature as wallets hand it back: 64 bytes, base58 or base64
+pub fn decode_signature(encoded: &str) -> Result<Signature, AppError> {
+    Signature::from_str(encoded)
+        .ok()
+        .or_else(|| {
+            let bytes = BASE64_STANDARD.decode(encoded).ok()?;
+            Signature::try_from(bytes.as_slice()).ok()
+        })
+        .ok_or_else(|| AppError::InvalidSignature("Signature must be 64 bytes, base58 or base64".to_string()))
+}
+
+/// `bytes` random bytes from the thread-local CSPRNG, hex-encoded
+pub fn random_hex(bytes: usize) -> String {
+    hex::encode((0..bytes).map(|_| rand::random::<u8>()).collect::<Vec<_>>())
+}
+
+pub fn encode_transaction(transaction: &Transaction) -> Result<String, AppError> {
+    let bytes = bincode::serialize(transaction)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize transaction: {}", e)))?;
+    Ok(BASE64_STANDARD.encode(bytes))
+}
+
+pub fn decode_transaction(encoded: &str) -> Result<Transaction, AppError> {
+    let bytes = BASE64_STANDARD
+        .decode(encoded)
+        .map_err(|_| AppError::BadRequest("Transaction is not valid base64".to_string()))?;
+    bincode::deserialize(&bytes)
+        .map_err(|_| AppError::BadRequest("Transaction could not be deserialized".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::native_token::LAMPORTS_PER_SOL;
+
+    #[test]
+    fn parses_decimal_sol_exactly() {
+        assert_eq!(parse_decimal_amount("0.3", SOL_DECIMALS).unwrap(), 300_000_000);
+        assert_eq!(parse_decimal_amount("1", SOL_DECIMALS).unwrap(), LAMPORTS_PER_SOL);
+        assert_eq!(parse_decimal_amount("0.000000001", SOL_DECIMALS).unwrap(), 1);
+        assert_eq!(parse_decimal_amount("12.5", SOL_DECIMALS).unwrap(), 12_500_000_000);
+        assert_eq!(parse_decimal_amount("18446744073.709551615", SOL_DECIMALS).unwrap(), u64::MAX);
+    }
+
+    #[test]
+    fn rejects_invalid_sol_amounts() {
+        for bad in ["", ".5", "5.", "-1", "1e9", "0.1234567891", "1.2.3", " 1", "18446744073.709551616", "99999999999999999999"] {
+            assert!(parse_decimal_amount(bad, SOL_DECIMALS).is_err(), "accepted {:?}", bad);
+        }
+    }
+
+    #[test]
+    fn formats_lamports_exactly() {
+        assert_eq!(lamports_to_sol(0), "0");
+        assert_eq!(lamports_to_sol(1), "0.000000001");
+        assert_eq!(lamports_to_sol(300_000_000), "0.3");
+        assert_eq!(lamports_to_sol(LAMPORTS_PER_SOL), "1");
+        assert_eq!(lamports_to_sol(u64::MAX), "18446744073.709551615");
+    }
+
+    #[test]
+    fn token_amounts_respect_mint_decimals() {
+        assert_eq!(parse_decimal_amount("1.5", 6).unwrap(), 1_500_000);
+        assert_eq!(parse_decimal_amount("42", 0).unwrap(), 42);
+        assert!(parse_decimal_amount("0.5", 0).is_err());
+        assert!(parse_decimal_amount("0.0000001", 6).is_err());
+        assert_eq!(format_decimal_amount(1_500_000, 6), "1.5");
+        assert_eq!(format_decimal_amount(42, 0), "42");
+        assert_eq!(format_decimal_amount(7, 2), "0.07");
+    }
+
+    #[test]
+    fn formats_signed_lamport_changes() {
+        assert_eq!(lamport_change_to_sol(-500_000_000), "-0.5");
+        assert_eq!(lamport_change_to_sol(5_000), "0.000005");
+        assert_eq!(lamport_change_to_sol(i64::MIN), "-9223372036.854775808");
+    }
+
+    #[test]
+    fn amount_conversions_round_trip() {
+        let samples = [0, 1, 9, 10, 299_999_999, 300_000_000, LAMPORTS_PER_SOL, LAMPORTS_PER_SOL + 1, u64::MAX / 3, u64::MAX];
+        for lamports in samples {
+            assert_eq!(parse_decimal_amount(&lamports_to_sol(lamports), SOL_DECIMALS).unwrap(), lamports);
+        }
+        for sol in ["0.1", "0.3", "2", "123.456789", "0.000000001"] {
+            assert_eq!(lamports_to_sol(parse_decimal_amount(sol, SOL_DECIMALS).unwrap()), sol);
+        }
+        for decimals in [0, 2, 6, 9, 19] {
+            for amount in samples {
+                assert_eq!(parse_decimal_amount(&format_decimal_amount(amount, decimals), decimals).unwrap(), amount);
+            }
+        }
+    }
+
+    #[test]
+    fn amount_spec_requires_exactly_one_positive_value() {
+        let spec = |amount: Option<&str>, lamports| AmountSpec { amount: amount.map(str::to_string), lamports };
+        assert_eq!(spec(Some("0.3"), None).to_lamports().unwrap(), 300_000_000);
+        assert_eq!(spec(None, Some(5)).to_lamports().unwrap(), 5);
+        assert!(spec(Some("0.3"), Some(5)).to_lamports().is_err());
+        assert!(spec(None, None).to_lamports().is_err());
+        assert!(spec(Some("0"), None).to_lamports().is_err());
+        assert!(spec(None, Some(0)).to_lamports().is_err());
+    }
+}
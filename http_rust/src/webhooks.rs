@@ -0,0 +1,759 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use hmac::{Hmac, Mac};
+use redb::TableDefinition;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use solana_client::{
+    rpc_client::GetConfirmedSignaturesForAddress2Config,
+    rpc_response::RpcConfirmedTransactionStatusWithSignature,
+};
+use solana_sdk::{commitment_config::CommitmentConfig, signature::Signature};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tracing::{info, warn};
+
+use crate::{
+    auth::{Caller, Scope},
+    cluster::{Cluster, ClusterRpc},
+    error::AppError,
+    store::{Store, Table},
+    types::*,
+    AppState,
+};
+
+const WEBHOOKS: Table = TableDefinition::new("webhooks");
+/// Newest signature seen per `cluster:address`
+const CURSORS: Table = TableDefinition::new("webhook_cursors");
+const DELIVERIES: Table = TableDefinition::new("webhook_deliveries");
+const DEAD_LETTERS: Table = TableDefinition::new("webhook_dead_letters");
+
+const MAX_WEBHOOK_ADDRESSES: usize = 100;
+const MIN_SECRET_LEN: usize = 16;
+/// Signatures fetched per address and scan; bursts beyond this between scans are skipped
+const SCAN_LIMIT: usize = 1000;
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_CONCURRENT_DELIVERIES: usize = 16;
+/// Attempts before a delivery moves to the dead-letter list
+const MAX_DELIVERY_ATTEMPTS: u32 = 8;
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(5);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(3600);
+
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature";
+pub const TIMESTAMP_HEADER: &str = "x-webhook-timestamp";
+
+/// Where webhooks may deliver. Hosts that resolve to loopback, private, link-local
+/// (cloud metadata included) or otherwise non-public addresses are refused, unless
+/// listed in `WEBHOOK_ALLOWED_HOSTS`.
+#[derive(Debug, Default)]
+pub struct Destinations {
+    allowed_hosts: BTreeSet<String>,
+}
+
+impl Destinations {
+    pub fn new(allowed_hosts: impl IntoIterator<Item = String>) -> Self {
+        Destinations { allowed_hosts: allowed_hosts.into_iter().map(|host| host.to_lowercase()).collect() }
+    }
+
+    fn allows(&self, host: &str) -> bool {
+        self.allowed_hosts.contains(&host.to_lowercase())
+    }
+
+    /// Checks a URL at registration, resolving its host
+    async fn check(&self, url: &str) -> Result<(), AppError> {
+        let refuse = |reason: &str| AppError::BadRequest(format!("Webhook URL {} {}", url, reason));
+        let parsed = reqwest::Url::parse(url).map_err(|_| refuse("is not a valid URL"))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(refuse("must be http(s)"));
+        }
+        let host = parsed.host_str().ok_or_else(|| refuse("has no host"))?;
+        if self.allows(host) {
+            return Ok(());
+        }
+        if let Some(ip) = literal_ip(host) {
+            return if is_public(ip) { Ok(()) } else { Err(refuse("points at a non-public address")) };
+        }
+        let port = parsed.port_or_known_default().unwrap_or(443);
+        let addrs: Vec<_> = tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|_| refuse("does not resolve"))?
+            .collect();
+        if addrs.is_empty() || addrs.iter().any(|addr| !is_public(addr.ip())) {
+            return Err(refuse("resolves to a non-public address"));
+        }
+        Ok(())
+    }
+
+    /// HTTP client for deliveries. Host names are re-resolved through the same check
+    /// on every connection, so a record changed after registration can't reach an
+    /// internal address, and redirects aren't followed.
+    fn client(self: Arc<Self>) -> reqwest::Client {
+        reqwest::Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .dns_resolver(self)
+            .build()
+            .expect("HTTP client configuration is static")
+    }
+
+    // IP literals skip the resolver; the URL can't have changed since registration,
+    // but the allow-list may have
+    fn permits_literal(&self, url: &str) -> bool {
+        let Some(host) = reqwest::Url::parse(url).ok().and_then(|url| url.host_str().map(str::to_string)) else {
+            return false;
+        };
+        self.allows(&host) || literal_ip(&host).is_none_or(is_public)
+    }
+}
+
+impl reqwest::dns::Resolve for Destinations {
+    fn resolve(&self, name: hyper::client::connect::dns::Name) -> reqwest::dns::Resolving {
+        let allowed = self.allows(name.as_str());
+        Box::pin(async move {
+            let addrs: Vec<_> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| allowed || is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+// `[::1]` style hosts come with brackets
+fn literal_ip(host: &str) -> Option<IpAddr> {
+    host.trim_start_matches('[').trim_end_matches(']').parse().ok()
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_v4(ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        // Shared address space, where some clouds put their metadata service
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 198 && (18..20).contains(&b))
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local, link-local and documentation ranges
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80
+        || first == 0x2001 && ip.segments()[1] == 0x0db8)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    pub cluster: String,
+    pub addresses: Vec<String>,
+    pub secret: String,
+    /// API key that registered the webhook, if authentication is enabled
+    pub owner: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Webhook {
+    fn response(&self, with_secret: bool) -> WebhookResponse {
+        WebhookResponse {
+            id: self.id.clone(),
+            url: self.url.clone(),
+            cluster: self.cluster.clone(),
+            addresses: self.addresses.clone(),
+            created_at: self.created_at.to_rfc3339(),
+            secret: with_secret.then(|| self.secret.clone()),
+        }
+    }
+
+    fn visible_to(&self, caller: &Caller) -> bool {
+        match &caller.0 {
+            None => true,
+            Some(api_key) => api_key.has_scope(Scope::Admin) || self.owner.as_deref() == Some(api_key.name.as_str()),
+        }
+    }
+}
+
+/// One payload on its way to a webhook, or in the dead-letter list once retries ran out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Delivery {
+    pub id: String,
+    pub webhook_id: String,
+    pub payload: Value,
+    pub attempts: u32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Cursor {
+    /// `None` until the address has any transaction
+    last_signature: Option<String>,
+}
+
+// Time-ordered so deliveries list oldest first, including several within one millisecond
+fn delivery_id() -> String {
+    static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+    let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    format!("{:016}-{:010}", Utc::now().timestamp_millis(), sequence % 10_000_000_000)
+}
+
+/// Hex HMAC-SHA256 of `message`; receivers recompute it over `{timestamp}.{body}`.
+pub fn sign(secret: &str, message: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(message);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn retry_delay(attempts: u32) -> Duration {
+    let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+    INITIAL_RETRY_DELAY.saturating_mul(factor).min(MAX_RETRY_DELAY)
+}
+
+fn visible_webhook(store: &Store, id: &str, cluster: &str, caller: &Caller) -> Result<Webhook, AppError> {
+    store
+        .get::<Webhook>(WEBHOOKS, id)?
+        .filter(|webhook| webhook.cluster == cluster && webhook.visible_to(caller))
+        .ok_or_else(|| AppError::NotFound(format!("Webhook {}", id)))
+}
+
+// Register a webhook for transactions touching any of `addresses` on the request's cluster
+pub async fn create_webhook(
+    State(state): State<AppState>,
+    ClusterRpc(cluster): ClusterRpc,
+    caller: Caller,
+    Json(req): Json<CreateWebhookRequest>,
+) -> Result<(StatusCode, Json<WebhookResponse>), AppError> {
+    if req.addresses.is_empty() || req.addresses.len() > MAX_WEBHOOK_ADDRESSES {
+        return Err(AppError::BadRequest(format!(
+            "A webhook watches between 1 and {} addresses",
+            MAX_WEBHOOK_ADDRESSES
+        )));
+    }
+    let addresses: BTreeSet<String> = req
+        .addresses
+        .iter()
+        .map(|address| validate_pubkey(address).map(|pubkey| pubkey.to_string()))
+        .collect::<Result<_, _>>()?;
+    let secret = match req.secret {
+        Some(secret) if secret.len() < MIN_SECRET_LEN => {
+            return Err(AppError::BadRequest(format!("Secret must be at least {} characters", MIN_SECRET_LEN)));
+        }
+        Some(secret) => secret,
+        None => random_hex(32),
+    };
+    state.webhook_destinations.check(&req.url).await?;
+
+    let webhook = Webhook {
+        id: random_hex(16),
+        url: req.url,
+        cluster: cluster.name.clone(),
+        addresses: addresses.into_iter().collect(),
+        secret,
+        owner: caller.0.as_ref().map(|api_key| api_key.name.clone()),
+        created_at: Utc::now(),
+    };
+    state.store.put(WEBHOOKS, &webhook.id, &webhook)?;
+    info!("Registered webhook {} for {} addresses on {}", webhook.id, webhook.addresses.len(), webhook.cluster);
+
+    Ok((StatusCode::CREATED, Json(webhook.response(true))))
+}
+
+pub async fn list_webhooks(
+    State(state): State<AppState>,
+    ClusterRpc(cluster): ClusterRpc,
+    caller: Caller,
+) -> Result<Json<Vec<WebhookResponse>>, AppError> {
+    let webhooks = state
+        .store
+        .list::<Webhook>(WEBHOOKS)?
+        .into_iter()
+        .map(|(_, webhook)| webhook)
+        .filter(|webhook| webhook.cluster == cluster.name && webhook.visible_to(&caller))
+        .map(|webhook| webhook.response(false))
+        .collect();
+    Ok(Json(webhooks))
+}
+
+pub async fn get_webhook(
+    State(state): State<AppState>,
+    ClusterRpc(cluster): ClusterRpc,
+    caller: Caller,
+    Path(id): Path<String>,
+) -> Result<Json<WebhookResponse>, AppError> {
+    let webhook = visible_webhook(&state.store, &id, &cluster.name, &caller)?;
+    Ok(Json(webhook.response(false)))
+}
+
+// Drops the webhook's dead letters with it; pending deliveries are dropped by the delivery task
+pub async fn delete_webhook(
+    State(state): State<AppState>,
+    ClusterRpc(cluster): ClusterRpc,
+    caller: Caller,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    visible_webhook(&state.store, &id, &cluster.name, &caller)?;
+    let dead_letters: Vec<String> = state
+        .store
+        .list::<Delivery>(DEAD_LETTERS)?
+        .into_iter()
+        .filter(|(_, delivery)| delivery.webhook_id == id)
+        .map(|(delivery_id, _)| delivery_id)
+        .collect();
+    state.store.write(|txn| {
+        for delivery_id in &dead_letters {
+            txn.remove(DEAD_LETTERS, delivery_id)?;
+        }
+        txn.remove(WEBHOOKS, &id)
+    })?;
+    info!("Deleted webhook {}", id);
+    Ok(Json(json!({ "id": id, "deleted": true })))
+}
+
+pub async fn list_dead_letters(
+    State(state): State<AppState>,
+    ClusterRpc(cluster): ClusterRpc,
+    caller: Caller,
+) -> Result<Json<Vec<Delivery>>, AppError> {
+    let webhooks: HashMap<String, Webhook> = state.store.list(WEBHOOKS)?.into_iter().collect();
+    let dead_letters = state
+        .store
+        .list::<Delivery>(DEAD_LETTERS)?
+        .into_iter()
+        .map(|(_, delivery)| delivery)
+        .filter(|delivery| {
+            webhooks
+                .get(&delivery.webhook_id)
+                .is_some_and(|webhook| webhook.cluster == cluster.name && webhook.visible_to(&caller))
+        })
+        .collect();
+    Ok(Json(dead_letters))
+}
+
+// Move a dead letter back to the delivery queue with a fresh set of attempts
+pub async fn retry_dead_letter(
+    State(state): State<AppState>,
+    ClusterRpc(cluster): ClusterRpc,
+    caller: Caller,
+    Path(id): Path<String>,
+) -> Result<Json<Delivery>, AppError> {
+    let not_found = || AppError::NotFound(format!("Dead letter {}", id));
+    let mut delivery: Delivery = state.store.get(DEAD_LETTERS, &id)?.ok_or_else(not_found)?;
+    visible_webhook(&state.store, &delivery.webhook_id, &cluster.name, &caller).map_err(|_| not_found())?;
+
+    delivery.attempts = 0;
+    delivery.next_attempt_at = Utc::now();
+    state.store.write(|txn| {
+        txn.remove(DEAD_LETTERS, &id)?;
+        txn.put(DELIVERIES, &id, &delivery)
+    })?;
+    Ok(Json(delivery))
+}
+
+/// Polls every watched address forever and queues a delivery per new transaction.
+pub async fn detect(state: AppState, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        if let Err(err) = detect_once(&state).await {
+            warn!("Webhook scan failed: {}", err);
+        }
+    }
+}
+
+async fn detect_once(state: &AppState) -> Result<(), AppError> {
+    let mut watched: BTreeMap<(String, String), Vec<Webhook>> = BTreeMap::new();
+    for (_, webhook) in state.store.list::<Webhook>(WEBHOOKS)? {
+        for address in &webhook.addresses {
+            watched.entry((webhook.cluster.clone(), address.clone())).or_default().push(webhook.clone());
+        }
+    }
+
+    for ((cluster_name, address), webhooks) in &watched {
+        let Some(cluster) = state.clusters.get(cluster_name) else { continue };
+        if let Err(err) = scan_address(&state.store, cluster, address, webhooks).await {
+            warn!("Webhook scan of {} on {} failed: {}", address, cluster_name, err);
+        }
+    }
+
+    // Forget cursors of addresses no webhook watches any more
+    for (key, _) in state.store.list::<Cursor>(CURSORS)? {
+        let watched_key = key.split_once(':').map(|(cluster, address)| (cluster.to_string(), address.to_string()));
+        if !watched_key.is_some_and(|watched_key| watched.contains_key(&watched_key)) {
+            state.store.remove(CURSORS, &key)?;
+        }
+    }
+    Ok(())
+}
+
+async fn scan_address(store: &Store, cluster: &Cluster, address: &str, webhooks: &[Webhook]) -> Result<(), AppError> {
+    let pubkey = validate_pubkey(address)?;
+    let cursor_key = format!("{}:{}", cluster.name, address);
+    let cursor: Option<Cursor> = store.get(CURSORS, &cursor_key)?;
+    let until = cursor
+        .as_ref()
+        .and_then(|cursor| cursor.last_signature.as_deref())
+        .and_then(|signature| signature.parse::<Signature>().ok());
+
+    let config = GetConfirmedSignaturesForAddress2Config {
+        before: None,
+        until,
+        limit: Some(SCAN_LIMIT),
+        commitment: Some(CommitmentConfig::confirmed()),
+    };
+    let signatures = cluster.rpc_client.get_signatures_for_address_with_config(&pubkey, config).await?;
+    if signatures.is_empty() && cursor.is_some() {
+        return Ok(());
+    }
+
+    // The first scan only records where history ends; earlier transactions aren't news
+    let deliveries: Vec<Delivery> = match cursor {
+        None => Vec::new(),
+        Some(_) => signatures
+            .iter()
+            .rev()
+            .flat_map(|status| webhooks.iter().map(move |webhook| new_delivery(webhook, address, status)))
+            .collect(),
+    };
+    let cursor = Cursor { last_signature: signatures.first().map(|status| status.signature.clone()) };
+
+    store.write(|txn| {
+        for delivery in &deliveries {
+            txn.put(DELIVERIES, &delivery.id, delivery)?;
+        }
+        txn.put(CURSORS, &cursor_key, &cursor)
+    })?;
+    if !deliveries.is_empty() {
+        info!("Queued {} webhook deliveries for {}", deliveries.len(), address);
+    }
+    Ok(())
+}
+
+fn new_delivery(webhook: &Webhook, address: &str, status: &RpcConfirmedTransactionStatusWithSignature) -> Delivery {
+    let id = delivery_id();
+    let now = Utc::now();
+    Delivery {
+        payload: json!({
+            "delivery_id": id,
+            "webhook_id": webhook.id,
+            "event": "transaction",
+            "cluster": webhook.cluster,
+            "address": address,
+            "signature": status.signature,
+            "slot": status.slot,
+            "block_time": status.block_time,
+            "success": status.err.is_none(),
+            "error": status.err.as_ref().map(|err| err.to_string()),
+            "memo": status.memo,
+        }),
+        id,
+        webhook_id: webhook.id.clone(),
+        attempts: 0,
+        next_attempt_at: now,
+        last_error: None,
+        created_at: now,
+    }
+}
+
+/// Sends due deliveries forever, retrying failures with exponential backoff.
+pub async fn deliver(store: Arc<Store>, destinations: Arc<Destinations>, interval: Duration) {
+    let http = destinations.clone().client();
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        if let Err(err) = deliver_due(&store, &destinations, &http).await {
+            warn!("Webhook delivery failed: {}", err);
+        }
+    }
+}
+
+async fn deliver_due(store: &Store, destinations: &Destinations, http: &reqwest::Client) -> Result<(), AppError> {
+    let now = Utc::now();
+    let webhooks: HashMap<String, Webhook> = store.list(WEBHOOKS)?.into_iter().collect();
+    let mut due = Vec::new();
+    for (id, delivery) in store.list::<Delivery>(DELIVERIES)? {
+        match webhooks.get(&delivery.webhook_id) {
+            None => {
+                store.remove(DELIVERIES, &id)?;
+            }
+            Some(webhook) if delivery.next_attempt_at <= now => due.push((webhook, delivery)),
+            Some(_) => {}
+        }
+    }
+
+    futures::stream::iter(due)
+        .for_each_concurrent(MAX_CONCURRENT_DELIVERIES, |(webhook, delivery)| async move {
+            if let Err(err) = attempt(store, destinations, http, webhook, delivery).await {
+                warn!("Failed to record webhook delivery: {}", err);
+            }
+        })
+        .await;
+    Ok(())
+}
+
+async fn attempt(
+    store: &Store,
+    destinations: &Destinations,
+    http: &reqwest::Client,
+    webhook: &Webhook,
+    mut delivery: Delivery,
+) -> Result<(), AppError> {
+    let body = delivery.payload.to_string();
+    let timestamp = Utc::now().timestamp().to_string();
+    let signature = sign(&webhook.secret, format!("{}.{}", timestamp, body).as_bytes());
+
+    let result = if !destinations.permits_literal(&webhook.url) {
+        Err("Destination is not a public address".to_string())
+    } else {
+        http.post(&webhook.url)
+            .header("content-type", "application/json")
+            .header("x-webhook-id", &webhook.id)
+            .header(TIMESTAMP_HEADER, &timestamp)
+            .header(SIGNATURE_HEADER, format!("sha256={}", signature))
+            .body(body)
+            .send()
+            .await
+            .map_err(|err| err.to_string())
+            .and_then(|response| match response.status() {
+                status if status.is_success() => Ok(()),
+                status => Err(format!("HTTP {}", status)),
+            })
+    };
+
+    match result {
+        Ok(()) => {
+            store.remove(DELIVERIES, &delivery.id)?;
+        }
+        Err(err) => {
+            delivery.attempts += 1;
+            delivery.last_error = Some(err);
+            if delivery.attempts >= MAX_DELIVERY_ATTEMPTS {
+                warn!("Webhook delivery {} to {} dead-lettered", delivery.id, webhook.url);
+                store.write(|txn| {
+                    txn.remove(DELIVERIES, &delivery.id)?;
+                    txn.put(DEAD_LETTERS, &delivery.id, &delivery)
+                })?;
+            } else {
+                let delay = retry_delay(delivery.attempts);
+                delivery.next_attempt_at = Utc::now() + chrono::Duration::from_std(delay).unwrap_or_default();
+                store.put(DELIVERIES, &delivery.id, &delivery)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool::RpcPool;
+    use async_trait::async_trait;
+    use axum::{http::HeaderMap, routing::post, Router};
+    use solana_client::{
+        client_error::Result as ClientResult,
+        rpc_request::RpcRequest,
+        rpc_sender::{RpcSender, RpcTransportStats},
+    };
+    use std::sync::Mutex;
+
+    /// Answers `getSignaturesForAddress` with whatever the test put in `signatures`.
+    struct HistorySender {
+        signatures: Arc<Mutex<Vec<Value>>>,
+    }
+
+    #[async_trait]
+    impl RpcSender for HistorySender {
+        async fn send(&self, request: RpcRequest, _params: Value) -> ClientResult<Value> {
+            match request {
+                RpcRequest::GetVersion => Ok(json!({ "solana-core": "1.18.26" })),
+                _ => Ok(Value::Array(self.signatures.lock().unwrap().clone())),
+            }
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            RpcTransportStats::default()
+        }
+
+        fn url(&self) -> String {
+            "history".to_string()
+        }
+    }
+
+    fn status(signature: &Signature, slot: u64) -> Value {
+        json!({ "signature": signature.to_string(), "slot": slot, "err": null, "memo": null, "blockTime": null })
+    }
+
+    fn webhook(url: &str) -> Webhook {
+        Webhook {
+            id: random_hex(16),
+            url: url.to_string(),
+            cluster: "devnet".to_string(),
+            addresses: vec![solana_sdk::pubkey::Pubkey::new_unique().to_string()],
+            secret: "0123456789abcdef".to_string(),
+            owner: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn signs_with_hmac_sha256() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_non_public_destinations() {
+        let destinations = Destinations::default();
+        for url in [
+            "http://127.0.0.1/hook",
+            "http://localhost:8080/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://10.0.0.1/hook",
+            "http://100.100.100.200/hook",
+            "http://[::1]/hook",
+            "http://[::ffff:192.168.1.1]/hook",
+            "http://[fd00:ec2::254]/hook",
+            "ftp://93.184.215.14/hook",
+        ] {
+            assert!(destinations.check(url).await.is_err(), "{}", url);
+        }
+        assert!(destinations.check("https://93.184.215.14/hook").await.is_ok());
+
+        let allowed = Destinations::new(["LocalHost".to_string()]);
+        assert!(allowed.check("http://localhost:8080/hook").await.is_ok());
+        assert!(allowed.permits_literal("http://localhost:8080/hook"));
+        assert!(!destinations.permits_literal("http://127.0.0.1:8080/hook"));
+    }
+
+    #[tokio::test]
+    async fn deleting_a_webhook_drops_its_dead_letters() {
+        let state = crate::tests::test_state(&["devnet"], crate::ratelimit::RateLimits::unlimited());
+        let (kept, deleted) = (webhook("https://a"), webhook("https://b"));
+        for webhook in [&kept, &deleted] {
+            state.store.put(WEBHOOKS, &webhook.id, webhook).unwrap();
+            let delivery = new_delivery(webhook, &webhook.addresses[0], &serde_json::from_value(status(&Signature::new_unique(), 1)).unwrap());
+            state.store.put(DEAD_LETTERS, &delivery.id, &delivery).unwrap();
+        }
+
+        let cluster = state.clusters["devnet"].clone();
+        let deleted_response = delete_webhook(State(state.clone()), ClusterRpc(cluster), Caller(None), Path(deleted.id.clone())).await.unwrap();
+        assert_eq!(deleted_response.0["deleted"], true);
+
+        let dead: Vec<(String, Delivery)> = state.store.list(DEAD_LETTERS).unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].1.webhook_id, kept.id);
+    }
+
+    #[test]
+    fn backs_off_exponentially() {
+        assert_eq!(retry_delay(1), Duration::from_secs(5));
+        assert_eq!(retry_delay(2), Duration::from_secs(10));
+        assert_eq!(retry_delay(4), Duration::from_secs(40));
+        assert_eq!(retry_delay(30), MAX_RETRY_DELAY);
+    }
+
+    #[tokio::test]
+    async fn queues_only_transactions_after_the_first_scan() {
+        let signatures = Arc::new(Mutex::new(vec![status(&Signature::new_unique(), 1)]));
+        let sender = HistorySender { signatures: signatures.clone() };
+        let pool = Arc::new(RpcPool::from_senders(vec![("history".to_string(), Box::new(sender))]));
+        let cluster = Cluster::new("devnet".to_string(), pool, "ws://127.0.0.1:9".to_string());
+        let store = Store::in_memory();
+        let webhooks = [webhook("http://a"), webhook("http://b")];
+        let address = webhooks[0].addresses[0].clone();
+
+        scan_address(&store, &cluster, &address, &webhooks).await.unwrap();
+        assert!(store.list::<Delivery>(DELIVERIES).unwrap().is_empty());
+
+        let (older, newer) = (Signature::new_unique(), Signature::new_unique());
+        *signatures.lock().unwrap() = vec![status(&newer, 3), status(&older, 2)];
+        scan_address(&store, &cluster, &address, &webhooks).await.unwrap();
+
+        let deliveries: Vec<Delivery> = store.list(DELIVERIES).unwrap().into_iter().map(|(_, d)| d).collect();
+        assert_eq!(deliveries.len(), 4);
+        assert_eq!(deliveries[0].payload["signature"], older.to_string());
+        let cursor: Cursor = store.get(CURSORS, &format!("devnet:{}", address)).unwrap().unwrap();
+        assert_eq!(cursor.last_signature, Some(newer.to_string()));
+    }
+
+    #[tokio::test]
+    async fn delivers_signed_payloads_and_dead_letters_failures() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let recorder = received.clone();
+        let receiver = Router::new().route(
+            "/hook",
+            post(move |headers: HeaderMap, body: String| async move {
+                recorder.lock().unwrap().push((headers, body));
+                StatusCode::OK
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, receiver).await.unwrap() });
+
+        let store = Store::in_memory();
+        let destinations = Arc::new(Destinations::new(["127.0.0.1".to_string()]));
+        let http = destinations.clone().client();
+        let good = webhook(&url);
+        let delivery = new_delivery(&good, &good.addresses[0], &serde_json::from_value(status(&Signature::new_unique(), 1)).unwrap());
+        store.put(DELIVERIES, &delivery.id, &delivery).unwrap();
+        attempt(&store, &destinations, &http, &good, delivery).await.unwrap();
+
+        assert!(store.list::<Delivery>(DELIVERIES).unwrap().is_empty());
+        let (headers, body) = received.lock().unwrap().pop().unwrap();
+        let timestamp = headers[TIMESTAMP_HEADER].to_str().unwrap();
+        let expected = format!("sha256={}", sign(&good.secret, format!("{}.{}", timestamp, body).as_bytes()));
+        assert_eq!(headers[SIGNATURE_HEADER], expected.as_str());
+
+        // Nothing listens on port 9: one more failure exhausts the attempts
+        let unreachable = webhook("http://127.0.0.1:9/hook");
+        let mut delivery = new_delivery(&unreachable, &unreachable.addresses[0], &serde_json::from_value(status(&Signature::new_unique(), 1)).unwrap());
+        delivery.attempts = MAX_DELIVERY_ATTEMPTS - 1;
+        store.put(DELIVERIES, &delivery.id, &delivery).unwrap();
+        attempt(&store, &destinations, &http, &unreachable, delivery).await.unwrap();
+
+        assert!(store.list::<Delivery>(DELIVERIES).unwrap().is_empty());
+        let dead: Vec<(String, Delivery)> = store.list(DEAD_LETTERS).unwrap();
+        assert_eq!(dead[0].1.attempts, MAX_DELIVERY_ATTEMPTS);
+        assert!(dead[0].1.last_error.is_some());
+    }
+}